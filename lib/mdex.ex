@@ -8,6 +8,18 @@ defmodule MDEx do
 
   alias MDEx.Native
 
+  @version Mix.Project.config()[:version]
+
+  @typedoc "Reasons `to_html/2` and `to_html_with_metadata/2` may fail with."
+  @type render_error ::
+          :input_too_large
+          | :output_too_large
+          | :pathological_input
+          | :timeout
+          | {:duplicate_heading_id, String.t()}
+          | {:unknown_theme, String.t(), [String.t()]}
+          | String.t()
+
   @doc """
   Convert `markdown` to HTML.
 
@@ -36,12 +48,190 @@ defmodule MDEx do
   * `:parse` - https://docs.rs/comrak/latest/comrak/struct.ParseOptions.html
   * `:render` - https://docs.rs/comrak/latest/comrak/struct.RenderOptions.html
   * `:features` - see the available options below
+  * `:preset` (default `nil`) - render with a predefined set of options for a specific use case, see the presets below
+
+  The `:shortcodes` extension, which renders emoji shortcodes like `:rocket:`, is also available.
 
   ### Features Options
 
   * `:sanitize` (default `false`) - sanitize output using [ammonia](https://crates.io/crates/ammonia).\n Recommended if passing `render: [unsafe_: true]`
   * `:syntax_highlight_theme` (default `"onedark"`) - syntax highlight code fences using [autumn themes](https://github.com/leandrocp/autumn/tree/main/priv/themes),
   you should pass the filename without special chars and without extension, for example you should pass `syntax_highlight_theme: "adwaita_dark"` to use the [Adwaita Dark](https://github.com/leandrocp/autumn/blob/main/priv/themes/adwaita-dark.toml) theme.
+  Unknown themes return `{:error, {:unknown_theme, name, suggestions}}` with the names of similar themes, if any.
+  * `:max_input_bytes` (default `nil`) - return `{:error, :input_too_large}` without rendering when the markdown exceeds this size in bytes.
+  * `:max_output_bytes` (default `nil`) - stop rendering and return `{:error, :output_too_large}` when the generated HTML exceeds this size in bytes.
+  Useful to protect against small inputs that expand into huge documents.
+  * `:timeout` (default `nil`) - stop rendering and return `{:error, :timeout}` when rendering takes longer than this, in milliseconds.
+  Checked after parsing and while writing the HTML, so a render can run over by the time it takes to parse the document.
+  * `:audit` (default `false`) - remove `<script>` elements, event handler attributes like `onclick` and `javascript:` urls
+  from the output even when `render: [unsafe_: true]`, a tripwire for documents that need raw HTML. Use `to_html_with_metadata/2`
+  to get the list of violations.
+  * `:document_dir` (default `nil`) - directory of the markdown document, used to resolve relative link and image urls, for example
+  `document_dir: "docs/guides"` turns `../images/logo.png` into `docs/images/logo.png`. Urls in raw HTML are not changed.
+  * `:responsive_images` (default `nil`) - add `srcset` and `sizes` attributes to images so browsers pick the best size:
+    * `:widths` (default `[480, 960, 1440]`) - widths in pixels of the image variants.
+    * `:url` (default `"{url}?w={width}"`) - url of each variant, where `{url}` is replaced by the image url and `{width}` by the width.
+    * `:sizes` (default `nil`) - value of the `sizes` attribute, for example `"(max-width: 960px) 100vw, 960px"`.
+  * `:video_embeds` (default `nil`) - embed videos from paragraphs containing only a video url, like `https://youtu.be/ID`,
+  or an image like `![Title](youtube:ID)` or `![Title](vimeo:ID)`, using the privacy friendly `youtube-nocookie.com` and `player.vimeo.com` players.
+  Embeds are kept by `sanitize: true` as long as they point to an allowed provider:
+    * `:providers` (default `[:youtube, :vimeo]`) - allowed video providers.
+    * `:wrapper_class` (default `"video-embed"`) - class of the `<div>` wrapping embeds, or `nil` to skip the wrapper.
+  * `:abbreviations` (default `false`) - support Markdown Extra abbreviation definitions like `*[HTML]: HyperText Markup Language`,
+  on a line by themselves, which are removed from the output and wrap every whole word occurrence of the abbreviation in `<abbr title="...">`.
+  * `:base_url` (default `nil`) - absolute url to resolve relative link and image urls against when the output is sanitized,
+  for example `base_url: "https://example.com/blog/"` turns `../about` into `https://example.com/about`.
+  * `:relative_urls` (default `[]`) - list of `{pattern, replacement}` rules to rewrite relative link and image urls when the output is sanitized,
+  for example `[{"/assets/", "https://cdn.example.com/"}, {~r/^(.+)\\.md$/, "$1.html"}]`. The first rule that matches rewrites the url:
+  string patterns replace the start of the url and regexes replace their first match, where the replacement may refer to groups like `$1`.
+  Urls no rule matches are resolved against `:base_url` when set, and otherwise kept, or removed by `preset: :feed`.
+  Regexes follow the same syntax as `:replacements`, invalid or too large regexes return `{:error, reason}`.
+  * `:attribute_rules` (default `[]`) - list of `{tag, attribute, action}` rules applied to the attributes kept when the output is sanitized,
+  for example `[{"img", "src", {:rewrite, "https://cdn.example.com/images/{value}"}}, {"span", "style", :keep}, {"*", "style", :drop}]`.
+  The first rule matching the tag, or `"*"` for any tag, and the attribute decides: `:keep` keeps the attribute, `:drop` removes it,
+  and `{:rewrite, template}` replaces its value with the template, where `{value}` is replaced by the current value.
+  Url attributes like `href` and `src` are only rewritten when relative, and are removed when the rewritten url has a scheme
+  the sanitizer doesn't allow or another host than the template.
+  Rules can't keep attributes the sanitizer removes.
+  * `:omit` (default `[]`) - node types removed with their content from the output, for text only contexts like feed summaries or notifications,
+  for example `[:image, :html_block]`. Paragraphs, links and list items left empty are removed as well. Supported types are `:block_quote`, `:list`,
+  `:code_block`, `:html_block`, `:heading`, `:thematic_break`, `:table`, `:footnote_definition`, `:code`, `:html_inline`, `:link`, `:image`
+  and `:footnote_reference`.
+  * `:replacements` (default `[]`) - list of `{pattern, replacement}` applied in order to the text of the document, skipping code, raw HTML
+  and the text of links, for example `[{"(tm)", "™"}, {~r/\\bK8s\\b/, "Kubernetes"}]`. Patterns are strings or regexes, where the replacement
+  may refer to groups like `$1`. Regexes use the syntax of the Rust [regex](https://docs.rs/regex) crate, which matches in linear time,
+  with modifiers written inline like `(?i)`. Invalid or too large regexes return `{:error, reason}`.
+  * `:typography` (default `nil`) - normalize the whitespace of text, leaving code and raw HTML untouched.
+  Pass `typography: []` to apply every rule or disable rules individually, blank lines are already collapsed by the markdown parser:
+    * `:collapse_spaces` (default `true`) - replace runs of spaces and tabs with a single space.
+    * `:trim_trailing` (default `true`) - remove spaces and tabs at the end of lines.
+    * `:non_breaking_spaces` (default `true`) - replace non-breaking spaces, including narrow and figure spaces, with regular spaces.
+    * `:space_before_punctuation` (default `true`) - remove spaces before `,`, `.`, `;`, `:`, `!`, `?` and `)` ending a word,
+    so `Hello , world !` becomes `Hello, world!` while `costs .5` is kept.
+  * `:short_links` (default `nil`) - shorten the text of links written as bare urls, like GitHub does for pasted links,
+  usually with `extension: [autolink: true]`. Urls with a query or fragment are kept:
+    * `:rules` (default `[:github_issues, :github_pull_requests, :github_commits]`) - issue and pull request urls become `org/repo#123`
+    and commit urls the first 7 characters of the sha.
+  * `:detect_language` (default `false`) - detect the natural language of the document, returned as `:language` by `to_html_with_metadata/2`.
+  * `:lang` (default `nil`) - add a `lang` attribute so screen readers pronounce the content correctly, to the `:wrapper` element
+  or to a `<div>` wrapping the fragment when there's none. Pass `lang: []` to detect the language, which is skipped when the detection
+  isn't reliable:
+    * `:tag` (default `nil`) - the language tag, like `"pt-BR"`, instead of detecting it.
+    * `:per_block` (default `false`) - also add a `lang` attribute to top level paragraphs reliably detected in another language.
+  * `:internal_links` (default `nil`) - rewrite the links to the site, for example to let LiveView `navigate` handle them,
+  leaving urls in code, text and raw HTML untouched. Note that `sanitize: true` removes the added attributes:
+    * `:prefixes` (default `["/"]`) - url prefixes of the links to the site, protocol relative urls like `//example.com` are never rewritten.
+    * `:path_prefix` (default `nil`) - path added in front of the urls, for example `"/docs"` turns `/guides` into `/docs/guides`.
+    * `:attributes` (default `[]`) - attributes added to the links, as a keyword list or map,
+    for example `%{"data-phx-link" => "redirect", "data-phx-link-state" => "push"}`.
+  * `:link_titles` (default `false`) - show where links go on hover, without JavaScript: links without a title get their destination
+  as `title`, except autolinks, and footnote references get the text of the footnote, with `extension: [footnotes: true]`.
+  * `:before_html`, `:after_html` (default `nil`) - HTML added before and after the rendered fragment, inside the `:wrapper` if set.
+  Added after sanitization, so only pass trusted HTML.
+  * `:wrapper` (default `nil`) - wrap the rendered fragment in an element, as a `{tag, attrs}` tuple,
+  for example `wrapper: {"article", class: "prose"}`. Attribute values are escaped.
+  * `:data_images` (default `nil`) - allow images with base64 `data:` urls, like `data:image/png;base64,...`, when they pass these checks,
+  and clear the source of the ones that don't. Also allows them through `sanitize: true`, which otherwise removes every `data:` url:
+    * `:max_bytes` (default `16_384`) - maximum size of the decoded image.
+    * `:mime_types` (default `["image/png", "image/jpeg", "image/gif", "image/webp"]`) - allowed types. Note that other types
+    are still removed by comrak unless `render: [unsafe_: true]`.
+  * `:cache` (default `nil`) - a reference created by `new_cache/1` to reuse the HTML of documents rendered with the same options.
+  * `:metrics` (default `nil`) - a reference created by `new_metrics/0` to collect rendering stats, see `get_metrics/1`.
+  * `:description_list` (default `nil`) - customize the output of `extension: [description_lists: true]`:
+    * `:dl_class`, `:dt_class`, `:dd_class` - class added to the `<dl>`, `<dt>` and `<dd>` elements.
+    Note that classes are removed by `sanitize: true`.
+    * `:tight` (default `false`) - render terms and details with a single paragraph without the `<p>` wrapper.
+  * `:entity_encoding` (default `:utf8`) - how non-ASCII characters are written to the output:
+    * `:utf8` - as is.
+    * `:numeric` - as numeric character references, for example `&#233;`.
+    * `:named` - as named entities when HTML defines one, for example `&eacute;`, otherwise as numeric references.
+  * `:heading_lint` (default `nil`) - report headings as warnings in `to_html_with_metadata/2`, complementing the id collisions
+  of `:heading_ids`. Pass `heading_lint: []` to report duplicates only:
+    * `:duplicates` (default `true`) - report headings with the same text as a previous heading of the same level.
+    * `:max_length` (default `nil`) - report headings longer than this number of characters.
+    * `:unit` (default `:grapheme`) - unit of `:max_length`, `:grapheme` to count emoji sequences and accented letters as the single
+    character users see, `:char` to count Unicode code points, or `:byte`.
+  * `:diff_words` (default `false`) - in code blocks labeled `diff`, compare each removed line with the added line at the same position
+  when a run of `-` lines is followed by as many `+` lines, and wrap the changed words in `<span class="diff-change">`, requires `:syntax_highlight_theme`.
+  * `:max_spans_per_block` (default `nil`) - render code blocks that would produce more highlight spans than this as plain escaped text,
+  since minified sources produce huge HTML, and report them as `:too_many_spans` warnings in `to_html_with_metadata/2`.
+  * `:code_block_attributes` (default `nil`) - customize the attributes of highlighted code blocks, requires `:syntax_highlight_theme`:
+    * `:translate` (default `"no"`) - value of the `translate` attribute on `<code>`, or `nil` to omit it.
+    * `:tabindex` (default `nil`) - value of the `tabindex` attribute on `<code>`, for example `0` to make scrollable blocks focusable.
+    * `:pre`, `:code` (default `%{}`) - extra static attributes added to the `<pre>` and `<code>` tags, as a map with string keys.
+  * `:input_limits` (default `nil`) - return `{:error, :pathological_input}` without rendering when the markdown exceeds any of these limits,
+  recommended when rendering untrusted input. Pass `input_limits: []` to use the defaults. Counts are approximate and ignore fenced code blocks:
+    * `:max_nesting_depth` (default `100`) - nested block quotes and list items on a line, or nested `[` brackets in a paragraph.
+    * `:max_delimiter_runs` (default `5_000`) - runs of `*`, `_` and `~` emphasis delimiters in a paragraph.
+    * `:max_link_definitions` (default `10_000`) - link reference definitions in the document.
+    * `:max_table_cells` (default `100_000`) - table cells in the document, counted on every line containing a `|`.
+  * `:id_prefix` (default `nil`) - namespace added in front of the ids of headings, from `:heading_ids` or `extension: [header_ids: prefix]`,
+  and of footnotes and their links, so several documents rendered on the same page don't share ids, for example `id_prefix: "post-1-"`.
+  Required to keep heading anchors with `sanitize: true`, which otherwise removes their ids. The sanitizer then keeps the ids of anchors
+  and adds the prefix to the ids written in raw HTML, like ammonia's `id_prefix`. It's the single prefix of heading ids: a `header_ids` prefix
+  must be empty or the same, and a different one returns an error since anchors and links would use different ids.
+  * `:broken_link_resolver` (default `nil`) - resolve references without a definition, like `[Some Page]` or `[the page][Some Page]`
+  in wikis, with a url template where `{ref}` is replaced by the percent-encoded label, lowercased and with whitespace collapsed,
+  for example `"/wiki/{ref}"`, or with a map of labels to urls, for example `%{"Some Page" => "/wiki/some-page"}`, where labels match
+  ignoring case and whitespace like reference definitions do. References missing from the map are left as text.
+  * `:collect_ids` (default `false`) - return the ids of the elements in the output as `:ids` in `to_html_with_metadata/2`.
+  * `:heading_template` (default `nil`) - markup of the headings with an anchor, with `{level}`, `{id}` and a single `{content}` placeholder
+  for the rendered heading text, for example `"<h{level} id=\"{id}\" class=\"heading\"><a href=\"#{id}\">{content}</a></h{level}>"`.
+  Adds anchors with the default `:heading_ids` when not set, and returns an error for templates without `{content}` or with more than one.
+  * `:heading_ids` (default `nil`) - add anchors to headings like `extension: [header_ids: prefix]`, with more control over the generated ids.
+  The `:header_ids` prefix, if also set, is kept in front of the ids:
+    * `:prefix`, `:suffix` (default `""`) - added around every id, for example to namespace documents rendered on the same page.
+    * `:collisions` (default `:number`) - how to handle headings with the same id:
+      * `:number` - append `-1`, `-2` and so on, like `:header_ids`.
+      * `:hash` - append a short hash of the enclosing headings, so ids don't change when other sections are added or moved.
+      * `:error` - return `{:error, {:duplicate_heading_id, id}}`.
+    * `:source` (default `:text`) - where ids come from, so translated documents can keep the same anchors across languages:
+      * `:text` - the heading text.
+      * `:explicit` - a trailing `{#id}` attribute block, like `# Instalación {#install}`, which is removed from the heading,
+      or the heading text when there's none.
+      * `:front_matter_map` - a `heading_ids` map of heading text to id in the front matter, requires `extension: [front_matter_delimiter: "---"]`,
+      or the heading text for headings not in the map. For example:
+
+            ---
+            heading_ids:
+              Instalación: install
+            ---
+    * `:exclude` (default `[]`) - text of the headings without id, like `["Table of contents"]`. Headings ending with `{-}` or `{.no-anchor}`,
+    which is removed from the heading, are also skipped. Skipped headings have no anchor and are not listed in the `:toc`.
+  * `:headings_as` (default `:heading`) - render headings as `<h1>` to `<h6>` tags with `:heading`, or as `<div role="heading" aria-level="1">`
+  with `:div`, to embed documents where real heading tags would break the outline of the page. Anchors from `extension: [header_ids: prefix]`
+  are rendered with `heading_ids: []` in that case, and `sanitize: true` keeps the `role` and `aria-level` attributes of headings.
+  * `:number_headings` (default `nil`) - prefix headings with hierarchical numbers like `2.1 `:
+    * `:levels` (default `1..6`) - range of heading levels to number, headings above the range restart the numbering.
+    Leading levels without headings are omitted, so a document starting at `##` is numbered `1`, `2`, and so on.
+    * `:format` (default `"1.2.3 "`) - how numbers are written: the text before the first digit, between digits and after the last digit
+    is used as prefix, separator and suffix.
+    * `:anchors` (default `false`) - include the numbers in the heading ids generated by `:header_ids` or `:heading_ids`.
+  * `:toc` (default `nil`) - replace `<!-- toc -->` and `[[toc]]` markers, on a line by themselves, with a `<nav class="toc">`
+  containing a nested list of links to the headings. Enables `heading_ids: []` when not set, so headings have anchors:
+    * `:levels` (default `1..6`) - range of heading levels to list, for example `2..3`.
+
+  ### Presets
+
+  Presets override the `:extension`, `:parse` and `:render` options and fill the `:features` that were not set.
+
+  * `:comments` - user comments and forum posts: renders only paragraphs of inline content, with block quotes, lists and headings flattened
+  into paragraphs, code blocks as inline code, and images as their alt text. Autolinks and emoji shortcodes are enabled, raw HTML is escaped,
+  and the output is sanitized to a small set of inline tags with `rel="nofollow noopener noreferrer"` links.
+  Defaults to `max_input_bytes: 10_000` and `input_limits: []`.
+  * `:feed` - RSS and Atom feed entries: the output is sanitized to remove scripts, iframes, forms, ids and classes, keeping the inline styles
+  of highlighted code. Task lists render as text, headings and footnotes render without ids, and relative urls are resolved against
+  `features: [base_url: url]` or removed when it's not set.
+  * `:hardened` - completely untrusted markdown: raw HTML is escaped, the output is sanitized with ammonia's defaults and every limit
+  is enabled at conservative values. Defaults to `max_input_bytes: 100_000`, `max_output_bytes: 1_000_000`, `max_spans_per_block: 10_000`,
+  `timeout: 1_000` and `input_limits: [max_nesting_depth: 32, max_delimiter_runs: 1_000, max_link_definitions: 1_000, max_table_cells: 10_000]`.
+
+  ## Return
+
+  Returns the HTML as a string, or `{:error, reason}` with one of the `t:render_error/0` reasons
+  when rendering fails, like `{:error, :output_too_large}` when `:max_output_bytes` is exceeded or
+  `{:error, reason}` with a message when an option is invalid. Earlier versions always returned a string,
+  so callers passing options must now handle the error tuple.
 
   ## Examples
 
@@ -60,21 +250,706 @@ defmodule MDEx do
       iex> MDEx.to_html("# Title with <script>console.log('dangerous script')</script>", render: [unsafe_: true], features: [sanitize: true])
       "<h1>Title with </h1>\\n"
 
+      iex> MDEx.to_html("# MDEx", features: [wrapper: {"article", class: "prose"}])
+      "<article class=\\"prose\\">\\n<h1>MDEx</h1>\\n</article>\\n"
+
   """
-  @spec to_html(String.t(), keyword()) :: String.t()
+  @spec to_html(String.t(), keyword()) :: String.t() | {:error, render_error()}
   def to_html(markdown, opts) when is_binary(markdown) do
+    Native.to_html_with_options(markdown, build_options(opts))
+  end
+
+  @doc """
+  Convert `markdown` to text styled with ANSI escape sequences, to print documents in terminals from CLI tools.
+
+  Headings, emphasis, links, lists, block quotes, tables and thematic breaks are styled with the basic terminal
+  styles and colors, so they follow the terminal palette, and code blocks are highlighted with the 24-bit colors
+  of `:syntax_highlight_theme`, or left plain when it's `nil`. The parse and extension options apply as in `to_html/2`,
+  `render: [width: n]` sets the width of thematic breaks, 80 columns by default, and the options only affecting
+  HTML, like `:sanitize`, are ignored.
+
+  ## Examples
+
+      iex> MDEx.to_ansi("# MDEx")
+      "\\e[1m\\e[34m# MDEx\\e[0m\\n"
+
+      iex> MDEx.to_ansi("> - **one**\\n> - two")
+      "\\e[2m│\\e[0m • \\e[1mone\\e[0m\\n\\e[2m│\\e[0m • two\\n"
+
+  """
+  @spec to_ansi(String.t(), keyword()) :: String.t() | {:error, render_error()}
+  def to_ansi(markdown, opts \\ []) when is_binary(markdown) do
+    Native.markdown_to_ansi_with_options(markdown, build_options(opts))
+  end
+
+  @doc """
+  Convert `markdown` to HTML with custom `opts`, like `to_html/2`, and also return metadata about the render.
+
+  ## Metadata
+
+  * `:was_sanitized` - `true` when sanitization changed the output, useful to flag user content
+  that contained disallowed HTML for moderation.
+  * `:violations` - what `features: [audit: true]` removed, as a list of maps with the `:kind`
+  (`:script`, `:event_handler` or `:javascript_url`) and the removed `:snippet`.
+  * `:warnings` - non-fatal issues found while rendering, as a list of maps with the `:kind`, a `:message`
+  and the `:sourcepos` of the enclosing block as `{{start_line, start_column}, {end_line, end_column}}`:
+    * `:unknown_language` - a code fence language without a grammar, highlighted as plain text.
+    * `:dropped_url` - a link or image url removed because of its scheme, like `javascript:`, or by `features: [data_images: [...]]`.
+    * `:undefined_footnote` - a footnote reference without definition, rendered as text, with `extension: [footnotes: true]`.
+    * `:too_many_spans` - a code block over `features: [max_spans_per_block: n]`, rendered as plain text.
+    * `:duplicate_heading` - a heading with the same text as a previous heading of the same level, with `features: [heading_lint: []]`.
+    * `:long_heading` - a heading longer than `features: [heading_lint: [max_length: n]]` characters.
+  * `:language` - the natural language of the text with `features: [detect_language: true]`, ignoring code, or `nil` when there isn't enough text.
+  A map with the `:lang` code for `lang` attributes, like `"en"`, the English `:name` of the language, a `:confidence` from `0.0` to `1.0`
+  and whether the detection is `:reliable`.
+  * `:ids` - the ids of the elements in the final HTML with `features: [collect_ids: true]`, like heading anchors, footnotes and ids
+  written in raw HTML, each once and in document order, to find collisions with the page the HTML is injected into. `nil` otherwise.
+
+  ## Examples
+
+      iex> MDEx.to_html_with_metadata("# MDEx")
+      {:ok, "<h1>MDEx</h1>\\n", %{was_sanitized: false, violations: [], warnings: [], language: nil, ids: nil}}
+
+      iex> MDEx.to_html_with_metadata("<script>alert(1)</script>", render: [unsafe_: true], features: [sanitize: true])
+      {:ok, "\\n", %{was_sanitized: true, violations: [], warnings: [], language: nil, ids: nil}}
+
+  """
+  @spec to_html_with_metadata(String.t(), keyword()) :: {:ok, String.t(), map()} | {:error, render_error()}
+  def to_html_with_metadata(markdown, opts \\ []) when is_binary(markdown) do
+    Native.to_html_with_metadata(markdown, build_options(opts))
+  end
+
+  @doc """
+  Parse `markdown` and return its AST as a JSON string, to send it to JavaScript frontends or store it
+  without encoding every node as an Elixir term.
+
+  Every node is an object with its `"type"`, like `"heading"` or `"text"`, its `"sourcepos"` as
+  `[start_line, start_column, end_line, end_column]`, its `"children"` and the fields of its type,
+  like the `"level"` of headings or the `"url"` and `"title"` of links and images. Inline nodes may have a zero `"sourcepos"`.
+  Accepts the same `:extension`, `:parse` and `:render` options as `to_html/2`, as well as `:max_input_bytes` and `:input_limits`.
+
+  ## Examples
+
+      iex> MDEx.to_json_ast("---")
+      ~s({"type":"document","sourcepos":[1,1,1,3],"children":[{"type":"thematic_break","sourcepos":[1,1,1,3],"children":[]}]})
+
+  """
+  @spec to_json_ast(String.t(), keyword()) :: String.t() | {:error, render_error()}
+  def to_json_ast(markdown, opts \\ []) when is_binary(markdown) do
+    Native.to_json_ast(markdown, build_options(opts))
+  end
+
+  @doc """
+  Parse the front matter of `markdown` and convert the rest of the document to HTML with the same `opts` as `to_html/2`,
+  so static site generators don't need a separate YAML or TOML library.
+
+  The format follows from the delimiter on the first line: `---` for YAML, `+++` for TOML and `;;;` for JSON.
+  The front matter is returned as a map with string keys, or `nil` when the document has none. Front matter that isn't a map
+  or fails to parse returns `{:error, "invalid front matter: " <> reason}`.
+
+  ## Examples
+
+      iex> MDEx.parse_front_matter("---\\ntitle: MDEx\\ntags: [elixir, rust]\\n---\\n# MDEx")
+      {:ok, %{"title" => "MDEx", "tags" => ["elixir", "rust"]}, "<h1>MDEx</h1>\\n"}
+
+      iex> MDEx.parse_front_matter("# MDEx")
+      {:ok, nil, "<h1>MDEx</h1>\\n"}
+
+  """
+  @spec parse_front_matter(String.t(), keyword()) :: {:ok, map() | nil, String.t()} | {:error, render_error()}
+  def parse_front_matter(markdown, opts \\ []) when is_binary(markdown) do
+    Native.parse_front_matter(markdown, build_options(opts))
+  end
+
+  @doc """
+  Check that formatting `markdown` as CommonMark doesn't change the document, before writing formatted files back.
+
+  The document is formatted with `opts`, like `render: [width: 80]`, parsed again and both syntax trees are compared.
+  How nodes are written doesn't matter, like setext or ATX headings, `*` or `-` bullets, indented or fenced code blocks,
+  escapes and where paragraphs wrap. The report has the formatted `:markdown` and, when the documents differ, the `:path`
+  of child indexes from the document to the first node that differs, with its `:expected` type in `markdown` and its
+  `:actual` type in the formatted markdown, `nil` when there's no node on that side.
+
+  ## Examples
+
+      iex> MDEx.verify_roundtrip("Title\\n=====\\n\\n* one\\n* two")
+      {:ok, %{equivalent: true, markdown: "# Title\\n\\n- one\\n- two\\n", path: nil, expected: nil, actual: nil}}
+
+      iex> MDEx.verify_roundtrip("- one\\n\\n* two")
+      {:ok, %{equivalent: false, markdown: "- one\\n\\n<!-- end list -->\\n\\n- two\\n", path: [1], expected: "list", actual: "html_block"}}
+
+  """
+  @spec verify_roundtrip(String.t(), keyword()) ::
+          {:ok,
+           %{
+             equivalent: boolean(),
+             markdown: String.t(),
+             path: [non_neg_integer()] | nil,
+             expected: String.t() | nil,
+             actual: String.t() | nil
+           }}
+          | {:error, render_error()}
+  def verify_roundtrip(markdown, opts \\ []) when is_binary(markdown) do
+    Native.verify_roundtrip(markdown, build_options(opts))
+  end
+
+  @doc """
+  Convert `html` to CommonMark, for importing content from CMSs and rich-text editors.
+
+  Headings, paragraphs, lists, block quotes, code blocks, tables, links, images, emphasis, strikethrough and line breaks
+  are converted, other elements are replaced by their content, and scripts, styles and comments are removed.
+  Whitespace is collapsed like browsers do. The language of code blocks comes from a `language-` or `lang-` class,
+  and tables always get a header row since markdown tables require one. Render options like `render: [width: 80]`
+  are applied to the output.
+
+  The HTML is read with a lenient tag scanner, not a spec-compliant parser like lol_html or html5ever, so it accepts any input
+  but malformed markup may nest differently than in browsers. Elements nested deeper than `features: [input_limits: [max_nesting_depth: n]]`,
+  and never deeper than its default of 100, are replaced by their content. `:max_input_bytes`, `:max_output_bytes` and `:timeout`
+  apply like when rendering, so the `:hardened` preset limits the conversion too.
+
+  ## Examples
+
+      iex> MDEx.html_to_markdown("<h1>MDEx</h1><p>Fast <strong>and</strong> <a href=\\"https://hexdocs.pm/mdex\\">extensible</a></p>")
+      "# MDEx\\n\\nFast **and** [extensible](https://hexdocs.pm/mdex)\\n"
+
+  """
+  @spec html_to_markdown(String.t(), keyword()) :: String.t() | {:error, render_error()}
+  def html_to_markdown(html, opts \\ []) when is_binary(html) do
+    Native.html_to_markdown(html, build_options(opts))
+  end
+
+  @doc """
+  Convert several markdown `documents` into a single HTML payload, for pages that aggregate content.
+
+  Each document is a `{id, markdown}` tuple and is rendered with the same `opts` as `to_html/2`
+  inside a `<div class="mdex-document" id="id">` wrapper. Heading ids generated by `:header_ids`
+  or `features: [heading_ids: ...]` are unique across all documents and wrappers.
+
+  Returns the first error if any document fails to render.
+
+  ## Examples
+
+      iex> MDEx.render_many_to_html([{"intro", "# Hello"}, {"usage", "`MDEx`"}])
+      ~s(<div class="mdex-document" id="intro">\\n<h1>Hello</h1>\\n</div>\\n<div class="mdex-document" id="usage">\\n<p><code>MDEx</code></p>\\n</div>\\n)
+
+  """
+  @spec render_many_to_html([{String.t(), String.t()}], keyword()) :: String.t() | {:error, render_error()}
+  def render_many_to_html(documents, opts \\ []) when is_list(documents) do
+    Native.render_many_to_html(documents, build_options(opts))
+  end
+
+  @doc """
+  Convert a list of markdown `documents` to HTML in a single call, for static site builds rendering many files.
+
+  Each document is rendered on its own with `opts`, like `to_html/2`, on up to `:max_concurrency` native threads,
+  defaulting to `System.schedulers_online/0`. Returns a list with the HTML or `{:error, reason}` of each document,
+  in the same order. The `:cache` feature isn't used.
+
+  ## Examples
+
+      iex> MDEx.markdown_to_html_batch(["# MDEx", "`batch`"])
+      ["<h1>MDEx</h1>\\n", "<p><code>batch</code></p>\\n"]
+
+  """
+  @spec markdown_to_html_batch([String.t()], keyword()) :: [String.t() | {:error, render_error()}]
+  def markdown_to_html_batch(documents, opts \\ []) when is_list(documents) do
+    {max_concurrency, opts} = Keyword.pop(opts, :max_concurrency, System.schedulers_online())
+    Native.markdown_to_html_batch(documents, build_options(opts), max_concurrency)
+  end
+
+  @doc """
+  List the heading anchors of several `{path, markdown}` documents and the ids shared by more than one document.
+
+  Ids are generated as `to_html/2` would with the same `opts`, including `:header_ids`, `features: [heading_ids: ...]`
+  and `features: [number_headings: ...]`, and each anchor has its `url` as `path#id`. Ids shared between documents
+  don't clash while each document has its own page, but they do when documents are combined, for example with
+  `render_many_to_html/2`.
+
+  ## Examples
+
+      iex> {:ok, report} = MDEx.anchor_report([{"install.md", "# Setup"}, {"usage.md", "# Setup\\n## Options"}])
+      iex> Enum.map(report.anchors, & &1.url)
+      ["install.md#setup", "usage.md#setup", "usage.md#options"]
+      iex> report.collisions
+      [%{id: "setup", paths: ["install.md", "usage.md"]}]
+
+  """
+  @spec anchor_report([{String.t(), String.t()}], keyword()) ::
+          {:ok, %{anchors: [map()], collisions: [%{id: String.t(), paths: [String.t()]}]}} | {:error, render_error()}
+  def anchor_report(documents, opts \\ []) when is_list(documents) do
+    Native.anchor_report(documents, build_options(opts))
+  end
+
+  @doc """
+  Convert `markdown` to HTML in the background, without blocking the caller.
+
+  Accepts the same `opts` as `to_html/2` and returns a job reference.
+  Once done, the result is sent to the calling process as:
+
+      {:mdex, job, {:ok, html}}
+      {:mdex, job, {:error, reason}}
+
+  Jobs run on a native pool with one thread per core, and wait for a free thread when all of them are busy.
+  The job can be stopped with `cancel/1`, for example when a LiveView navigates away
+  in the middle of a long render. Cancelled jobs don't send any message.
+
+  ## Examples
+
+      iex> job = MDEx.render_async("# MDEx")
+      iex> receive do
+      ...>   {:mdex, ^job, result} -> result
+      ...> end
+      {:ok, "<h1>MDEx</h1>\\n"}
+
+  """
+  @spec render_async(String.t(), keyword()) :: reference()
+  def render_async(markdown, opts \\ []) when is_binary(markdown) do
+    Native.render_async(markdown, build_options(opts))
+  end
+
+  @doc """
+  Convert a list of markdown `documents` to HTML in the background, sending each result as soon as it's rendered.
+
+  Accepts the same `opts` as `markdown_to_html_batch/2`, including `:max_concurrency`, and returns a job reference.
+  Large site builds can report progress or write files as documents finish instead of waiting for the slowest one.
+  The calling process receives a message for each document, with its index in `documents`, in the order they finish:
+
+      {:mdex, job, {index, {:ok, html}}}
+      {:mdex, job, {index, {:error, reason}}}
+
+  followed, once all documents are rendered, by a summary with `:ok` or the error of each document in the order of `documents`:
+
+      {:mdex, job, {:done, [:ok | {:error, reason}]}}
+
+  Documents are rendered on the same pool as `render_async/2`, by up to `:max_concurrency` of its threads.
+  The job can be stopped with `cancel/1`, documents not started yet are skipped and no more messages are sent.
+
+  ## Examples
+
+      iex> job = MDEx.render_batch_async(["# MDEx"])
+      iex> receive do
+      ...>   {:mdex, ^job, {0, result}} -> result
+      ...> end
+      {:ok, "<h1>MDEx</h1>\\n"}
+      iex> receive do
+      ...>   {:mdex, ^job, {:done, summary}} -> summary
+      ...> end
+      [:ok]
+
+  """
+  @spec render_batch_async([String.t()], keyword()) :: reference()
+  def render_batch_async(documents, opts \\ []) when is_list(documents) do
+    {max_concurrency, opts} = Keyword.pop(opts, :max_concurrency, System.schedulers_online())
+    Native.render_batch_async(documents, build_options(opts), max_concurrency)
+  end
+
+  @doc """
+  Cancel a render started by `render_async/2` or `render_batch_async/2`.
+
+  The render stops before the next step of the pipeline, or at the next block boundary while writing the HTML,
+  and no result message is sent.
+  """
+  @spec cancel(reference()) :: :ok
+  def cancel(job) when is_reference(job) do
+    Native.cancel(job)
+  end
+
+  @doc """
+  List the headings of `markdown` without rendering it.
+
+  Runs a block-level scan that only looks for headings, which is much cheaper than
+  `to_html/2` for building navigation sidebars of large documents.
+  The `:text` is the raw markdown of the heading and `:sourcepos` is `{{start_line, start_column}, {end_line, end_column}}`.
+  Headings inside block quotes or list items are not included, and neither are lines of HTML blocks or
+  of the front matter when `extension: [front_matter_delimiter: "---"]` is set.
+
+  ## Examples
+
+      iex> MDEx.outline("# MDEx\\n\\nIntro\\n\\nUsage\\n-----")
+      [
+        %{level: 1, text: "MDEx", sourcepos: {{1, 1}, {1, 6}}},
+        %{level: 2, text: "Usage", sourcepos: {{5, 1}, {6, 5}}}
+      ]
+
+  """
+  @spec outline(String.t(), keyword()) :: [
+          %{level: 1..6, text: String.t(), sourcepos: {{pos_integer(), pos_integer()}, {pos_integer(), pos_integer()}}}
+        ]
+  def outline(markdown, opts \\ []) when is_binary(markdown) do
+    Native.outline(markdown, build_options(opts))
+  end
+
+  @doc """
+  List the headings enclosing a position of `markdown`, outermost first, to render breadcrumbs or "on this page" context.
+
+  The target is either a `{line, column}` position, for example from `:sourcepos`, or the anchor of a heading, in which case
+  the list ends with that heading. Returns an empty list when the position is before the first heading or no heading has the anchor.
+
+  Each heading has its `:level`, `:text`, `:sourcepos` and the `:id` it gets when rendered with the same `opts`,
+  see `:header_ids` and `features: [heading_ids: ...]`.
+
+  ## Examples
+
+      iex> markdown = "# Guide\\n\\n## Install\\n\\nRun it\\n\\n## Usage\\n"
+      iex> markdown |> MDEx.heading_path_at({5, 1}) |> Enum.map(& &1.text)
+      ["Guide", "Install"]
+      iex> markdown |> MDEx.heading_path_at("usage") |> Enum.map(& &1.id)
+      ["guide", "usage"]
+
+  """
+  @spec heading_path_at(String.t(), {pos_integer(), pos_integer()} | String.t(), keyword()) ::
+          [%{level: 1..6, text: String.t(), id: String.t(), sourcepos: tuple()}] | {:error, render_error()}
+  def heading_path_at(markdown, target, opts \\ []) when is_binary(markdown) do
+    Native.heading_path_at(markdown, target, build_options(opts))
+  end
+
+  @doc """
+  List the images in `markdown` without alt text, to generate it in batch.
+
+  The `sourcepos` is the position of the block containing the image, usually a paragraph.
+  Accepts the same `:extension`, `:parse` and `:render` options as `to_html/2`.
+
+  ## Examples
+
+      iex> MDEx.images_missing_alt("![](cat.png)\\n\\n![A dog](dog.png)")
+      [%{url: "cat.png", title: "", sourcepos: {{1, 1}, {1, 12}}}]
+
+  """
+  @spec images_missing_alt(String.t(), keyword()) :: [
+          %{url: String.t(), title: String.t(), sourcepos: {{pos_integer(), pos_integer()}, {pos_integer(), pos_integer()}}}
+        ]
+  def images_missing_alt(markdown, opts \\ []) when is_binary(markdown) do
+    Native.images_missing_alt(markdown, build_options(opts))
+  end
+
+  @doc """
+  Extract the metadata of `markdown` to build meta tags and sitemaps, in a single pass over the document:
+
+    * `:title` - text of the first level 1 heading, or `nil`.
+    * `:description` - text of the first top level paragraph, cut at the last word that fits in `:description_length`
+    (default `160`) `:unit`s with an ellipsis, or `nil`.
+    * `:images` - every image with its `:url`, `:alt` and `:title`.
+    * `:links` - unique absolute `http` and `https` link urls, without their fragment.
+
+  Relative urls are resolved against `features: [base_url: url]` when set. The `:unit` (default `:grapheme`) of the description
+  length is `:grapheme` to count emoji sequences and accented letters as the single character users see, `:char` to count
+  Unicode code points, or `:byte`.
+  Accepts the same `:extension`, `:parse` and `:render` options as `to_html/2`.
+
+  ## Examples
+
+      iex> MDEx.seo_metadata("# MDEx\\n\\nFast and extensible Markdown for Elixir.\\n\\n![Logo](logo.png)\\n\\n[Docs](https://hexdocs.pm/mdex#usage)", description_length: 20)
+      %{
+        title: "MDEx",
+        description: "Fast and extensible…",
+        images: [%{url: "logo.png", alt: "Logo", title: ""}],
+        links: ["https://hexdocs.pm/mdex"]
+      }
+
+  """
+  @spec seo_metadata(String.t(), keyword()) :: %{
+          title: String.t() | nil,
+          description: String.t() | nil,
+          images: [%{url: String.t(), alt: String.t(), title: String.t()}],
+          links: [String.t()]
+        }
+  def seo_metadata(markdown, opts \\ []) when is_binary(markdown) do
+    description_length = Keyword.get(opts, :description_length, 160)
+    Native.seo_metadata(markdown, build_options(opts), description_length, Keyword.get(opts, :unit, :grapheme))
+  end
+
+  @doc """
+  Return a stable hash of the configuration `to_html/2` renders with for `opts`, to detect when stored renders must be invalidated.
+
+  The options are resolved the same way as when rendering, with the `:preset` overrides and the defaults of every option,
+  so equivalent options have the same fingerprint, for example `[]` and `[features: [sanitize: false]]`. The `:cache` and `:metrics`
+  features are ignored. The fingerprint also changes with the version of MDEx, from `mix.exs`, whose output may change.
+  Returns `{:error, reason}` for invalid options, like an unknown theme.
+
+  ## Examples
+
+      iex> MDEx.render_options_fingerprint([]) == MDEx.render_options_fingerprint(features: [sanitize: false])
+      true
+
+      iex> MDEx.render_options_fingerprint([]) == MDEx.render_options_fingerprint(features: [sanitize: true])
+      false
+
+  """
+  @spec render_options_fingerprint(keyword()) :: String.t() | {:error, render_error()}
+  def render_options_fingerprint(opts \\ []) when is_list(opts) do
+    Native.render_options_fingerprint(build_options(opts), @version)
+  end
+
+  @doc """
+  Render only the code blocks of `markdown`, for pages that hydrate code blocks lazily or cache them apart from the prose.
+
+  Returns a list of `{index, html}` with the position of the block among the code blocks of the document, starting at `0`,
+  and the same HTML `to_html/2` renders for it with the same `opts`, without rendering the rest of the document.
+
+  ## Examples
+
+      iex> MDEx.code_blocks_to_html("# Install\\n\\n```\\nmix deps.get\\n```\\n\\n    iex -S mix", features: [syntax_highlight_theme: nil])
+      [{0, "<pre><code>mix deps.get\\n</code></pre>\\n"}, {1, "<pre><code>iex -S mix\\n</code></pre>\\n"}]
+
+  """
+  @spec code_blocks_to_html(String.t(), keyword()) :: [{non_neg_integer(), String.t()}] | {:error, render_error()}
+  def code_blocks_to_html(markdown, opts \\ []) when is_binary(markdown) do
+    Native.code_blocks_to_html(markdown, build_options(opts))
+  end
+
+  @doc """
+  Sanitize `html` and list what was removed, to preview how a sanitization policy affects stored content
+  before rendering it with `features: [sanitize: true]`.
+
+  Uses the same policy as `to_html/2` with the same `opts`, like `preset: :comments` or `features: [data_images: []]`.
+  Each removal has the `:kind` (`:element`, `:attribute`, `:url` or `:comment`), the lowercase `:name` of the element or attribute,
+  the removed `:snippet` and the `:line` and `:column` where it starts in `html`. Attributes the policy allows but removed
+  for their URL, like `javascript:` links or schemes the policy doesn't allow, are listed as `:url`. Removals are found by matching the tags
+  of `html` and of the sanitized HTML, so changes to text and attribute values are not listed.
+
+  ## Examples
+
+      iex> MDEx.sanitize_preview(~s{<p onclick="steal()">Hi<script>alert(1)</script></p>})
+      %{
+        html: "<p>Hi</p>",
+        removed: [
+          %{kind: :attribute, name: "onclick", snippet: ~s{onclick="steal()"}, line: 1, column: 1},
+          %{kind: :element, name: "script", snippet: "<script>", line: 1, column: 24}
+        ]
+      }
+
+  """
+  @spec sanitize_preview(String.t(), keyword()) :: %{html: String.t(), removed: [map()]}
+  def sanitize_preview(html, opts \\ []) when is_binary(html) do
+    Native.sanitize_preview(html, build_options(opts))
+  end
+
+  @doc """
+  Create a metrics reference to collect stats from `to_html/2` calls.
+
+  Pass it as `features: [metrics: metrics]` and read the counters with `get_metrics/1`.
+  The same reference can be shared across processes.
+
+  ## Examples
+
+      iex> metrics = MDEx.new_metrics()
+      iex> MDEx.to_html("# MDEx", features: [metrics: metrics])
+      "<h1>MDEx</h1>\\n"
+      iex> MDEx.get_metrics(metrics)
+      %{documents_rendered: 1, bytes_in: 6, bytes_out: 14, code_blocks_highlighted: 0, sanitized_documents: 0}
+
+  """
+  @spec new_metrics() :: reference()
+  def new_metrics do
+    Native.new_metrics()
+  end
+
+  @doc """
+  Create a cache for the HTML rendered by `to_html/2`, kept in native memory.
+
+  Pass it as `features: [cache: cache]` to return the cached HTML when the same markdown is rendered
+  with the same options, skipping the whole pipeline, including `:metrics`. Errors are not cached.
+  Each entry keeps a copy of the markdown and options next to the HTML to match them exactly.
+  The same reference can be shared across processes.
+
+  ## Options
+
+  * `:max_entries` (default `1_000`) - number of documents to keep, the least recently used is evicted first.
+  * `:ttl` (default `nil`) - time in milliseconds after which entries expire, or `nil` to keep them until evicted.
+
+  ## Examples
+
+      iex> cache = MDEx.new_cache(max_entries: 100)
+      iex> MDEx.to_html("# MDEx", features: [cache: cache])
+      "<h1>MDEx</h1>\\n"
+
+  """
+  @spec new_cache(keyword()) :: reference()
+  def new_cache(opts \\ []) when is_list(opts) do
+    Native.new_cache(Keyword.get(opts, :max_entries, 1_000), Keyword.get(opts, :ttl))
+  end
+
+  @doc """
+  Read the counters collected in `metrics`.
+
+  * `:documents_rendered` - number of documents rendered
+  * `:bytes_in` - total size of the markdown inputs
+  * `:bytes_out` - total size of the generated HTML
+  * `:code_blocks_highlighted` - number of code blocks passed through the syntax highlighter
+  * `:sanitized_documents` - number of documents changed by sanitization
+  """
+  @spec get_metrics(reference()) :: %{atom() => non_neg_integer()}
+  def get_metrics(metrics) when is_reference(metrics) do
+    Native.get_metrics(metrics)
+  end
+
+  @doc """
+  Load a tree-sitter grammar compiled as a shared library and use it to highlight code blocks tagged as `name`.
+
+  Registered grammars take precedence over the built-in languages and stay loaded until the VM exits.
+  Registering the same `name` again replaces the previous grammar.
+
+  ## Options
+
+  * `:symbol` (default `"tree_sitter_<name>"`) - name of the function exported by the library that returns the language.
+  * `:highlights_query` (required) - contents of the grammar `highlights.scm` query.
+  * `:injections_query` (default `""`) - contents of the grammar `injections.scm` query.
+  * `:locals_query` (default `""`) - contents of the grammar `locals.scm` query.
+
+  ## Examples
+
+      MDEx.register_grammar("nix", "/path/to/nix.so", highlights_query: File.read!("/path/to/queries/highlights.scm"))
+      MDEx.to_html("```nix\n{ pkgs }: pkgs.hello\n```")
+
+  """
+  @spec register_grammar(String.t(), Path.t(), keyword()) :: :ok | {:error, String.t()}
+  def register_grammar(name, path, opts) when is_binary(name) and is_list(opts) do
+    Native.register_grammar(
+      name,
+      to_string(path),
+      Keyword.get(opts, :symbol),
+      Keyword.fetch!(opts, :highlights_query),
+      Keyword.get(opts, :injections_query, ""),
+      Keyword.get(opts, :locals_query, "")
+    )
+  end
+
+  @doc """
+  Highlight `source` as a standalone SVG image, for places where HTML and CSS are not available like social cards.
+
+  `lang` is the language name used in code fences, or `nil` for plain text, and `theme` is one of the
+  [autumn themes](https://github.com/leandrocp/autumn/tree/main/priv/themes) as in `:syntax_highlight_theme`.
+  The image is sized to fit the code, assuming a monospace font.
+
+  ## Options
+
+  * `:font_family` (default `"monospace"`)
+  * `:font_size` (default `14`) - font size in pixels.
+  * `:line_height` (default `1.5`) - line height relative to the font size, must be a float.
+  * `:padding` (default `16`) - space around the code in pixels.
+
+  ## Examples
+
+      iex> MDEx.highlight_code_svg("ok", nil, "onedark")
+      ~s(<svg xmlns="http://www.w3.org/2000/svg" width="48.8" height="53" viewBox="0 0 48.8 53"><rect width="100%" height="100%" fill="#282C34"/><text font-family="monospace" font-size="14" fill="#ABB2BF" xml:space="preserve"><tspan x="16" y="30">ok</tspan></text></svg>)
+
+  """
+  @spec highlight_code_svg(String.t(), String.t() | nil, String.t(), keyword()) :: String.t() | {:error, String.t()}
+  def highlight_code_svg(source, lang, theme, opts \\ []) when is_binary(source) and is_binary(theme) do
+    Native.highlight_code_svg(source, lang, theme, struct(MDEx.Types.SvgOptions, opts))
+  end
+
+  @doc """
+  Highlight `source` written in `lang` and return the highlighted tokens, for tools that analyze code
+  with the same tree-sitter queries used to render code blocks, like counting TODO comments or extracting strings.
+
+  Each token is a `{{start, stop}, scope}` tuple with the byte range of the token in `source`, `stop` exclusive,
+  and its innermost highlight scope, like `"comment"` or `"string"`. Text that isn't highlighted is not included.
+
+  Returns `{:error, reason}` for unknown languages.
+
+  ## Examples
+
+      iex> MDEx.highlight_tokens("ok", "unknown")
+      {:error, "unknown language: unknown"}
+
+  """
+  @spec highlight_tokens(String.t(), String.t()) :: [{{non_neg_integer(), non_neg_integer()}, String.t()}] | {:error, String.t()}
+  def highlight_tokens(source, lang) when is_binary(source) and is_binary(lang) do
+    Native.highlight_tokens(source, lang)
+  end
+
+  defp build_options(opts) do
     extension = Keyword.get(opts, :extension, %{})
     parse = Keyword.get(opts, :parse, %{})
     render = Keyword.get(opts, :render, %{})
-    features = Keyword.get(opts, :features, %{})
+    features =
+      opts
+      |> Keyword.get(:features, %{})
+      |> Map.new()
+      |> build_nested(:description_list, MDEx.Types.DescriptionListOptions)
+      |> build_nested(:code_block_attributes, MDEx.Types.CodeBlockAttributesOptions)
+      |> build_nested(:input_limits, MDEx.Types.InputLimitsOptions)
+      |> build_nested(:heading_ids, MDEx.Types.HeadingIdsOptions)
+      |> build_nested(:number_headings, MDEx.Types.NumberHeadingsOptions)
+      |> build_nested(:toc, MDEx.Types.TocOptions)
+      |> build_nested(:short_links, MDEx.Types.ShortLinksOptions)
+      |> build_nested(:lang, MDEx.Types.LangOptions)
+      |> build_nested(:data_images, MDEx.Types.DataImagesOptions)
+      |> build_nested(:responsive_images, MDEx.Types.ResponsiveImagesOptions)
+      |> build_nested(:video_embeds, MDEx.Types.VideoEmbedsOptions)
+      |> build_nested(:typography, MDEx.Types.TypographyOptions)
+      |> build_nested(:heading_lint, MDEx.Types.HeadingLintOptions)
+      |> build_nested(:internal_links, MDEx.Types.InternalLinksOptions)
+      |> build_wrapper()
+      |> build_internal_links()
+      |> build_patterns(:replacements)
+      |> build_patterns(:relative_urls)
+      |> build_attribute_rules()
+      |> build_broken_link_resolver()
 
-    opts = %MDEx.Types.Options{
+    %MDEx.Types.Options{
       extension: struct(MDEx.Types.ExtensionOptions, extension),
       parse: struct(MDEx.Types.ParseOptions, parse),
       render: struct(MDEx.Types.RenderOptions, render),
-      features: struct(MDEx.Types.FeaturesOptions, features)
+      features: struct(MDEx.Types.FeaturesOptions, features),
+      preset: Keyword.get(opts, :preset)
     }
+  end
+
+  defp build_wrapper(%{wrapper: {tag, attrs}} = options) do
+    attrs = Enum.map(attrs, fn {name, value} -> {to_string(name), to_string(value)} end)
+    Map.put(options, :wrapper, {to_string(tag), attrs})
+  end
+
+  defp build_wrapper(options), do: options
+
+  defp build_internal_links(%{internal_links: %{attributes: attrs} = internal_links} = options) do
+    attrs = Enum.map(attrs, fn {name, value} -> {to_string(name), to_string(value)} end)
+    Map.put(options, :internal_links, %{internal_links | attributes: attrs})
+  end
+
+  defp build_internal_links(options), do: options
+
+  defp build_patterns(options, key) do
+    case Map.get(options, key) do
+      nil ->
+        options
+
+      patterns ->
+        patterns =
+          Enum.map(patterns, fn
+            {%Regex{} = regex, replacement} -> {:regex, Regex.source(regex), replacement}
+            {pattern, replacement} -> {:string, pattern, replacement}
+          end)
+
+        Map.put(options, key, patterns)
+    end
+  end
+
+  defp build_attribute_rules(%{attribute_rules: rules} = options) do
+    rules =
+      Enum.map(rules, fn
+        {tag, attribute, {:rewrite, template}} -> {to_string(tag), to_string(attribute), :rewrite, template}
+        {tag, attribute, action} when action in [:keep, :drop] -> {to_string(tag), to_string(attribute), action, nil}
+      end)
+
+    Map.put(options, :attribute_rules, rules)
+  end
+
+  defp build_attribute_rules(options), do: options
+
+  defp build_broken_link_resolver(%{broken_link_resolver: links} = options) when is_map(links) or is_list(links) do
+    links = Map.new(links, fn {label, url} -> {to_string(label), to_string(url)} end)
+    Map.put(options, :broken_link_resolver, links)
+  end
+
+  defp build_broken_link_resolver(options), do: options
 
-    Native.to_html_with_options(markdown, opts)
+  defp build_nested(options, key, module) do
+    case Map.get(options, key) do
+      nil -> options
+      value -> Map.put(options, key, struct(module, value))
+    end
   end
 end
@@ -17,4 +17,35 @@ defmodule MDEx.Native do
 
   def to_html(_md), do: :erlang.nif_error(:nif_not_loaded)
   def to_html_with_options(_md, _options), do: :erlang.nif_error(:nif_not_loaded)
+  def to_html_with_metadata(_md, _options), do: :erlang.nif_error(:nif_not_loaded)
+  def render_many_to_html(_documents, _options), do: :erlang.nif_error(:nif_not_loaded)
+  def markdown_to_html_batch(_documents, _options, _max_concurrency), do: :erlang.nif_error(:nif_not_loaded)
+  def to_json_ast(_md, _options), do: :erlang.nif_error(:nif_not_loaded)
+  def parse_front_matter(_md, _options), do: :erlang.nif_error(:nif_not_loaded)
+  def html_to_markdown(_html, _options), do: :erlang.nif_error(:nif_not_loaded)
+  def verify_roundtrip(_md, _options), do: :erlang.nif_error(:nif_not_loaded)
+  def markdown_to_ansi_with_options(_md, _options), do: :erlang.nif_error(:nif_not_loaded)
+  def anchor_report(_documents, _options), do: :erlang.nif_error(:nif_not_loaded)
+  def render_options_fingerprint(_options, _version), do: :erlang.nif_error(:nif_not_loaded)
+  def render_async(_md, _options), do: :erlang.nif_error(:nif_not_loaded)
+  def render_async_panic, do: :erlang.nif_error(:nif_not_loaded)
+  def pooled_output_capacities(_documents), do: :erlang.nif_error(:nif_not_loaded)
+  def render_batch_async(_documents, _options, _max_concurrency), do: :erlang.nif_error(:nif_not_loaded)
+  def cancel(_job), do: :erlang.nif_error(:nif_not_loaded)
+  def code_blocks_to_html(_md, _options), do: :erlang.nif_error(:nif_not_loaded)
+  def outline(_md, _options), do: :erlang.nif_error(:nif_not_loaded)
+  def heading_path_at(_md, _target, _options), do: :erlang.nif_error(:nif_not_loaded)
+  def sanitize_preview(_html, _options), do: :erlang.nif_error(:nif_not_loaded)
+  def new_metrics, do: :erlang.nif_error(:nif_not_loaded)
+  def get_metrics(_metrics), do: :erlang.nif_error(:nif_not_loaded)
+  def new_cache(_max_entries, _ttl), do: :erlang.nif_error(:nif_not_loaded)
+
+  def register_grammar(_name, _path, _symbol, _highlights_query, _injections_query, _locals_query),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  def register_incompatible_grammar(_name), do: :erlang.nif_error(:nif_not_loaded)
+  def images_missing_alt(_md, _options), do: :erlang.nif_error(:nif_not_loaded)
+  def seo_metadata(_md, _options, _description_length, _unit), do: :erlang.nif_error(:nif_not_loaded)
+  def highlight_code_svg(_source, _lang, _theme, _options), do: :erlang.nif_error(:nif_not_loaded)
+  def highlight_tokens(_source, _lang), do: :erlang.nif_error(:nif_not_loaded)
 end
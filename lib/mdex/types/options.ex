@@ -9,7 +9,8 @@ defmodule MDEx.Types.ExtensionOptions do
             header_ids: nil,
             footnotes: false,
             description_lists: false,
-            front_matter_delimiter: nil
+            front_matter_delimiter: nil,
+            shortcodes: false
 end
 
 defmodule MDEx.Types.ParseOptions do
@@ -31,10 +32,155 @@ defmodule MDEx.Types.RenderOptions do
             sourcepos: false
 end
 
+defmodule MDEx.Types.DescriptionListOptions do
+  @moduledoc false
+  defstruct dl_class: nil,
+            dt_class: nil,
+            dd_class: nil,
+            tight: false
+end
+
+defmodule MDEx.Types.CodeBlockAttributesOptions do
+  @moduledoc false
+  defstruct translate: "no",
+            tabindex: nil,
+            pre: %{},
+            code: %{}
+end
+
 defmodule MDEx.Types.FeaturesOptions do
   @moduledoc false
   defstruct sanitize: false,
-            syntax_highlight_theme: "onedark"
+            syntax_highlight_theme: "onedark",
+            max_input_bytes: nil,
+            max_output_bytes: nil,
+            metrics: nil,
+            description_list: nil,
+            entity_encoding: :utf8,
+            code_block_attributes: nil,
+            input_limits: nil,
+            heading_ids: nil,
+            number_headings: nil,
+            document_dir: nil,
+            audit: false,
+            before_html: nil,
+            after_html: nil,
+            wrapper: nil,
+            data_images: nil,
+            cache: nil,
+            responsive_images: nil,
+            video_embeds: nil,
+            typography: nil,
+            toc: nil,
+            link_titles: false,
+            max_spans_per_block: nil,
+            headings_as: :heading,
+            short_links: nil,
+            detect_language: false,
+            lang: nil,
+            id_prefix: nil,
+            replacements: [],
+            abbreviations: false,
+            omit: [],
+            base_url: nil,
+            heading_lint: nil,
+            internal_links: nil,
+            diff_words: false,
+            heading_template: nil,
+            collect_ids: false,
+            broken_link_resolver: nil,
+            timeout: nil,
+            relative_urls: [],
+            attribute_rules: []
+end
+
+defmodule MDEx.Types.InputLimitsOptions do
+  @moduledoc false
+  defstruct max_nesting_depth: 100,
+            max_delimiter_runs: 5_000,
+            max_link_definitions: 10_000,
+            max_table_cells: 100_000
+end
+
+defmodule MDEx.Types.HeadingIdsOptions do
+  @moduledoc false
+  defstruct prefix: "",
+            suffix: "",
+            collisions: :number,
+            source: :text,
+            exclude: []
+end
+
+defmodule MDEx.Types.NumberHeadingsOptions do
+  @moduledoc false
+  defstruct levels: 1..6,
+            format: "1.2.3 ",
+            anchors: false
+end
+
+defmodule MDEx.Types.HeadingLintOptions do
+  @moduledoc false
+  defstruct duplicates: true,
+            max_length: nil,
+            unit: :grapheme
+end
+
+defmodule MDEx.Types.TocOptions do
+  @moduledoc false
+  defstruct levels: 1..6
+end
+
+defmodule MDEx.Types.ShortLinksOptions do
+  @moduledoc false
+  defstruct rules: [:github_issues, :github_pull_requests, :github_commits]
+end
+
+defmodule MDEx.Types.InternalLinksOptions do
+  @moduledoc false
+  defstruct prefixes: ["/"],
+            path_prefix: nil,
+            attributes: []
+end
+
+defmodule MDEx.Types.LangOptions do
+  @moduledoc false
+  defstruct tag: nil,
+            per_block: false
+end
+
+defmodule MDEx.Types.DataImagesOptions do
+  @moduledoc false
+  defstruct max_bytes: 16_384,
+            mime_types: ["image/png", "image/jpeg", "image/gif", "image/webp"]
+end
+
+defmodule MDEx.Types.ResponsiveImagesOptions do
+  @moduledoc false
+  defstruct widths: [480, 960, 1440],
+            url: "{url}?w={width}",
+            sizes: nil
+end
+
+defmodule MDEx.Types.VideoEmbedsOptions do
+  @moduledoc false
+  defstruct providers: [:youtube, :vimeo],
+            wrapper_class: "video-embed"
+end
+
+defmodule MDEx.Types.TypographyOptions do
+  @moduledoc false
+  defstruct collapse_spaces: true,
+            trim_trailing: true,
+            non_breaking_spaces: true,
+            space_before_punctuation: true
+end
+
+defmodule MDEx.Types.SvgOptions do
+  @moduledoc false
+  defstruct font_family: "monospace",
+            font_size: 14,
+            line_height: 1.5,
+            padding: 16
 end
 
 defmodule MDEx.Types.Options do
@@ -42,5 +188,6 @@ defmodule MDEx.Types.Options do
   defstruct extension: %MDEx.Types.ExtensionOptions{},
             parse: %MDEx.Types.ParseOptions{},
             render: %MDEx.Types.RenderOptions{},
-            features: %MDEx.Types.FeaturesOptions{}
+            features: %MDEx.Types.FeaturesOptions{},
+            preset: nil
 end
@@ -0,0 +1,154 @@
+use crate::raw_html::{allow_injected_html, escape_html, html_inline, new_node};
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{Arena, ComrakOptions};
+use std::borrow::Cow;
+
+// `(abbreviation, title)`
+pub type Abbreviation = (String, String);
+
+// Removes Markdown Extra abbreviation definitions, `*[HTML]: HyperText Markup
+// Language` lines outside of code fences, from the markdown. Lines are blanked
+// instead of removed so source positions don't change.
+pub fn extract(md: &str) -> (Cow<'_, str>, Vec<Abbreviation>) {
+    let mut abbreviations = Vec::new();
+    let mut stripped = String::with_capacity(md.len());
+    let mut fence: Option<&str> = None;
+
+    for line in md.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+
+        match fence {
+            Some(marker) if trimmed.starts_with(marker) => fence = None,
+            Some(_) => (),
+            None => {
+                fence = ["```", "~~~"]
+                    .into_iter()
+                    .find(|marker| trimmed.starts_with(marker));
+            }
+        }
+
+        match definition(line).filter(|_| fence.is_none()) {
+            Some(abbreviation) => {
+                abbreviations.push(abbreviation);
+                stripped.push_str(&line[line.trim_end_matches(['\r', '\n']).len()..]);
+            }
+            None => stripped.push_str(line),
+        }
+    }
+
+    match abbreviations.is_empty() {
+        true => (Cow::Borrowed(md), abbreviations),
+        false => (Cow::Owned(stripped), abbreviations),
+    }
+}
+
+fn definition(line: &str) -> Option<Abbreviation> {
+    let rest = line.strip_prefix("*[")?;
+    let (abbreviation, title) = rest.split_once("]:")?;
+
+    if abbreviation.trim().is_empty() || abbreviation.contains(['[', ']']) {
+        return None;
+    }
+
+    Some((abbreviation.trim().to_string(), title.trim().to_string()))
+}
+
+// Wraps the whole word occurrences of the abbreviations in the text of the
+// document with `<abbr>`, preferring the longest abbreviation at each
+// position. Code and raw html are left untouched.
+pub fn apply<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    root: &'a AstNode<'a>,
+    abbreviations: &[Abbreviation],
+    comrak_options: &mut ComrakOptions,
+) {
+    let mut abbreviations: Vec<_> = abbreviations.iter().collect();
+    abbreviations.sort_by_key(|(abbreviation, _)| std::cmp::Reverse(abbreviation.len()));
+
+    let texts: Vec<_> = root
+        .descendants()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::Text(_)))
+        .collect();
+    let mut injected = false;
+
+    for node in texts {
+        let literal = match node.data.borrow().value {
+            NodeValue::Text(ref literal) => literal.clone(),
+            _ => continue,
+        };
+
+        let parts = split(&literal, &abbreviations);
+
+        if parts.len() == 1 {
+            continue;
+        }
+
+        if !injected {
+            allow_injected_html(root, comrak_options);
+            injected = true;
+        }
+
+        for (text, title) in parts {
+            if text.is_empty() {
+                continue;
+            }
+
+            let text_node = new_node(arena, NodeValue::Text(text.to_string()));
+
+            match title {
+                Some(title) => {
+                    node.insert_before(html_inline(
+                        arena,
+                        format!("<abbr title=\"{}\">", escape_html(title)),
+                    ));
+                    node.insert_before(text_node);
+                    node.insert_before(html_inline(arena, "</abbr>".to_string()));
+                }
+                None => node.insert_before(text_node),
+            }
+        }
+
+        node.detach();
+    }
+}
+
+// Text split at the abbreviations, with the title of the abbreviation parts.
+fn split<'t>(text: &'t str, abbreviations: &[&'t Abbreviation]) -> Vec<(&'t str, Option<&'t str>)> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut index = 0;
+
+    while index < text.len() {
+        let at_boundary = text[..index]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric());
+
+        let found = abbreviations.iter().find(|(abbreviation, _)| {
+            at_boundary
+                && text[index..].starts_with(abbreviation.as_str())
+                && text[index + abbreviation.len()..]
+                    .chars()
+                    .next()
+                    .is_none_or(|c| !c.is_alphanumeric())
+        });
+
+        match found {
+            Some((abbreviation, title)) => {
+                parts.push((&text[start..index], None));
+                parts.push((
+                    &text[index..index + abbreviation.len()],
+                    Some(title.as_str()),
+                ));
+                index += abbreviation.len();
+                start = index;
+            }
+            None => {
+                index += text[index..].chars().next().map_or(1, char::len_utf8);
+            }
+        }
+    }
+
+    parts.push((&text[start..], None));
+    parts
+}
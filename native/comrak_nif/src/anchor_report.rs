@@ -0,0 +1,72 @@
+use crate::headings;
+use crate::render::RenderError;
+use crate::types::options::ExOptions;
+use std::collections::BTreeMap;
+
+#[derive(Debug, NifMap)]
+pub struct ExAnchor {
+    pub path: String,
+    pub id: String,
+    pub url: String,
+    pub level: u8,
+    pub text: String,
+}
+
+#[derive(Debug, NifMap)]
+pub struct ExAnchorCollision {
+    pub id: String,
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, NifMap)]
+pub struct ExAnchorReport {
+    pub anchors: Vec<ExAnchor>,
+    pub collisions: Vec<ExAnchorCollision>,
+}
+
+// Heading anchors of each document, with the same ids the documents get when
+// rendered on their own, and the ids generated by more than one document,
+// which would collide if the documents were combined into a single page.
+pub fn anchor_report(
+    documents: Vec<(String, &str, ExOptions)>,
+) -> Result<ExAnchorReport, RenderError> {
+    let mut anchors = Vec::new();
+
+    for (path, md, options) in documents {
+        anchors.extend(
+            headings::parse_headings(md, options)?
+                .into_iter()
+                .map(|heading| ExAnchor {
+                    url: format!("{}#{}", path, heading.id),
+                    path: path.clone(),
+                    id: heading.id,
+                    level: heading.level,
+                    text: heading.text,
+                }),
+        );
+    }
+
+    let mut paths_by_id: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+
+    for anchor in &anchors {
+        let paths = paths_by_id.entry(&anchor.id).or_default();
+
+        if !paths.contains(&anchor.path) {
+            paths.push(anchor.path.clone());
+        }
+    }
+
+    let collisions = paths_by_id
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(id, paths)| ExAnchorCollision {
+            id: id.to_string(),
+            paths,
+        })
+        .collect();
+
+    Ok(ExAnchorReport {
+        anchors,
+        collisions,
+    })
+}
@@ -0,0 +1,543 @@
+use crate::broken_links;
+use crate::grammar_registry;
+use crate::render::{self, RenderError};
+use crate::theme;
+use crate::types::options::ExOptions;
+use autumn::themes::Theme;
+use comrak::nodes::{AstNode, ListDelimType, ListType, NodeValue, TableAlignment};
+use comrak::Arena;
+use inkjet::constants::HIGHLIGHT_NAMES;
+use tree_sitter_highlight::{HighlightEvent, Highlighter};
+use unicode_segmentation::UnicodeSegmentation;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const ITALIC: &str = "\x1b[3m";
+const UNDERLINE: &str = "\x1b[4m";
+const STRIKETHROUGH: &str = "\x1b[9m";
+const BLUE: &str = "\x1b[34m";
+const CYAN: &str = "\x1b[36m";
+
+// Thematic breaks span `render: [width: n]`, or this many columns.
+const DEFAULT_WIDTH: usize = 80;
+
+// The document as text for terminals, styled with SGR escape sequences.
+// Code blocks are highlighted with the 24-bit colors of the
+// `syntax_highlight_theme`, everything else with the basic styles and colors
+// every terminal supports, so the output follows the terminal palette.
+pub fn render(md: &str, options: ExOptions) -> Result<String, RenderError> {
+    let (_, comrak_options, features) = render::resolve_options(options)?;
+
+    render::check_input(md, &features)?;
+
+    let theme = match features.syntax_highlight_theme {
+        Some(ref theme) => Some(theme::resolve(theme)?),
+        None => None,
+    };
+    let width = match comrak_options.render.width {
+        0 => DEFAULT_WIDTH,
+        width => width,
+    };
+
+    let arena = Arena::new();
+    let root = broken_links::parse_document(
+        &arena,
+        md,
+        &comrak_options,
+        features.broken_link_resolver.as_ref(),
+    );
+    let mut terminal = Terminal::new(theme, width);
+
+    terminal.blocks(root);
+    Ok(terminal.output)
+}
+
+// Written at the start of every line of a block quote, list item or
+// footnote, `first` only on the first line, like list markers.
+struct Prefix {
+    first: Option<String>,
+    rest: String,
+}
+
+struct Terminal<'t> {
+    output: String,
+    prefixes: Vec<Prefix>,
+    styles: Vec<String>,
+    line_start: bool,
+    blank_line: bool,
+    tight: bool,
+    theme: Option<&'t Theme>,
+    width: usize,
+}
+
+impl<'t> Terminal<'t> {
+    fn new(theme: Option<&'t Theme>, width: usize) -> Self {
+        Self {
+            output: String::new(),
+            prefixes: Vec::new(),
+            styles: Vec::new(),
+            line_start: true,
+            blank_line: false,
+            tight: false,
+            theme,
+            width,
+        }
+    }
+
+    fn blocks<'a>(&mut self, node: &'a AstNode<'a>) {
+        for child in node.children() {
+            self.block(child);
+        }
+    }
+
+    fn block<'a>(&mut self, node: &'a AstNode<'a>) {
+        match node.data.borrow().value {
+            NodeValue::Document | NodeValue::DescriptionList | NodeValue::DescriptionItem(_) => {
+                self.blocks(node)
+            }
+            NodeValue::FrontMatter(_) => (),
+            NodeValue::Paragraph => {
+                self.start_block();
+                self.inlines(node);
+                self.end_block();
+                self.blank_line = !self.tight;
+            }
+            NodeValue::Heading(ref heading) => {
+                self.start_block();
+                self.push_style(format!("{}{}", BOLD, BLUE));
+                self.write(&"#".repeat(heading.level as usize));
+                self.write(" ");
+                self.inlines(node);
+                self.pop_style();
+                self.end_block();
+            }
+            NodeValue::BlockQuote => {
+                self.start_block();
+                self.prefixes.push(Prefix {
+                    first: None,
+                    rest: format!("{}│{} ", DIM, RESET),
+                });
+                self.blocks(node);
+                self.prefixes.pop();
+                self.blank_line = true;
+            }
+            NodeValue::List(ref list) => {
+                let tight = std::mem::replace(&mut self.tight, list.tight);
+
+                self.start_block();
+
+                for (index, item) in node.children().enumerate() {
+                    let marker = match list.list_type {
+                        ListType::Bullet => "•".to_string(),
+                        ListType::Ordered => {
+                            let delimiter = match list.delimiter {
+                                ListDelimType::Period => '.',
+                                ListDelimType::Paren => ')',
+                            };
+
+                            format!("{}{}", list.start + index, delimiter)
+                        }
+                    };
+                    let marker = match item.data.borrow().value {
+                        NodeValue::TaskItem(Some(_)) => format!("{} [x] ", marker),
+                        NodeValue::TaskItem(None) => format!("{} [ ] ", marker),
+                        _ => format!("{} ", marker),
+                    };
+
+                    if index == 0 || self.tight {
+                        self.blank_line = false;
+                    }
+
+                    self.item(marker, item);
+                }
+
+                self.tight = tight;
+                self.blank_line = true;
+            }
+            NodeValue::FootnoteDefinition(ref name) => {
+                self.start_block();
+                self.item(format!("{}[^{}]{} ", DIM, name, RESET), node);
+                self.blank_line = true;
+            }
+            NodeValue::DescriptionTerm => {
+                self.push_style(BOLD.to_string());
+                self.blocks(node);
+                self.pop_style();
+            }
+            NodeValue::DescriptionDetails => {
+                self.prefixes.push(Prefix {
+                    first: None,
+                    rest: "  ".to_string(),
+                });
+                self.blocks(node);
+                self.prefixes.pop();
+            }
+            NodeValue::CodeBlock(ref block) => {
+                let lang = block.info.split_whitespace().next().unwrap_or_default();
+
+                self.start_block();
+                self.prefixes.push(Prefix {
+                    first: None,
+                    rest: "  ".to_string(),
+                });
+                self.code(block.literal.trim_end_matches('\n'), lang);
+                self.prefixes.pop();
+                self.end_block();
+            }
+            NodeValue::HtmlBlock(ref block) => {
+                self.start_block();
+                self.push_style(DIM.to_string());
+                self.write(block.literal.trim_end_matches('\n'));
+                self.pop_style();
+                self.end_block();
+            }
+            NodeValue::ThematicBreak => {
+                self.start_block();
+                self.push_style(DIM.to_string());
+                self.write(&"─".repeat(self.width));
+                self.pop_style();
+                self.end_block();
+            }
+            NodeValue::Table(ref alignments) => {
+                self.start_block();
+                self.table(node, alignments);
+                self.blank_line = true;
+            }
+            _ => self.inlines(node),
+        }
+    }
+
+    // The children of a list item or footnote, with the marker on the first
+    // line and the following lines aligned with the content.
+    fn item<'a>(&mut self, marker: String, node: &'a AstNode<'a>) {
+        let indent = " ".repeat(visible_width(&marker));
+
+        self.prefixes.push(Prefix {
+            first: Some(marker),
+            rest: indent,
+        });
+
+        match node.children().next() {
+            Some(_) => self.blocks(node),
+            None => {
+                self.write_prefixes();
+                self.newline();
+            }
+        }
+
+        self.prefixes.pop();
+    }
+
+    fn inlines<'a>(&mut self, node: &'a AstNode<'a>) {
+        for child in node.children() {
+            self.inline(child);
+        }
+    }
+
+    fn inline<'a>(&mut self, node: &'a AstNode<'a>) {
+        match node.data.borrow().value {
+            NodeValue::Text(ref literal) => self.write(literal),
+            NodeValue::SoftBreak | NodeValue::LineBreak => self.newline(),
+            NodeValue::Code(ref code) => self.styled(CYAN, &code.literal),
+            NodeValue::HtmlInline(ref literal) => self.styled(DIM, literal),
+            NodeValue::Emph => self.styled_inlines(ITALIC, node),
+            NodeValue::Strong => self.styled_inlines(BOLD, node),
+            NodeValue::Strikethrough => self.styled_inlines(STRIKETHROUGH, node),
+            NodeValue::Link(ref link) => {
+                self.push_style(format!("{}{}", UNDERLINE, BLUE));
+                self.inlines(node);
+                self.pop_style();
+
+                // autolinks already show the url
+                if !link.url.is_empty() && link.url != text(node) {
+                    self.styled(DIM, &format!(" ({})", link.url));
+                }
+            }
+            NodeValue::Image(_) => {
+                self.push_style(DIM.to_string());
+                self.write("[image: ");
+                self.inlines(node);
+                self.write("]");
+                self.pop_style();
+            }
+            NodeValue::FootnoteReference(ref name) => self.styled(DIM, &format!("[^{}]", name)),
+            NodeValue::ShortCode(ref shortcode) => {
+                if let Some(emoji) = shortcode.emoji() {
+                    self.write(&emoji);
+                }
+            }
+            _ => self.inlines(node),
+        }
+    }
+
+    fn styled(&mut self, style: &str, text: &str) {
+        self.push_style(style.to_string());
+        self.write(text);
+        self.pop_style();
+    }
+
+    fn styled_inlines<'a>(&mut self, style: &str, node: &'a AstNode<'a>) {
+        self.push_style(style.to_string());
+        self.inlines(node);
+        self.pop_style();
+    }
+
+    // Highlighted with the theme when both the theme and the language are
+    // known, otherwise written as is, also when the highlighter fails.
+    fn code(&mut self, source: &str, lang: &str) {
+        let (theme, config) = match (self.theme, grammar_registry::config(lang)) {
+            (Some(theme), Some(config)) => (theme, config),
+            _ => return self.write_code(source),
+        };
+
+        let mut highlighter = Highlighter::new();
+        let events = highlighter
+            .highlight(config, source.as_bytes(), None, |token| {
+                grammar_registry::config(token)
+            })
+            .and_then(|highlights| highlights.collect::<Result<Vec<_>, _>>());
+
+        let events = match events {
+            Ok(events) => events,
+            Err(_) => return self.write_code(source),
+        };
+
+        for event in events {
+            match event {
+                HighlightEvent::Source { start, end } => {
+                    self.write_code(source.get(start..end).unwrap_or_default())
+                }
+                HighlightEvent::HighlightStart(highlight) => {
+                    self.push_style(sgr(theme, HIGHLIGHT_NAMES[highlight.0]))
+                }
+                HighlightEvent::HighlightEnd => self.pop_style(),
+            }
+        }
+    }
+
+    // Every line of code gets the prefixes, also empty ones, so block quote
+    // bars stay continuous.
+    fn write_code(&mut self, text: &str) {
+        let mut lines = text.split('\n').peekable();
+
+        while let Some(line) = lines.next() {
+            match (self.line_start, line.is_empty() && lines.peek().is_some()) {
+                (true, true) => self.write_blank_line(),
+                (true, false) => self.write_prefixes(),
+                (false, _) => (),
+            }
+
+            self.output.push_str(line);
+
+            if lines.peek().is_some() && !self.line_start {
+                self.newline();
+            }
+        }
+    }
+
+    // Columns are as wide as their widest cell, the header row is separated
+    // from the body by a rule.
+    fn table<'a>(&mut self, node: &'a AstNode<'a>, alignments: &[TableAlignment]) {
+        let rows: Vec<Vec<String>> = node
+            .children()
+            .map(|row| {
+                row.children()
+                    .map(|cell| {
+                        let mut terminal = Terminal::new(self.theme, self.width);
+                        terminal.inlines(cell);
+                        terminal.output
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut widths = vec![0; alignments.len()];
+
+        for row in &rows {
+            for (column, cell) in row.iter().enumerate().take(widths.len()) {
+                widths[column] = widths[column].max(visible_width(cell));
+            }
+        }
+
+        for (index, row) in rows.iter().enumerate() {
+            let cells: Vec<String> = widths
+                .iter()
+                .enumerate()
+                .map(|(column, width)| {
+                    let cell = row.get(column).map_or("", String::as_str);
+                    let padding = width - visible_width(cell);
+                    let (left, right) = match alignments[column] {
+                        TableAlignment::Right => (padding, 0),
+                        TableAlignment::Center => (padding / 2, padding - padding / 2),
+                        TableAlignment::Left | TableAlignment::None => (0, padding),
+                    };
+
+                    format!("{}{}{}", " ".repeat(left), cell, " ".repeat(right))
+                })
+                .collect();
+
+            self.write(&cells.join(&format!(" {}│{} ", DIM, RESET)));
+            self.newline();
+
+            if index == 0 {
+                let rule: Vec<String> = widths.iter().map(|width| "─".repeat(*width)).collect();
+                self.styled(DIM, &rule.join("─┼─"));
+                self.newline();
+            }
+        }
+    }
+
+    // Separates the block from the previous one by an empty line, unless
+    // they are in a tight list.
+    fn start_block(&mut self) {
+        if !self.line_start {
+            self.newline();
+        }
+
+        if self.blank_line {
+            self.write_blank_line();
+        }
+
+        self.blank_line = false;
+    }
+
+    fn end_block(&mut self) {
+        if !self.line_start {
+            self.newline();
+        }
+
+        self.blank_line = true;
+    }
+
+    fn write(&mut self, text: &str) {
+        let mut lines = text.split('\n').peekable();
+
+        while let Some(line) = lines.next() {
+            if self.line_start && !line.is_empty() {
+                self.write_prefixes();
+            }
+
+            self.output.push_str(line);
+
+            if lines.peek().is_some() {
+                self.newline();
+            }
+        }
+    }
+
+    // Styles are reset at the end of every line and applied again after the
+    // prefixes of the next one, so prefixes are never styled and lines can be
+    // printed on their own.
+    fn newline(&mut self) {
+        if !self.line_start && !self.styles.is_empty() {
+            self.output.push_str(RESET);
+        }
+
+        self.output.push('\n');
+        self.line_start = true;
+    }
+
+    // Markers are left for the first line of the block.
+    fn write_blank_line(&mut self) {
+        let prefixes: String = self
+            .prefixes
+            .iter()
+            .map(|prefix| prefix.rest.as_str())
+            .collect();
+
+        self.output.push_str(prefixes.trim_end_matches(' '));
+        self.output.push('\n');
+        self.line_start = true;
+    }
+
+    fn write_prefixes(&mut self) {
+        for prefix in &mut self.prefixes {
+            match prefix.first.take() {
+                Some(first) => self.output.push_str(&first),
+                None => self.output.push_str(&prefix.rest),
+            }
+        }
+
+        self.styles
+            .iter()
+            .for_each(|style| self.output.push_str(style));
+        self.line_start = false;
+    }
+
+    fn push_style(&mut self, style: String) {
+        if !self.line_start {
+            self.output.push_str(&style);
+        }
+
+        self.styles.push(style);
+    }
+
+    fn pop_style(&mut self) {
+        self.styles.pop();
+
+        if !self.line_start {
+            self.output.push_str(RESET);
+            self.styles
+                .iter()
+                .for_each(|style| self.output.push_str(style));
+        }
+    }
+}
+
+// 24-bit color and font style of a theme scope.
+fn sgr(theme: &Theme, scope: &str) -> String {
+    let (_class, style) = theme.get_scope(scope);
+    let mut sgr = String::new();
+
+    for (property, value) in theme::declarations(style) {
+        match (property, value) {
+            ("color", color) => {
+                if let Some((r, g, b)) = rgb(color) {
+                    sgr.push_str(&format!("\x1b[38;2;{};{};{}m", r, g, b));
+                }
+            }
+            ("font-weight", "bold") => sgr.push_str(BOLD),
+            ("font-style", "italic") => sgr.push_str(ITALIC),
+            ("text-decoration", "underline") => sgr.push_str(UNDERLINE),
+            _ => (),
+        }
+    }
+
+    sgr
+}
+
+fn rgb(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color.strip_prefix('#')?;
+    let channel = |range| u8::from_str_radix(hex.get(range)?, 16).ok();
+
+    match hex.len() {
+        6 => Some((channel(0..2)?, channel(2..4)?, channel(4..6)?)),
+        _ => None,
+    }
+}
+
+// Columns taken by the text, without the escape sequences.
+fn visible_width(text: &str) -> usize {
+    let mut visible = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('\x1b') {
+        visible.push_str(&rest[..start]);
+        rest = &rest[start..];
+        rest = rest.find('m').map_or("", |end| &rest[end + 1..]);
+    }
+
+    visible.push_str(rest);
+    visible.graphemes(true).count()
+}
+
+fn text<'a>(node: &'a AstNode<'a>) -> String {
+    node.descendants()
+        .filter_map(|node| match node.data.borrow().value {
+            NodeValue::Text(ref literal) => Some(literal.clone()),
+            NodeValue::Code(ref code) => Some(code.literal.clone()),
+            _ => None,
+        })
+        .collect()
+}
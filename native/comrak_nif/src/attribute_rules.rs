@@ -0,0 +1,69 @@
+use crate::sanitize_preview::is_url_attribute;
+use crate::types::options::{ExAttributeAction, ExAttributeRule};
+use ammonia::url::ParseError;
+use ammonia::Url;
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+const ANY_TAG: &str = "*";
+const VALUE: &str = "{value}";
+
+// Applies the first rule matching the element and the attribute, keeping
+// the value when none does. Only sees the attributes the sanitizer allows.
+pub fn apply<'a>(
+    rules: &[ExAttributeRule],
+    url_schemes: &HashSet<&str>,
+    element: &str,
+    attribute: &str,
+    value: Cow<'a, str>,
+) -> Option<Cow<'a, str>> {
+    let rule = rules.iter().find(|rule| {
+        (rule.tag == ANY_TAG || rule.tag.eq_ignore_ascii_case(element))
+            && rule.attribute.eq_ignore_ascii_case(attribute)
+    });
+
+    match rule {
+        None => Some(value),
+        Some(rule) => match rule.action {
+            ExAttributeAction::Keep => Some(value),
+            ExAttributeAction::Drop => None,
+            ExAttributeAction::Rewrite => match rule.template {
+                Some(ref template) if is_url_attribute(element, attribute) => {
+                    rewrite_url(template, url_schemes, value)
+                }
+                Some(ref template) => Some(Cow::Owned(template.replace(VALUE, &value))),
+                None => Some(value),
+            },
+        },
+    }
+}
+
+// Ammonia checks urls before the attribute filter runs, so rewritten urls are
+// checked again here. Only relative urls are rewritten, and the result must
+// have an allowed scheme and the host of the template, so a value like
+// `@evil.com/x.png` can't move the url to another host.
+fn rewrite_url<'a>(
+    template: &str,
+    url_schemes: &HashSet<&str>,
+    value: Cow<'a, str>,
+) -> Option<Cow<'a, str>> {
+    if Url::parse(&value) != Err(ParseError::RelativeUrlWithoutBase) {
+        return Some(value);
+    }
+
+    let rewritten = template.replace(VALUE, &value);
+
+    match Url::parse(&rewritten) {
+        Ok(url) => {
+            let template = Url::parse(&template.replace(VALUE, "")).ok();
+            let same_host = template.is_none_or(|template| template.host_str() == url.host_str());
+
+            match url_schemes.contains(url.scheme()) && same_host {
+                true => Some(Cow::Owned(rewritten)),
+                false => None,
+            }
+        }
+        Err(ParseError::RelativeUrlWithoutBase) => Some(Cow::Owned(rewritten)),
+        Err(_) => None,
+    }
+}
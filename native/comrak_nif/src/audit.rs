@@ -0,0 +1,283 @@
+// Tripwire for documents rendered with raw html: removes `<script>` elements,
+// event handler attributes and `javascript:` urls from the generated html and
+// reports each removal. It's a tag scanner, not an html parser, and errs on the
+// side of removing too much.
+
+#[derive(Debug, NifUnitEnum)]
+pub enum ExViolationKind {
+    Script,
+    EventHandler,
+    JavascriptUrl,
+}
+
+#[derive(Debug, NifMap)]
+pub struct ExViolation {
+    pub kind: ExViolationKind,
+    pub snippet: String,
+}
+
+pub struct Tag<'a> {
+    pub name: &'a str,
+    pub closing: bool,
+    pub self_closing: bool,
+    pub attributes: Vec<Attribute<'a>>,
+    pub len: usize,
+}
+
+pub struct Attribute<'a> {
+    pub name: &'a str,
+    pub value: Option<&'a str>,
+    pub raw: &'a str,
+}
+
+// Returns the html without the violations, or `None` if there are none.
+pub fn audit(html: &str) -> (Option<String>, Vec<ExViolation>) {
+    let mut violations = Vec::new();
+    let mut audited = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        audited.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |end| end + 3);
+            audited.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+
+        let tag = match parse_tag(rest) {
+            Some(tag) => tag,
+            None => {
+                audited.push('<');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+
+        if tag.name.eq_ignore_ascii_case("script") {
+            let end = match tag.closing {
+                true => tag.len,
+                false => script_end(rest, tag.len),
+            };
+
+            violations.push(ExViolation {
+                kind: ExViolationKind::Script,
+                snippet: rest[..end].to_string(),
+            });
+            rest = &rest[end..];
+            continue;
+        }
+
+        let removed = violations.len();
+
+        let kept: Vec<_> = tag
+            .attributes
+            .iter()
+            .filter(|attribute| match violation(attribute) {
+                Some(kind) => {
+                    violations.push(ExViolation {
+                        kind,
+                        snippet: attribute.raw.to_string(),
+                    });
+                    false
+                }
+                None => true,
+            })
+            .collect();
+
+        if violations.len() == removed {
+            audited.push_str(&rest[..tag.len]);
+        } else {
+            audited.push('<');
+            audited.push_str(tag.name);
+            kept.iter().for_each(|attribute| {
+                audited.push(' ');
+                audited.push_str(attribute.raw);
+            });
+            audited.push_str(if tag.self_closing { " />" } else { ">" });
+        }
+
+        rest = &rest[tag.len..];
+    }
+
+    audited.push_str(rest);
+
+    match violations.is_empty() {
+        true => (None, violations),
+        false => (Some(audited), violations),
+    }
+}
+
+fn violation(attribute: &Attribute) -> Option<ExViolationKind> {
+    let name = attribute.name.as_bytes();
+
+    if name.len() > 2 && name[..2].eq_ignore_ascii_case(b"on") {
+        return Some(ExViolationKind::EventHandler);
+    }
+
+    // browsers decode entities and ignore whitespace and control characters
+    // in the scheme, so `jav&#x09;ascript:` is still a javascript url
+    let value: String = decode_entities(attribute.value?)
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control())
+        .take("javascript:".len())
+        .collect();
+
+    match value.eq_ignore_ascii_case("javascript:") {
+        true => Some(ExViolationKind::JavascriptUrl),
+        false => None,
+    }
+}
+
+// End of the script element starting at `html`, including the closing tag, or
+// the end of the html if it's never closed.
+fn script_end(html: &str, open_tag_len: usize) -> usize {
+    let mut offset = open_tag_len;
+
+    while let Some(start) = html[offset..].find("</") {
+        let start = offset + start;
+
+        if let Some(tag) = parse_tag(&html[start..]) {
+            if tag.name.eq_ignore_ascii_case("script") {
+                return start + tag.len;
+            }
+        }
+
+        offset = start + 2;
+    }
+
+    html.len()
+}
+
+pub fn parse_tag(html: &str) -> Option<Tag<'_>> {
+    let bytes = html.as_bytes();
+    let closing = bytes.get(1) == Some(&b'/');
+    let name_start = if closing { 2 } else { 1 };
+
+    if !bytes.get(name_start)?.is_ascii_alphabetic() {
+        return None;
+    }
+
+    let name_end = name_start
+        + bytes[name_start..]
+            .iter()
+            .take_while(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b':'))
+            .count();
+
+    let mut tag = Tag {
+        name: &html[name_start..name_end],
+        closing,
+        self_closing: false,
+        attributes: Vec::new(),
+        len: 0,
+    };
+    let mut index = name_end;
+
+    loop {
+        while bytes.get(index)?.is_ascii_whitespace() || bytes[index] == b'/' {
+            tag.self_closing = bytes[index] == b'/';
+            index += 1;
+        }
+
+        if bytes[index] == b'>' {
+            tag.len = index + 1;
+            return Some(tag);
+        }
+
+        let start = index;
+
+        while !matches!(bytes.get(index)?, b'=' | b'>' | b'/')
+            && !bytes[index].is_ascii_whitespace()
+        {
+            index += 1;
+        }
+
+        let name = &html[start..index];
+        let mut value = None;
+        let mut after_name = index;
+
+        while bytes.get(after_name)?.is_ascii_whitespace() {
+            after_name += 1;
+        }
+
+        if bytes[after_name] == b'=' {
+            index = after_name + 1;
+
+            while bytes.get(index)?.is_ascii_whitespace() {
+                index += 1;
+            }
+
+            let value_start = index;
+
+            match bytes[index] {
+                quote @ (b'"' | b'\'') => {
+                    let len = html[index + 1..].find(quote as char)?;
+                    value = Some(&html[index + 1..index + 1 + len]);
+                    index += len + 2;
+                }
+                _ => {
+                    while !bytes.get(index)?.is_ascii_whitespace() && bytes[index] != b'>' {
+                        index += 1;
+                    }
+                    value = Some(&html[value_start..index]);
+                }
+            }
+        }
+
+        tag.attributes.push(Attribute {
+            name,
+            value,
+            raw: &html[start..index],
+        });
+    }
+}
+
+// Decodes numeric character references, with or without the trailing `;`,
+// and the named references that can hide a scheme.
+fn decode_entities(value: &str) -> String {
+    let mut decoded = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        if let Some(numeric) = rest.strip_prefix('#') {
+            let (digits, radix) = match numeric.strip_prefix(['x', 'X']) {
+                Some(hex) => (hex, 16),
+                None => (numeric, 10),
+            };
+            let len = digits.chars().take_while(|c| c.is_digit(radix)).count();
+
+            if let Some(c) = u32::from_str_radix(&digits[..len], radix)
+                .ok()
+                .and_then(char::from_u32)
+            {
+                decoded.push(c);
+                let after = &digits[len..];
+                rest = after.strip_prefix(';').unwrap_or(after);
+                continue;
+            }
+        }
+
+        let named = [("colon;", ':'), ("tab;", '\t'), ("newline;", '\n')]
+            .into_iter()
+            .find(|(name, _)| {
+                rest.get(..name.len())
+                    .is_some_and(|prefix| prefix.eq_ignore_ascii_case(name))
+            });
+
+        match named {
+            Some((name, c)) => {
+                decoded.push(c);
+                rest = &rest[name.len()..];
+            }
+            None => decoded.push('&'),
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
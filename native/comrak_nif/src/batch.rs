@@ -0,0 +1,134 @@
+use crate::job::CancellationToken;
+use crate::pool;
+use crate::render::{self, Html, RenderError};
+use crate::types::options::ExOptions;
+use std::iter::Enumerate;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::vec::IntoIter;
+
+// Renders each `(markdown, options)` document on its own, on up to
+// `max_concurrency` threads that take the next document as soon as they are
+// done, so a few large documents don't hold back the rest. Results are in the
+// order of the documents.
+pub fn render(
+    documents: Vec<(&str, ExOptions)>,
+    max_concurrency: usize,
+) -> Vec<Result<Html, RenderError>> {
+    let workers = max_concurrency.clamp(1, documents.len().max(1));
+
+    if workers == 1 {
+        return documents
+            .into_iter()
+            .map(|(md, options)| render::render_html(md, options, None))
+            .collect();
+    }
+
+    let queue = Mutex::new(documents.into_iter().enumerate());
+
+    let mut rendered: Vec<(usize, Result<Html, RenderError>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut rendered = Vec::new();
+
+                    loop {
+                        let next = queue.lock().unwrap().next();
+
+                        match next {
+                            Some((index, (md, options))) => {
+                                rendered.push((index, render::render_html(md, options, None)))
+                            }
+                            None => return rendered,
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("render thread panicked"))
+            .collect()
+    });
+
+    rendered.sort_unstable_by_key(|(index, _)| *index);
+    rendered.into_iter().map(|(_, result)| result).collect()
+}
+
+// Like `render` for a batch that outlives the caller, with up to
+// `max_concurrency` workers running on the shared render pool instead of
+// threads of their own. `done` gets the index and result of each document as
+// soon as it's rendered, and the last worker to stop hands what `done`
+// returned to `finished`, in the order of the documents. Once cancelled,
+// documents not started yet are skipped and `finished` isn't called.
+pub fn spawn_each<T, F, G>(
+    documents: Vec<(String, ExOptions)>,
+    max_concurrency: usize,
+    cancellation: CancellationToken,
+    done: F,
+    finished: G,
+) where
+    T: Send + 'static,
+    F: Fn(usize, Result<Html, RenderError>) -> T + Send + Sync + 'static,
+    G: FnOnce(Vec<T>) + Send + 'static,
+{
+    let workers = max_concurrency.clamp(1, documents.len().max(1));
+    let batch = Arc::new(Batch {
+        queue: Mutex::new(documents.into_iter().enumerate()),
+        rendered: Mutex::new(Vec::new()),
+        running: AtomicUsize::new(workers),
+        cancellation,
+        done,
+        finished: Mutex::new(Some(finished)),
+    });
+
+    for _ in 0..workers {
+        let batch = batch.clone();
+        pool::spawn(move || batch.work());
+    }
+}
+
+struct Batch<T, F, G> {
+    queue: Mutex<Enumerate<IntoIter<(String, ExOptions)>>>,
+    rendered: Mutex<Vec<(usize, T)>>,
+    running: AtomicUsize,
+    cancellation: CancellationToken,
+    done: F,
+    finished: Mutex<Option<G>>,
+}
+
+impl<T, F, G> Batch<T, F, G>
+where
+    F: Fn(usize, Result<Html, RenderError>) -> T,
+    G: FnOnce(Vec<T>),
+{
+    fn work(&self) {
+        while !self.cancellation.is_cancelled() {
+            let next = self.queue.lock().unwrap().next();
+
+            match next {
+                Some((index, (md, options))) => {
+                    let result = render::catch_panic(|| {
+                        render::render_html(&md, options, Some(self.cancellation.clone()))
+                    });
+                    let result = (self.done)(index, result);
+                    self.rendered.lock().unwrap().push((index, result));
+                }
+                None => break,
+            }
+        }
+
+        if self.running.fetch_sub(1, Ordering::AcqRel) > 1 || self.cancellation.is_cancelled() {
+            return;
+        }
+
+        let mut rendered = std::mem::take(&mut *self.rendered.lock().unwrap());
+        rendered.sort_unstable_by_key(|(index, _)| *index);
+
+        if let Some(finished) = self.finished.lock().unwrap().take() {
+            finished(rendered.into_iter().map(|(_, result)| result).collect());
+        }
+    }
+}
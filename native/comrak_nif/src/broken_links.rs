@@ -0,0 +1,62 @@
+use crate::types::options::ExBrokenLinkResolver;
+use comrak::nodes::AstNode;
+use comrak::{parse_document_with_broken_link_callback, Arena, ComrakOptions};
+
+const REF: &str = "{ref}";
+
+// Parses the document resolving `[label]`, `[text][label]` and `[label][]`
+// references without a definition, like wiki links, from a url template or a
+// map of labels to urls. References the resolver doesn't know stay text.
+pub fn parse_document<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    md: &str,
+    options: &ComrakOptions,
+    resolver: Option<&ExBrokenLinkResolver>,
+) -> &'a AstNode<'a> {
+    match resolver {
+        Some(resolver) => {
+            let mut callback = |label: &str| resolve(resolver, label);
+            parse_document_with_broken_link_callback(arena, md, options, Some(&mut callback))
+        }
+        None => comrak::parse_document(arena, md, options),
+    }
+}
+
+// Labels are matched like comrak matches reference definitions, ignoring
+// case and runs of whitespace. `{ref}` is replaced by the label matched this
+// way, percent-encoded.
+fn resolve(resolver: &ExBrokenLinkResolver, label: &str) -> Option<(String, String)> {
+    let label = normalize(label);
+    let url = match resolver {
+        ExBrokenLinkResolver::Template(template) => template.replace(REF, &percent_encode(&label)),
+        ExBrokenLinkResolver::Links(links) => links
+            .iter()
+            .find(|(candidate, _)| normalize(candidate) == label)
+            .map(|(_, url)| url.clone())?,
+    };
+
+    Some((url, String::new()))
+}
+
+fn normalize(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
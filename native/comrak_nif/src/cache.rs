@@ -0,0 +1,134 @@
+use rustler::{Decoder, NifResult, ResourceArc, Term};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+// LRU cache of rendered html keyed by the markdown and the options, so hot
+// documents skip the whole pipeline.
+#[derive(Debug)]
+pub struct CacheResource {
+    max_entries: usize,
+    ttl: Option<Duration>,
+    entries: Mutex<Entries>,
+}
+
+// `options` is the options term in the external term format, which covers
+// every option without hashing the decoded structs. The whole key is compared
+// on lookup, so a hash collision can't return the html of another document.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Key {
+    md: Box<str>,
+    options: Box<[u8]>,
+}
+
+impl Key {
+    pub fn new(md: &str, options: &[u8]) -> Self {
+        Key {
+            md: md.into(),
+            options: options.into(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    by_key: HashMap<Arc<Key>, Entry>,
+    // keys by last use, oldest first
+    by_use: BTreeMap<u64, Arc<Key>>,
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    html: Arc<[u8]>,
+    inserted_at: Instant,
+    used_at: u64,
+}
+
+impl CacheResource {
+    pub fn new(max_entries: usize, ttl: Option<Duration>) -> Self {
+        CacheResource {
+            max_entries,
+            ttl,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    pub fn get(&self, key: &Key) -> Option<Arc<[u8]>> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let inserted_at = entries.by_key.get(key)?.inserted_at;
+        let expired = self.ttl.is_some_and(|ttl| inserted_at.elapsed() > ttl);
+
+        if expired {
+            entries.remove(key);
+            return None;
+        }
+
+        entries.touch(key)
+    }
+
+    pub fn insert(&self, key: Key, html: &[u8]) {
+        if self.max_entries == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.remove(&key);
+
+        while entries.by_key.len() >= self.max_entries {
+            match entries.by_use.first_key_value() {
+                Some((_, oldest)) => {
+                    let oldest = oldest.clone();
+                    entries.remove(&oldest)
+                }
+                None => break,
+            }
+        }
+
+        let key = Arc::new(key);
+        entries.clock += 1;
+        let used_at = entries.clock;
+        entries.by_use.insert(used_at, key.clone());
+        entries.by_key.insert(
+            key,
+            Entry {
+                html: Arc::from(html),
+                inserted_at: Instant::now(),
+                used_at,
+            },
+        );
+    }
+}
+
+impl Entries {
+    fn touch(&mut self, key: &Key) -> Option<Arc<[u8]>> {
+        self.clock += 1;
+        let entry = self.by_key.get_mut(key)?;
+        let key = self.by_use.remove(&entry.used_at)?;
+        self.by_use.insert(self.clock, key);
+        entry.used_at = self.clock;
+        Some(entry.html.clone())
+    }
+
+    fn remove(&mut self, key: &Key) {
+        if let Some(entry) = self.by_key.remove(key) {
+            self.by_use.remove(&entry.used_at);
+        }
+    }
+}
+
+// Wraps the resource so it can be carried in the options structs.
+pub struct CacheRef(pub ResourceArc<CacheResource>);
+
+impl fmt::Debug for CacheRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CacheRef").finish()
+    }
+}
+
+impl<'a> Decoder<'a> for CacheRef {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        Ok(CacheRef(term.decode()?))
+    }
+}
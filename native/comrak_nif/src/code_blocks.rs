@@ -0,0 +1,69 @@
+use crate::entities;
+use crate::inkjet_adapter::InkjetAdapter;
+use crate::omit;
+use crate::presets;
+use crate::render::{self, RenderError};
+use crate::sanitizer;
+use crate::theme;
+use crate::types::options::{ExEntityEncoding, ExOptions};
+use comrak::nodes::NodeValue;
+use comrak::{format_html_with_plugins, parse_document, Arena, ComrakPlugins};
+
+// `(index, html)` of the code blocks of the document, rendered like
+// `render_html` renders them, without rendering the rest of the document. The
+// index is the position of the block among the code blocks of the document.
+pub fn to_html(md: &str, options: ExOptions) -> Result<Vec<(usize, String)>, RenderError> {
+    let (preset, comrak_options, features) = render::resolve_options(options)?;
+
+    render::check_input(md, &features)?;
+
+    let inkjet_adapter = match features.syntax_highlight_theme {
+        Some(ref theme) => Some(InkjetAdapter::new(
+            theme::resolve(theme)?,
+            features.code_block_attributes.as_ref(),
+            features.max_spans_per_block,
+            features.diff_words,
+        )),
+        None => None,
+    };
+    let mut plugins = ComrakPlugins::default();
+    if let Some(ref inkjet_adapter) = inkjet_adapter {
+        plugins.render.codefence_syntax_highlighter = Some(inkjet_adapter);
+    }
+
+    let arena = Arena::new();
+    let root = parse_document(&arena, md, &comrak_options);
+
+    if let Some(ref preset) = preset {
+        presets::transform(preset, &arena, root);
+    }
+
+    if !features.omit.is_empty() {
+        omit::apply(root, &features.omit);
+    }
+
+    let sanitizer = sanitizer::builder(preset.as_ref(), &features);
+
+    root.descendants()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::CodeBlock(_)))
+        .enumerate()
+        .map(|(index, node)| {
+            let mut output = Vec::new();
+            format_html_with_plugins(node, &comrak_options, &mut output, &plugins)
+                .map_err(|err| RenderError::Other(err.to_string()))?;
+
+            let mut html =
+                String::from_utf8(output).map_err(|err| RenderError::Other(err.to_string()))?;
+
+            if let Some(ref sanitizer) = sanitizer {
+                html = sanitizer.clean(&html).to_string();
+            }
+
+            if features.entity_encoding != ExEntityEncoding::Utf8 {
+                html = entities::encode(&html, &features.entity_encoding);
+            }
+
+            Ok((index, html))
+        })
+        .collect()
+}
@@ -0,0 +1,74 @@
+use crate::types::options::ExDataImagesOptions;
+use comrak::nodes::{AstNode, NodeValue};
+
+// Clears the source of images with data urls that aren't allowed, so they
+// render as broken images instead of embedding arbitrary content.
+pub fn filter_images<'a>(root: &'a AstNode<'a>, options: &ExDataImagesOptions) {
+    for node in root.descendants() {
+        let mut ast = node.data.borrow_mut();
+
+        if let NodeValue::Image(ref mut link) = ast.value {
+            if is_dropped(&link.url, options) {
+                link.url.clear();
+            }
+        }
+    }
+}
+
+pub fn is_dropped(url: &str, options: &ExDataImagesOptions) -> bool {
+    is_data_url(url) && !is_allowed(url, options)
+}
+
+// Data urls are only kept on image sources that pass the same checks as
+// `filter_images`, the sanitizer allows the `data` scheme everywhere else.
+pub fn keep_attribute(
+    element: &str,
+    attribute: &str,
+    value: &str,
+    options: &ExDataImagesOptions,
+) -> bool {
+    !is_data_url(value) || (element == "img" && attribute == "src" && is_allowed(value, options))
+}
+
+fn is_data_url(url: &str) -> bool {
+    url.trim_start()
+        .get(..5)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:"))
+}
+
+// `data:<mime>[;<param>]*;base64,<payload>` with an allowed mime type and a
+// decoded payload within the limit.
+fn is_allowed(url: &str, options: &ExDataImagesOptions) -> bool {
+    let url = url.trim_start();
+    let (header, payload) = match url[5..].split_once(',') {
+        Some(parts) => parts,
+        None => return false,
+    };
+
+    let mut params = header.split(';');
+    let mime = params.next().unwrap_or_default().to_ascii_lowercase();
+
+    if !params
+        .last()
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("base64"))
+    {
+        return false;
+    }
+
+    if !options
+        .mime_types
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(&mime))
+    {
+        return false;
+    }
+
+    let data = payload.trim_end_matches('=');
+    let padding = payload.len() - data.len();
+
+    padding <= 2
+        && data
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/'))
+        && data.len() * 3 / 4 <= options.max_bytes
+}
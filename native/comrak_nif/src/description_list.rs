@@ -0,0 +1,76 @@
+use crate::raw_html::{allow_injected_html, escape_html, html_block};
+use crate::types::options::ExDescriptionListOptions;
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{Arena, ComrakOptions};
+
+pub fn apply<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    root: &'a AstNode<'a>,
+    options: &ExDescriptionListOptions,
+    comrak_options: &mut ComrakOptions,
+) {
+    let nodes: Vec<_> = root
+        .descendants()
+        .filter(|node| {
+            matches!(
+                node.data.borrow().value,
+                NodeValue::DescriptionList
+                    | NodeValue::DescriptionTerm
+                    | NodeValue::DescriptionDetails
+            )
+        })
+        .collect();
+
+    if nodes.is_empty() {
+        return;
+    }
+
+    if options.tight {
+        for node in &nodes {
+            unwrap_single_paragraph(node);
+        }
+    }
+
+    if options.dl_class.is_none() && options.dt_class.is_none() && options.dd_class.is_none() {
+        return;
+    }
+
+    allow_injected_html(root, comrak_options);
+
+    for node in nodes {
+        let (tag, class) = match node.data.borrow().value {
+            NodeValue::DescriptionList => ("dl", &options.dl_class),
+            NodeValue::DescriptionTerm => ("dt", &options.dt_class),
+            _ => ("dd", &options.dd_class),
+        };
+
+        if let Some(class) = class {
+            // the node keeps its children but renders nothing by itself
+            node.data.borrow_mut().value = NodeValue::Document;
+            node.prepend(html_block(
+                arena,
+                format!("<{} class=\"{}\">", tag, escape_html(class)),
+            ));
+            node.append(html_block(arena, format!("</{}>", tag)));
+        }
+    }
+}
+
+// Renders the inlines of a term or details with a single paragraph without
+// the `<p>` wrapper, like tight list items.
+fn unwrap_single_paragraph<'a>(node: &'a AstNode<'a>) {
+    if let NodeValue::DescriptionList = node.data.borrow().value {
+        return;
+    }
+
+    let child = match node.first_child() {
+        Some(child) if child.next_sibling().is_none() => child,
+        _ => return,
+    };
+
+    let mut ast = child.data.borrow_mut();
+
+    if let NodeValue::Paragraph = ast.value {
+        ast.value = NodeValue::Document;
+    }
+}
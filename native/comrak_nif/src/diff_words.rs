@@ -0,0 +1,210 @@
+use crate::raw_html::escape_html;
+use std::ops::Range;
+
+// Lines with more tokens are compared as a whole, the LCS table grows with
+// the product of both lengths.
+const MAX_TOKENS: usize = 256;
+
+// Byte ranges of the words that changed between each removed line and the
+// added line at the same position in the following run of added lines. Like
+// git's diff-highlight, runs are only paired when they have the same number
+// of lines, otherwise it's unclear which lines were edited.
+pub fn changed_ranges(source: &str) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    for line in source.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        lines.push((offset, content));
+        offset += line.len();
+    }
+
+    let mut ranges = Vec::new();
+    let mut index = 0;
+
+    while index < lines.len() {
+        let removed = run(&lines[index..], '-');
+        let added = run(&lines[index + removed..], '+');
+
+        if removed == 0 || removed != added {
+            index += removed.max(1);
+            continue;
+        }
+
+        for pair in 0..removed {
+            let (old_offset, old) = lines[index + pair];
+            let (new_offset, new) = lines[index + removed + pair];
+
+            compare(
+                old_offset + 1,
+                &old[1..],
+                new_offset + 1,
+                &new[1..],
+                &mut ranges,
+            );
+        }
+
+        index += removed + added;
+    }
+
+    ranges.sort_by_key(|range| range.start);
+    ranges
+}
+
+// The source in the range escaped, with the parts in the changed ranges
+// wrapped in `<span class="diff-change">`.
+pub fn format_source(source: &str, range: Range<usize>, changed: &[Range<usize>]) -> String {
+    let mut output = String::new();
+    let mut position = range.start;
+
+    for change in changed {
+        if change.end <= position || change.start >= range.end {
+            continue;
+        }
+
+        let start = change.start.max(position);
+        let end = change.end.min(range.end);
+
+        output.push_str(&escape_html(&source[position..start]));
+        output.push_str("<span class=\"diff-change\">");
+        output.push_str(&escape_html(&source[start..end]));
+        output.push_str("</span>");
+        position = end;
+    }
+
+    output.push_str(&escape_html(&source[position..range.end]));
+    output
+}
+
+// Number of lines at the start with the marker, file headers like `---` and
+// `+++` excluded.
+fn run(lines: &[(usize, &str)], marker: char) -> usize {
+    let header: String = [marker; 3].iter().collect();
+
+    lines
+        .iter()
+        .take_while(|(_, line)| line.starts_with(marker) && !line.starts_with(&header))
+        .count()
+}
+
+fn compare(
+    old_offset: usize,
+    old: &str,
+    new_offset: usize,
+    new: &str,
+    ranges: &mut Vec<Range<usize>>,
+) {
+    let old_tokens = tokens(old);
+    let new_tokens = tokens(new);
+
+    if old_tokens.len() > MAX_TOKENS || new_tokens.len() > MAX_TOKENS {
+        return;
+    }
+
+    let (old_common, new_common) = common(&old_tokens, &new_tokens);
+
+    push_changed(old_offset, &old_tokens, &old_common, ranges);
+    push_changed(new_offset, &new_tokens, &new_common, ranges);
+}
+
+// Words, runs of whitespace and single punctuation characters, as byte
+// ranges of the line.
+fn tokens(line: &str) -> Vec<(Range<usize>, &str)> {
+    let mut tokens = Vec::new();
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+        let kind = token_kind(c);
+
+        if kind != TokenKind::Punctuation {
+            while let Some(&(next, next_c)) = chars.peek() {
+                if token_kind(next_c) != kind {
+                    break;
+                }
+
+                end = next + next_c.len_utf8();
+                chars.next();
+            }
+        }
+
+        tokens.push((start..end, &line[start..end]));
+    }
+
+    tokens
+}
+
+#[derive(PartialEq)]
+enum TokenKind {
+    Word,
+    Space,
+    Punctuation,
+}
+
+fn token_kind(c: char) -> TokenKind {
+    if c.is_alphanumeric() || c == '_' {
+        TokenKind::Word
+    } else if c.is_whitespace() {
+        TokenKind::Space
+    } else {
+        TokenKind::Punctuation
+    }
+}
+
+// Whether each token of both lines is part of their longest common
+// subsequence.
+fn common(old: &[(Range<usize>, &str)], new: &[(Range<usize>, &str)]) -> (Vec<bool>, Vec<bool>) {
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = match old[i].1 == new[j].1 {
+                true => lengths[i + 1][j + 1] + 1,
+                false => lengths[i + 1][j].max(lengths[i][j + 1]),
+            };
+        }
+    }
+
+    let mut old_common = vec![false; old.len()];
+    let mut new_common = vec![false; new.len()];
+    let (mut i, mut j) = (0, 0);
+
+    while i < old.len() && j < new.len() {
+        if old[i].1 == new[j].1 {
+            old_common[i] = true;
+            new_common[j] = true;
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    (old_common, new_common)
+}
+
+// Adjacent changed tokens are merged, whitespace alone is never marked.
+fn push_changed(
+    offset: usize,
+    tokens: &[(Range<usize>, &str)],
+    common: &[bool],
+    ranges: &mut Vec<Range<usize>>,
+) {
+    let mut current: Option<Range<usize>> = None;
+
+    for ((range, token), common) in tokens.iter().zip(common) {
+        let is_space = token.trim().is_empty();
+
+        match (*common, current.as_mut()) {
+            (false, Some(current)) => current.end = offset + range.end,
+            (false, None) if !is_space => current = Some(offset + range.start..offset + range.end),
+            (false, None) => (),
+            (true, _) if is_space => (),
+            (true, _) => ranges.extend(current.take()),
+        }
+    }
+
+    ranges.extend(current);
+}
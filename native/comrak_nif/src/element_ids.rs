@@ -0,0 +1,46 @@
+use crate::audit::parse_tag;
+use std::collections::HashSet;
+
+// Ids of the elements of the html in document order, each once, as written
+// in the attributes. Comments are skipped, everything else comes from the
+// same tag scanner as the audit.
+pub fn collect(html: &str) -> Vec<String> {
+    let mut ids = Vec::new();
+    let mut seen = HashSet::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |end| end + 3);
+            rest = &rest[end..];
+            continue;
+        }
+
+        let tag = match parse_tag(rest) {
+            Some(tag) => tag,
+            None => {
+                rest = &rest[1..];
+                continue;
+            }
+        };
+
+        let id = tag
+            .attributes
+            .iter()
+            .filter(|_| !tag.closing)
+            .find(|attribute| attribute.name.eq_ignore_ascii_case("id"))
+            .and_then(|attribute| attribute.value);
+
+        if let Some(id) = id {
+            if !id.is_empty() && seen.insert(id) {
+                ids.push(id.to_string());
+            }
+        }
+
+        rest = &rest[tag.len..];
+    }
+
+    ids
+}
@@ -0,0 +1,172 @@
+use crate::types::options::ExEntityEncoding;
+
+// HTML 4 Latin-1 entities, indexed by codepoint - 160.
+const LATIN1: [&str; 96] = [
+    "nbsp", "iexcl", "cent", "pound", "curren", "yen", "brvbar", "sect", "uml", "copy", "ordf",
+    "laquo", "not", "shy", "reg", "macr", "deg", "plusmn", "sup2", "sup3", "acute", "micro",
+    "para", "middot", "cedil", "sup1", "ordm", "raquo", "frac14", "frac12", "frac34", "iquest",
+    "Agrave", "Aacute", "Acirc", "Atilde", "Auml", "Aring", "AElig", "Ccedil", "Egrave", "Eacute",
+    "Ecirc", "Euml", "Igrave", "Iacute", "Icirc", "Iuml", "ETH", "Ntilde", "Ograve", "Oacute",
+    "Ocirc", "Otilde", "Ouml", "times", "Oslash", "Ugrave", "Uacute", "Ucirc", "Uuml", "Yacute",
+    "THORN", "szlig", "agrave", "aacute", "acirc", "atilde", "auml", "aring", "aelig", "ccedil",
+    "egrave", "eacute", "ecirc", "euml", "igrave", "iacute", "icirc", "iuml", "eth", "ntilde",
+    "ograve", "oacute", "ocirc", "otilde", "ouml", "divide", "oslash", "ugrave", "uacute", "ucirc",
+    "uuml", "yacute", "thorn", "yuml",
+];
+
+// Escapes produced by the formatter and the sanitizer.
+const ESCAPES: [(&str, char); 4] = [("quot", '"'), ("amp", '&'), ("lt", '<'), ("gt", '>')];
+
+// Re-encodes the rendered html so that it only contains ASCII, for systems
+// that can't handle UTF-8. Markup is left untouched since it's always ASCII.
+pub fn encode(html: &str, encoding: &ExEntityEncoding) -> String {
+    let mut encoded = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '&' {
+            if let Some((entity, len)) = named_entity_at(rest) {
+                match encoding {
+                    ExEntityEncoding::Numeric => encoded.push_str(&format!("&#{};", entity as u32)),
+                    _ => encoded.push_str(&rest[..len]),
+                }
+
+                rest = &rest[len..];
+                continue;
+            }
+        }
+
+        match encoding {
+            _ if c.is_ascii() => encoded.push(c),
+            ExEntityEncoding::Utf8 => encoded.push(c),
+            ExEntityEncoding::Named => match entity_name(c) {
+                Some(name) => {
+                    encoded.push('&');
+                    encoded.push_str(name);
+                    encoded.push(';');
+                }
+                None => encoded.push_str(&format!("&#{};", c as u32)),
+            },
+            ExEntityEncoding::Numeric => encoded.push_str(&format!("&#{};", c as u32)),
+        }
+
+        rest = &rest[c.len_utf8()..];
+    }
+
+    encoded
+}
+
+// Decodes numeric references and the named entities known here, anything
+// else is left as written.
+pub fn decode(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        match named_entity_at(rest).or_else(|| numeric_entity_at(rest)) {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+fn entity_name(c: char) -> Option<&'static str> {
+    match c as u32 {
+        160..=255 => Some(LATIN1[c as usize - 160]),
+        _ => match c {
+            'Œ' => Some("OElig"),
+            'œ' => Some("oelig"),
+            'Š' => Some("Scaron"),
+            'š' => Some("scaron"),
+            'Ÿ' => Some("Yuml"),
+            'ƒ' => Some("fnof"),
+            '–' => Some("ndash"),
+            '—' => Some("mdash"),
+            '‘' => Some("lsquo"),
+            '’' => Some("rsquo"),
+            '‚' => Some("sbquo"),
+            '“' => Some("ldquo"),
+            '”' => Some("rdquo"),
+            '„' => Some("bdquo"),
+            '†' => Some("dagger"),
+            '‡' => Some("Dagger"),
+            '•' => Some("bull"),
+            '…' => Some("hellip"),
+            '‰' => Some("permil"),
+            '′' => Some("prime"),
+            '″' => Some("Prime"),
+            '‹' => Some("lsaquo"),
+            '›' => Some("rsaquo"),
+            '€' => Some("euro"),
+            '™' => Some("trade"),
+            '←' => Some("larr"),
+            '↑' => Some("uarr"),
+            '→' => Some("rarr"),
+            '↓' => Some("darr"),
+            '↔' => Some("harr"),
+            '−' => Some("minus"),
+            '∞' => Some("infin"),
+            '≠' => Some("ne"),
+            '≤' => Some("le"),
+            '≥' => Some("ge"),
+            _ => None,
+        },
+    }
+}
+
+// Matches a known named entity at the start of `html`, returning the char it
+// stands for and the length of the entity.
+fn named_entity_at(html: &str) -> Option<(char, usize)> {
+    let end = html.bytes().skip(1).take(32).position(|b| b == b';')? + 1;
+    let name = html.get(1..end)?;
+
+    if name.is_empty() || !name.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        return None;
+    }
+
+    let c = ESCAPES
+        .iter()
+        .find(|(escape, _)| *escape == name)
+        .map(|(_, c)| *c)
+        .or_else(|| {
+            LATIN1
+                .iter()
+                .position(|entity| *entity == name)
+                .and_then(|index| char::from_u32(index as u32 + 160))
+        })?;
+
+    Some((c, end + 1))
+}
+
+// Matches a `&#...;` or `&#x...;` reference at the start of `html`, returning
+// the char and the length of the reference.
+fn numeric_entity_at(html: &str) -> Option<(char, usize)> {
+    let numeric = html.strip_prefix("&#")?;
+    let (digits, radix) = match numeric.strip_prefix(['x', 'X']) {
+        Some(hex) => (hex, 16),
+        None => (numeric, 10),
+    };
+    let len = digits.chars().take_while(|c| c.is_digit(radix)).count();
+
+    if len == 0 || !digits[len..].starts_with(';') {
+        return None;
+    }
+
+    let c = u32::from_str_radix(&digits[..len], radix)
+        .ok()
+        .and_then(char::from_u32)?;
+
+    Some((c, html.len() - digits.len() + len + 1))
+}
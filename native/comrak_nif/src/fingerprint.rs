@@ -0,0 +1,83 @@
+use crate::render::{self, RenderError};
+use crate::theme;
+use crate::types::options::ExOptions;
+use comrak::{ComrakOptions, ListStyleType};
+use serde_json::{json, Value};
+
+// FNV-1a, which unlike the std hasher gives the same hash across builds and
+// platforms.
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+// Stable hash of the configuration a render would use: the preset, the comrak
+// options and the features after defaults are applied, which also decide the
+// highlighter and sanitizer settings, and the MDEx `version`. The options are
+// hashed as JSON with the fields named, so the hash doesn't depend on the
+// `Debug` output of comrak or the compiler. The cache and metrics handles
+// don't change the output and are left out of the serialized features.
+pub fn fingerprint(options: ExOptions, version: &str) -> Result<String, RenderError> {
+    let (preset, comrak_options, features) = render::resolve_options(options)?;
+
+    if let Some(ref theme) = features.syntax_highlight_theme {
+        theme::resolve(theme)?;
+    }
+
+    let resolved = json!({
+        "version": version,
+        "preset": preset,
+        "comrak": comrak_json(&comrak_options),
+        "features": features,
+    });
+    let resolved = serde_json::to_vec(&resolved)
+        .map_err(|err| RenderError::Other(format!("fingerprint: {}", err)))?;
+
+    Ok(format!("{:016x}", fnv1a(&resolved)))
+}
+
+fn comrak_json(options: &ComrakOptions) -> Value {
+    let extension = &options.extension;
+    let parse = &options.parse;
+    let render = &options.render;
+    let list_style = match render.list_style {
+        ListStyleType::Dash => "dash",
+        ListStyleType::Plus => "plus",
+        ListStyleType::Star => "star",
+    };
+
+    json!({
+        "extension": {
+            "strikethrough": extension.strikethrough,
+            "tagfilter": extension.tagfilter,
+            "table": extension.table,
+            "autolink": extension.autolink,
+            "tasklist": extension.tasklist,
+            "superscript": extension.superscript,
+            "header_ids": extension.header_ids,
+            "footnotes": extension.footnotes,
+            "description_lists": extension.description_lists,
+            "front_matter_delimiter": extension.front_matter_delimiter,
+            "shortcodes": extension.shortcodes,
+        },
+        "parse": {
+            "smart": parse.smart,
+            "default_info_string": parse.default_info_string,
+            "relaxed_tasklist_matching": parse.relaxed_tasklist_matching,
+        },
+        "render": {
+            "hardbreaks": render.hardbreaks,
+            "github_pre_lang": render.github_pre_lang,
+            "full_info_string": render.full_info_string,
+            "width": render.width,
+            "unsafe_": render.unsafe_,
+            "escape": render.escape,
+            "list_style": list_style,
+            "sourcepos": render.sourcepos,
+        },
+    })
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
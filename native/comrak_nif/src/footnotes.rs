@@ -0,0 +1,48 @@
+use crate::raw_html::escape_html;
+
+// Markup comrak renders for footnote references, definitions and back
+// references, followed by the `fn` or `fnref` id.
+const FOOTNOTE_IDS: [&str; 4] = [
+    "<sup class=\"footnote-ref\"><a href=\"#",
+    "\" id=\"fnref",
+    "<li id=\"fn",
+    "<a href=\"#fnref",
+];
+
+// Prefixes the ids and links of footnotes, which comrak numbers from 1 in
+// every document, so documents rendered on the same page don't share them.
+pub fn prefix_ids(html: &str, prefix: &str) -> String {
+    let prefix = escape_html(prefix);
+    let mut prefixed = String::with_capacity(html.len());
+    let mut rest = html;
+
+    loop {
+        let next = FOOTNOTE_IDS
+            .iter()
+            .filter_map(|markup| rest.find(markup).map(|start| (start, markup)))
+            .min_by_key(|(start, _)| *start);
+
+        let (start, markup) = match next {
+            Some(next) => next,
+            None => break,
+        };
+
+        // the id starts after the markup, or after `#` in links
+        let id_start = match markup.strip_suffix("fnref").or(markup.strip_suffix("fn")) {
+            Some(before_id) => start + before_id.len(),
+            None => start + markup.len(),
+        };
+
+        prefixed.push_str(&rest[..id_start]);
+        prefixed.push_str(&prefix);
+        rest = &rest[id_start..];
+
+        // skip the id so it's not matched again
+        let id_len = rest.find('"').unwrap_or(rest.len());
+        prefixed.push_str(&rest[..id_len]);
+        rest = &rest[id_len..];
+    }
+
+    prefixed.push_str(rest);
+    prefixed
+}
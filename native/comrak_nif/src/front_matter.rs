@@ -0,0 +1,75 @@
+use crate::render::{self, Html, RenderError};
+use crate::types::options::ExOptions;
+use serde_json::{Map, Value};
+
+// Delimiters like Hugo's, the front matter format follows from the delimiter.
+const DELIMITERS: [(&str, Format); 3] = [
+    ("---", Format::Yaml),
+    ("+++", Format::Toml),
+    (";;;", Format::Json),
+];
+
+#[derive(Clone, Copy)]
+enum Format {
+    Yaml,
+    Toml,
+    Json,
+}
+
+// The decoded front matter, or `None` when the document has none, and the
+// html of the document. The document is rendered with comrak's front matter
+// extension set to the delimiter found, so it's left out of the html and
+// sourcepos still match the markdown.
+pub fn parse(md: &str, mut options: ExOptions) -> Result<(Option<Value>, Html), RenderError> {
+    let front_matter = match split(md) {
+        Some((delimiter, format, source)) => {
+            options.extension.front_matter_delimiter = Some(delimiter.to_string());
+            Some(decode(format, source)?)
+        }
+        None => None,
+    };
+
+    let html = render::render_html(md, options, None)?;
+
+    Ok((front_matter, html))
+}
+
+// `(delimiter, format, front matter)` when the first line is a delimiter
+// closed by the same delimiter on a later line. A byte order mark is skipped
+// like comrak does.
+fn split(md: &str) -> Option<(&'static str, Format, &str)> {
+    let (first, rest) = md.trim_start_matches('\u{feff}').split_once('\n')?;
+    let (delimiter, format) = DELIMITERS
+        .iter()
+        .find(|(delimiter, _)| first.trim_end_matches('\r') == *delimiter)?;
+    let mut end = 0;
+
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end_matches(['\n', '\r']) == *delimiter {
+            return Some((delimiter, *format, &rest[..end]));
+        }
+
+        end += line.len();
+    }
+
+    None
+}
+
+// Empty front matter decodes to an empty map, anything but a map is an error.
+fn decode(format: Format, source: &str) -> Result<Value, RenderError> {
+    let value = match format {
+        Format::Yaml => serde_yaml::from_str(source).map_err(|err| err.to_string()),
+        Format::Toml => toml::from_str(source).map_err(|err| err.to_string()),
+        Format::Json if source.trim().is_empty() => Ok(Value::Null),
+        Format::Json => serde_json::from_str(source).map_err(|err| err.to_string()),
+    }
+    .map_err(|err| RenderError::Other(format!("invalid front matter: {}", err)))?;
+
+    match value {
+        Value::Object(_) => Ok(value),
+        Value::Null => Ok(Value::Object(Map::new())),
+        _ => Err(RenderError::Other(
+            "invalid front matter: expected a map".to_string(),
+        )),
+    }
+}
@@ -0,0 +1,86 @@
+use inkjet::constants::HIGHLIGHT_NAMES;
+use libloading::{Library, Symbol};
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+use tree_sitter::Language;
+use tree_sitter_highlight::HighlightConfiguration;
+
+// Grammars loaded at runtime, looked up before the ones bundled with inkjet.
+// Libraries and configurations live until the VM exits since highlighters may
+// hold on to them while rendering.
+static GRAMMARS: OnceLock<RwLock<HashMap<String, &'static HighlightConfiguration>>> =
+    OnceLock::new();
+
+pub struct Grammar {
+    pub name: String,
+    pub path: String,
+    pub symbol: Option<String>,
+    pub highlights_query: String,
+    pub injections_query: String,
+    pub locals_query: String,
+}
+
+pub fn register(grammar: Grammar) -> Result<(), String> {
+    let symbol = grammar
+        .symbol
+        .unwrap_or_else(|| format!("tree_sitter_{}", grammar.name.replace('-', "_")));
+
+    let language = unsafe {
+        let library = Library::new(&grammar.path)
+            .map_err(|err| format!("failed to load {}: {}", grammar.path, err))?;
+        let language_fn: Symbol<unsafe extern "C" fn() -> Language> = library
+            .get(symbol.as_bytes())
+            .map_err(|err| format!("failed to find {} in {}: {}", symbol, grammar.path, err))?;
+        let language = language_fn();
+        std::mem::forget(library);
+        language
+    };
+
+    let mut config = HighlightConfiguration::new(
+        language,
+        &grammar.highlights_query,
+        &grammar.injections_query,
+        &grammar.locals_query,
+    )
+    .map_err(|err| format!("invalid query: {:?}", err))?;
+    config.configure(HIGHLIGHT_NAMES);
+
+    insert(grammar.name, config)
+}
+
+// Only used by the tests, registers a grammar whose language has an ABI
+// version no parser accepts, so highlighting its code blocks fails.
+pub fn register_incompatible(name: String) -> Result<(), String> {
+    static VERSION: u32 = 0;
+
+    let language = inkjet::Language::Diff.config().language;
+    let mut config = HighlightConfiguration::new(language, "", "", "")
+        .map_err(|err| format!("invalid query: {:?}", err))?;
+    // `Language` wraps a pointer to a `TSLanguage`, whose first field is its
+    // ABI version, the only field read before the parser rejects it
+    config.language = unsafe { std::mem::transmute::<*const u32, Language>(&VERSION) };
+
+    insert(name, config)
+}
+
+fn insert(name: String, config: HighlightConfiguration) -> Result<(), String> {
+    grammars()
+        .write()
+        .map_err(|err| err.to_string())?
+        .insert(name, Box::leak(Box::new(config)));
+
+    Ok(())
+}
+
+// Registered grammars first, then the languages bundled with inkjet.
+pub fn config(name: &str) -> Option<&'static HighlightConfiguration> {
+    get(name).or_else(|| inkjet::Language::from_token(name).map(|lang| lang.config()))
+}
+
+fn get(name: &str) -> Option<&'static HighlightConfiguration> {
+    GRAMMARS.get()?.read().ok()?.get(name).copied()
+}
+
+fn grammars() -> &'static RwLock<HashMap<String, &'static HighlightConfiguration>> {
+    GRAMMARS.get_or_init(|| RwLock::new(HashMap::new()))
+}
@@ -0,0 +1,42 @@
+use crate::raw_html::escape_html;
+use crate::render::RenderError;
+
+const CONTENT: &str = "{content}";
+
+// Renders headings with anchors from a template like
+// `<h{level} id="{id}"><a href="#{id}">{content}</a></h{level}>`, with the
+// `enter` and `exit` halves of comrak's `HeadingAdapter`, which comrak 0.18
+// doesn't have. The heading inlines are rendered in place of `{content}`.
+#[derive(Debug)]
+pub struct TemplateHeadingAdapter {
+    enter: String,
+    exit: String,
+}
+
+impl TemplateHeadingAdapter {
+    pub fn new(template: &str) -> Result<Self, RenderError> {
+        match template.split_once(CONTENT) {
+            Some((enter, exit)) if !exit.contains(CONTENT) => Ok(Self {
+                enter: enter.to_string(),
+                exit: exit.to_string(),
+            }),
+            _ => Err(RenderError::Other(format!(
+                "invalid heading_template: expected {} once in {:?}",
+                CONTENT, template
+            ))),
+        }
+    }
+
+    pub fn enter(&self, level: u8, id: &str) -> String {
+        fill(&self.enter, level, id)
+    }
+
+    pub fn exit(&self, level: u8, id: &str) -> String {
+        fill(&self.exit, level, id)
+    }
+}
+
+fn fill(part: &str, level: u8, id: &str) -> String {
+    part.replace("{level}", &level.to_string())
+        .replace("{id}", &escape_html(id))
+}
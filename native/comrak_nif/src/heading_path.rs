@@ -0,0 +1,44 @@
+use crate::headings::{self, ExHeading};
+use crate::render::RenderError;
+use crate::types::options::ExOptions;
+
+#[derive(Debug, NifUntaggedEnum)]
+pub enum ExHeadingTarget {
+    Position((usize, usize)),
+    Anchor(String),
+}
+
+// Enclosing headings of a `{line, column}` position or of the heading with
+// the given anchor, outermost first. Empty when the position comes before the
+// first heading or no heading has the anchor.
+pub fn heading_path_at(
+    md: &str,
+    target: &ExHeadingTarget,
+    options: ExOptions,
+) -> Result<Vec<ExHeading>, RenderError> {
+    let mut path: Vec<ExHeading> = Vec::new();
+
+    for heading in headings::parse_headings(md, options)? {
+        let reached = match target {
+            ExHeadingTarget::Position(position) => heading.sourcepos.0 > *position,
+            ExHeadingTarget::Anchor(_) => false,
+        };
+
+        if reached {
+            return Ok(path);
+        }
+
+        path.retain(|parent| parent.level < heading.level);
+        let found = matches!(target, ExHeadingTarget::Anchor(anchor) if *anchor == heading.id);
+        path.push(heading);
+
+        if found {
+            return Ok(path);
+        }
+    }
+
+    match target {
+        ExHeadingTarget::Position(_) => Ok(path),
+        ExHeadingTarget::Anchor(_) => Ok(Vec::new()),
+    }
+}
@@ -0,0 +1,470 @@
+// Block-level scan that only looks for ATX and setext headings, skipping
+// front matter, fenced and indented code and HTML blocks, without building
+// the comrak AST.
+//
+// Headings nested in block quotes or list items are not reported, including
+// the ones on indented or lazy continuation lines of an item, and the text is
+// the raw inline markdown of the heading.
+
+use crate::audit::parse_tag;
+
+#[derive(Debug, NifMap)]
+pub struct ExOutlineHeading {
+    pub level: u8,
+    pub text: String,
+    pub sourcepos: ((usize, usize), (usize, usize)),
+}
+
+#[derive(Debug)]
+pub struct Fence {
+    marker: u8,
+    length: usize,
+}
+
+// How an HTML block ends, from the CommonMark start conditions: on a line
+// containing one of the terminators, or before a blank line.
+#[derive(Debug)]
+enum HtmlBlock {
+    Until(&'static [&'static str]),
+    BlankLine,
+}
+
+const RAW_TAGS: [&str; 4] = ["pre", "script", "style", "textarea"];
+
+const BLOCK_TAGS: [&str; 62] = [
+    "address",
+    "article",
+    "aside",
+    "base",
+    "basefont",
+    "blockquote",
+    "body",
+    "caption",
+    "center",
+    "col",
+    "colgroup",
+    "dd",
+    "details",
+    "dialog",
+    "dir",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "frame",
+    "frameset",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hr",
+    "html",
+    "iframe",
+    "legend",
+    "li",
+    "link",
+    "main",
+    "menu",
+    "menuitem",
+    "nav",
+    "noframes",
+    "ol",
+    "optgroup",
+    "option",
+    "p",
+    "param",
+    "section",
+    "source",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "track",
+    "ul",
+];
+
+// The top-level block quote or list item the current line may belong to.
+#[derive(Debug)]
+enum Container {
+    BlockQuote,
+    ListItem { content_indent: usize },
+}
+
+#[derive(Debug)]
+struct Paragraph<'a> {
+    start_line: usize,
+    start_column: usize,
+    lines: Vec<&'a str>,
+}
+
+// The front matter is only skipped when `front_matter_delimiter` is set, like
+// comrak's front matter extension.
+pub fn outline(md: &str, front_matter_delimiter: Option<&str>) -> Vec<ExOutlineHeading> {
+    let mut headings = Vec::new();
+    let mut fence: Option<Fence> = None;
+    let mut html: Option<HtmlBlock> = None;
+    let mut paragraph: Option<Paragraph> = None;
+    let mut container: Option<Container> = None;
+    let mut previous_blank = true;
+    let front_matter = front_matter_delimiter
+        .and_then(|delimiter| front_matter_lines(md, delimiter))
+        .unwrap_or(0);
+
+    for (index, line) in md.lines().enumerate().skip(front_matter) {
+        let line_number = index + 1;
+        let (indent, rest) = split_indent(line);
+        let blank = rest.trim().is_empty();
+        let after_blank = std::mem::replace(&mut previous_blank, blank);
+
+        if let Some(ref open) = fence {
+            if indent < 4 && closes_fence(rest, open) {
+                fence = None;
+            }
+            continue;
+        }
+
+        if let Some(ref open) = html {
+            match open {
+                HtmlBlock::BlankLine if rest.trim().is_empty() => html = None,
+                HtmlBlock::Until(terminators) if contains_any(line, terminators) => html = None,
+                _ => (),
+            }
+            continue;
+        }
+
+        if let Some(ref open) = container {
+            let inside = match open {
+                Container::BlockQuote => indent < 4 && rest.starts_with('>'),
+                Container::ListItem { content_indent } => blank || indent >= *content_indent,
+            };
+
+            if inside || (!blank && !after_blank && is_lazy_continuation(rest)) {
+                continue;
+            }
+
+            container = None;
+        }
+
+        if blank {
+            paragraph = None;
+            continue;
+        }
+
+        if indent >= 4 {
+            // lazy paragraph continuation, otherwise an indented code block
+            if let Some(ref mut paragraph) = paragraph {
+                paragraph.lines.push(rest.trim());
+            }
+            continue;
+        }
+
+        if let Some(open) = open_fence(rest) {
+            fence = Some(open);
+            paragraph = None;
+            continue;
+        }
+
+        if let Some(block) = html_block_start(rest, paragraph.is_some()) {
+            html = match block {
+                HtmlBlock::Until(terminators) if contains_any(rest, terminators) => None,
+                block => Some(block),
+            };
+            paragraph = None;
+            continue;
+        }
+
+        if let Some((level, text)) = atx_heading(rest) {
+            headings.push(ExOutlineHeading {
+                level,
+                text,
+                sourcepos: (
+                    (line_number, line.len() - rest.len() + 1),
+                    (line_number, line.trim_end().len()),
+                ),
+            });
+            paragraph = None;
+            continue;
+        }
+
+        if let Some(level) = setext_underline(rest) {
+            if let Some(paragraph) = paragraph.take() {
+                headings.push(ExOutlineHeading {
+                    level,
+                    text: paragraph.lines.join("\n"),
+                    sourcepos: (
+                        (paragraph.start_line, paragraph.start_column),
+                        (line_number, line.trim_end().len()),
+                    ),
+                });
+                continue;
+            }
+        }
+
+        if is_thematic_break(rest) {
+            paragraph = None;
+            continue;
+        }
+
+        if let Some(open) = container_start(indent, rest) {
+            container = Some(open);
+            paragraph = None;
+            continue;
+        }
+
+        match paragraph {
+            Some(ref mut paragraph) => paragraph.lines.push(rest.trim()),
+            None => {
+                paragraph = Some(Paragraph {
+                    start_line: line_number,
+                    start_column: line.len() - rest.len() + 1,
+                    lines: vec![rest.trim()],
+                })
+            }
+        }
+    }
+
+    headings
+}
+
+// Number of lines taken by the front matter, including both delimiters, when
+// the first line is the delimiter and a later line closes it. A byte order
+// mark is skipped like comrak does.
+fn front_matter_lines(md: &str, delimiter: &str) -> Option<usize> {
+    let mut lines = md.trim_start_matches('\u{feff}').lines();
+
+    if lines.next()?.trim_end() != delimiter {
+        return None;
+    }
+
+    lines
+        .position(|line| line.trim_end() == delimiter)
+        .map(|closing| closing + 2)
+}
+
+// Start conditions 1 to 7 of CommonMark HTML blocks. Only condition 7, a
+// complete open or closing tag alone on the line, can't interrupt a paragraph.
+fn html_block_start(rest: &str, in_paragraph: bool) -> Option<HtmlBlock> {
+    if !rest.starts_with('<') {
+        return None;
+    }
+
+    let lower = rest.to_ascii_lowercase();
+    let name_end = |name: &str| {
+        name.bytes()
+            .take_while(|b| b.is_ascii_alphanumeric())
+            .count()
+    };
+
+    let name = &lower[1..];
+    let end = name_end(name);
+
+    if RAW_TAGS.contains(&&name[..end])
+        && matches!(name.as_bytes().get(end), None | Some(b' ' | b'\t' | b'>'))
+    {
+        return Some(HtmlBlock::Until(&[
+            "</pre>",
+            "</script>",
+            "</style>",
+            "</textarea>",
+        ]));
+    }
+
+    if lower.starts_with("<!--") {
+        return Some(HtmlBlock::Until(&["-->"]));
+    }
+
+    if lower.starts_with("<?") {
+        return Some(HtmlBlock::Until(&["?>"]));
+    }
+
+    if lower.starts_with("<![cdata[") {
+        return Some(HtmlBlock::Until(&["]]>"]));
+    }
+
+    if lower.starts_with("<!") && lower.as_bytes().get(2).is_some_and(u8::is_ascii_alphabetic) {
+        return Some(HtmlBlock::Until(&[">"]));
+    }
+
+    let name = name.strip_prefix('/').unwrap_or(name);
+    let end = name_end(name);
+
+    if BLOCK_TAGS.contains(&&name[..end])
+        && (matches!(name.as_bytes().get(end), None | Some(b' ' | b'\t' | b'>'))
+            || name[end..].starts_with("/>"))
+    {
+        return Some(HtmlBlock::BlankLine);
+    }
+
+    match parse_tag(rest) {
+        Some(tag)
+            if !in_paragraph
+                && rest[tag.len..].trim().is_empty()
+                && !RAW_TAGS
+                    .iter()
+                    .any(|raw| tag.name.eq_ignore_ascii_case(raw)) =>
+        {
+            Some(HtmlBlock::BlankLine)
+        }
+        _ => None,
+    }
+}
+
+// HTML block terminators are matched case-insensitively.
+fn contains_any(line: &str, terminators: &[&str]) -> bool {
+    let line = line.to_ascii_lowercase();
+    terminators
+        .iter()
+        .any(|terminator| line.contains(terminator))
+}
+
+// Returns the indentation width, expanding tabs to the next multiple of 4,
+// and the rest of the line.
+pub fn split_indent(line: &str) -> (usize, &str) {
+    let mut width = 0;
+
+    for (offset, byte) in line.bytes().enumerate() {
+        match byte {
+            b' ' => width += 1,
+            b'\t' => width += 4 - width % 4,
+            _ => return (width, &line[offset..]),
+        }
+    }
+
+    (width, "")
+}
+
+pub fn open_fence(rest: &str) -> Option<Fence> {
+    let marker = *rest.as_bytes().first()?;
+
+    if marker != b'`' && marker != b'~' {
+        return None;
+    }
+
+    let length = rest.bytes().take_while(|&b| b == marker).count();
+
+    if length < 3 || (marker == b'`' && rest[length..].contains('`')) {
+        return None;
+    }
+
+    Some(Fence { marker, length })
+}
+
+pub fn closes_fence(rest: &str, open: &Fence) -> bool {
+    let length = rest.bytes().take_while(|&b| b == open.marker).count();
+    length >= open.length && rest[length..].trim().is_empty()
+}
+
+fn atx_heading(rest: &str) -> Option<(u8, String)> {
+    let hashes = rest.bytes().take_while(|&b| b == b'#').count();
+
+    if hashes == 0 || hashes > 6 {
+        return None;
+    }
+
+    let after = &rest[hashes..];
+
+    if !(after.is_empty() || after.starts_with(' ') || after.starts_with('\t')) {
+        return None;
+    }
+
+    let content = after.trim();
+    let without_closing = content.trim_end_matches('#');
+
+    let content = if without_closing.is_empty() {
+        ""
+    } else if without_closing.ends_with(' ') || without_closing.ends_with('\t') {
+        without_closing.trim_end()
+    } else {
+        content
+    };
+
+    Some((hashes as u8, content.to_string()))
+}
+
+fn setext_underline(rest: &str) -> Option<u8> {
+    let underline = rest.trim_end();
+
+    if underline.bytes().all(|b| b == b'=') {
+        Some(1)
+    } else if underline.bytes().all(|b| b == b'-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+fn is_thematic_break(rest: &str) -> bool {
+    let mut marker = None;
+    let mut count = 0;
+
+    for byte in rest.bytes() {
+        match byte {
+            b' ' | b'\t' => continue,
+            b'*' | b'-' | b'_' if marker.is_none() || marker == Some(byte) => {
+                marker = Some(byte);
+                count += 1;
+            }
+            _ => return false,
+        }
+    }
+
+    count >= 3
+}
+
+// A list item's content starts after the marker and up to 4 spaces, or one
+// space when the item starts blank or with an indented code block.
+fn container_start(indent: usize, rest: &str) -> Option<Container> {
+    let bytes = rest.as_bytes();
+
+    let marker_end = match bytes.first() {
+        Some(b'>') => return Some(Container::BlockQuote),
+        Some(b'-' | b'*' | b'+') => 1,
+        Some(b'0'..=b'9') => {
+            let digits = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+
+            match bytes.get(digits) {
+                Some(b'.' | b')') if digits <= 9 => digits + 1,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    if !matches!(bytes.get(marker_end), None | Some(b' ' | b'\t')) {
+        return None;
+    }
+
+    let (spaces, content) = split_indent(&rest[marker_end..]);
+    let spaces = if content.is_empty() || spaces > 4 {
+        1
+    } else {
+        spaces
+    };
+
+    Some(Container::ListItem {
+        content_indent: indent + marker_end + spaces,
+    })
+}
+
+// Lines after a non-blank line of a container continue its paragraph unless
+// they start a block of their own.
+fn is_lazy_continuation(rest: &str) -> bool {
+    atx_heading(rest).is_none()
+        && open_fence(rest).is_none()
+        && html_block_start(rest, true).is_none()
+        && !is_thematic_break(rest)
+        && container_start(0, rest).is_none()
+}
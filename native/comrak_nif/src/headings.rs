@@ -0,0 +1,554 @@
+use crate::heading_adapter::TemplateHeadingAdapter;
+use crate::raw_html::{allow_injected_html, escape_html, html_inline, new_node};
+use crate::render::{self, RenderError};
+use crate::types::options::{
+    ExHeadingCollisions, ExHeadingIdSource, ExHeadingIdsOptions, ExHeadingsAs,
+    ExNumberHeadingsOptions, ExOptions,
+};
+use comrak::nodes::{AstNode, NodeCode, NodeValue};
+use comrak::{Anchorizer, Arena, ComrakOptions};
+use std::collections::{HashMap, HashSet};
+
+// Renders headings with the same markup as comrak's `header_ids` extension,
+// but generating the ids here so the prefix, suffix and collisions can be
+// controlled per render. Replaces the extension when both are set, keeping
+// its prefix in front of the ids. `ids` holds the ids already taken, which
+// is shared between documents rendered together. Returns the headings with
+// their ids. The template, if any, replaces the markup of the headings.
+pub fn apply<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    root: &'a AstNode<'a>,
+    options: &ExHeadingIdsOptions,
+    headings_as: &ExHeadingsAs,
+    template: Option<&TemplateHeadingAdapter>,
+    ids: &mut HashSet<String>,
+    comrak_options: &mut ComrakOptions,
+) -> Result<Vec<HeadingId<'a>>, RenderError> {
+    let extension_prefix = comrak_options.extension.header_ids.take();
+    let headings = heading_ids(root, options, extension_prefix.as_deref(), ids)?;
+
+    if headings.is_empty() {
+        return Ok(headings);
+    }
+
+    allow_injected_html(root, comrak_options);
+
+    for heading in &headings {
+        match template {
+            Some(template) => {
+                template_heading(arena, heading.node, heading.level, &heading.id, template)
+            }
+            None => wrap_heading(
+                arena,
+                heading.node,
+                heading.level,
+                &heading.id,
+                headings_as,
+                comrak_options.render.sourcepos,
+            ),
+        }
+    }
+
+    Ok(headings)
+}
+
+pub struct HeadingId<'a> {
+    pub node: &'a AstNode<'a>,
+    pub level: u8,
+    pub text: String,
+    pub id: String,
+}
+
+// Ids of the headings in document order, added to `ids` as they're taken.
+pub fn heading_ids<'a>(
+    root: &'a AstNode<'a>,
+    options: &ExHeadingIdsOptions,
+    extension_prefix: Option<&str>,
+    ids: &mut HashSet<String>,
+) -> Result<Vec<HeadingId<'a>>, RenderError> {
+    let mut headings = Vec::new();
+    // slugs of the enclosing headings, used to tell apart repeated headings
+    let mut path: Vec<(u8, String)> = Vec::new();
+    let pinned_ids = match options.source {
+        ExHeadingIdSource::FrontMatterMap => front_matter_ids(root),
+        _ => HashMap::new(),
+    };
+
+    for node in root.descendants() {
+        let level = match node.data.borrow().value {
+            NodeValue::Heading(ref heading) => heading.level,
+            _ => continue,
+        };
+
+        // skipped headings get no id and aren't listed, like in the toc
+        let no_anchor = take_no_anchor(node);
+        let explicit_id = match options.source {
+            ExHeadingIdSource::Explicit if !no_anchor => take_attribute_id(node),
+            _ => None,
+        };
+
+        let text = collect_text(node);
+
+        if no_anchor
+            || options
+                .exclude
+                .iter()
+                .any(|excluded| excluded == text.trim())
+        {
+            continue;
+        }
+
+        let slug = explicit_id
+            .or_else(|| pinned_ids.get(text.trim()).cloned())
+            .unwrap_or_else(|| anchorize(&text));
+        path.retain(|(parent_level, _)| *parent_level < level);
+
+        let base = format!(
+            "{}{}{}{}",
+            extension_prefix.unwrap_or_default(),
+            options.prefix,
+            slug,
+            options.suffix
+        );
+
+        let id = if !ids.contains(&base) {
+            base
+        } else {
+            match options.collisions {
+                ExHeadingCollisions::Number => unique_id(ids, &base),
+                ExHeadingCollisions::Hash => {
+                    let hashed = format!("{}-{:06x}", base, path_hash(&path, &slug));
+
+                    match ids.contains(&hashed) {
+                        true => unique_id(ids, &hashed),
+                        false => hashed,
+                    }
+                }
+                ExHeadingCollisions::Error => return Err(RenderError::DuplicateHeadingId(base)),
+            }
+        };
+
+        path.push((level, slug));
+        ids.insert(id.clone());
+        headings.push(HeadingId {
+            node,
+            level,
+            text,
+            id,
+        });
+    }
+
+    Ok(headings)
+}
+
+#[derive(Debug, NifMap)]
+pub struct ExHeading {
+    pub level: u8,
+    pub text: String,
+    pub id: String,
+    pub sourcepos: ((usize, usize), (usize, usize)),
+}
+
+// Headings of the markdown with the ids they get when rendered with the same
+// options, without rendering it. Options are resolved and the document goes
+// through the same passes as `to_html` up to the heading ids.
+pub fn parse_headings(md: &str, options: ExOptions) -> Result<Vec<ExHeading>, RenderError> {
+    let (preset, mut comrak_options, features) = render::resolve_options(options)?;
+    render::check_input(md, &features)?;
+
+    let arena = Arena::new();
+    let (root, abbreviations) =
+        render::parse(&arena, md, preset.as_ref(), &features, &comrak_options);
+    render::transform(&arena, root, &features, &abbreviations, &mut comrak_options)?;
+
+    let heading_ids_options = features.heading_ids.unwrap_or_default();
+    let extension_prefix = comrak_options.extension.header_ids.take();
+    let headings = heading_ids(
+        root,
+        &heading_ids_options,
+        extension_prefix.as_deref(),
+        &mut HashSet::new(),
+    )?;
+
+    Ok(headings
+        .into_iter()
+        .map(|heading| {
+            let ast = heading.node.data.borrow();
+            let sourcepos = (
+                (ast.sourcepos.start.line, ast.sourcepos.start.column),
+                (ast.sourcepos.end.line, ast.sourcepos.end.column),
+            );
+
+            ExHeading {
+                level: heading.level,
+                text: heading.text,
+                id: heading.id,
+                sourcepos,
+            }
+        })
+        .collect())
+}
+
+// Prefixes headings in the levels range with their hierarchical number.
+// Headings above the range restart the numbering. Numbers are added as text
+// when they should be part of the ids, otherwise as html which is ignored
+// when collecting the heading text.
+pub fn number<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    root: &'a AstNode<'a>,
+    options: &ExNumberHeadingsOptions,
+    comrak_options: &mut ComrakOptions,
+) {
+    let format = NumberFormat::parse(&options.format);
+    let first = options.levels.first.max(1);
+    let last = options.levels.last.min(6);
+    let mut counters = [0usize; 7];
+
+    if !options.anchors {
+        allow_injected_html(root, comrak_options);
+    }
+
+    for node in root.descendants() {
+        let level = match node.data.borrow().value {
+            NodeValue::Heading(ref heading) => heading.level,
+            _ => continue,
+        };
+
+        if level < first {
+            counters = [0; 7];
+            continue;
+        }
+
+        if level > last {
+            continue;
+        }
+
+        let level = level as usize;
+        counters[level] += 1;
+        counters[level + 1..]
+            .iter_mut()
+            .for_each(|counter| *counter = 0);
+
+        // levels skipped at the top, like a document without h1, are omitted
+        let numbers: Vec<_> = counters[first as usize..=level]
+            .iter()
+            .skip_while(|counter| **counter == 0)
+            .map(|counter| counter.to_string())
+            .collect();
+        let number = format!(
+            "{}{}{}",
+            format.prefix,
+            numbers.join(format.separator),
+            format.suffix
+        );
+
+        let value = match options.anchors {
+            true => NodeValue::Text(number),
+            false => NodeValue::HtmlInline(escape_html(&number)),
+        };
+        node.prepend(new_node(arena, value));
+    }
+}
+
+// "1.2.3 " means numbers separated by `.` and followed by a space, with the
+// separator defaulting to `.` when the format has a single number. Text
+// before the first number is kept as a prefix.
+struct NumberFormat<'a> {
+    prefix: &'a str,
+    separator: &'a str,
+    suffix: &'a str,
+}
+
+impl<'a> NumberFormat<'a> {
+    fn parse(format: &'a str) -> Self {
+        let is_digit = |c: char| c.is_ascii_digit();
+
+        let (first, last) = match (format.find(is_digit), format.rfind(is_digit)) {
+            (Some(first), Some(last)) => (first, last),
+            _ => {
+                return NumberFormat {
+                    prefix: "",
+                    separator: ".",
+                    suffix: format,
+                }
+            }
+        };
+
+        let after_first = format[first..=last].trim_start_matches(is_digit);
+        let separator = match after_first.find(is_digit) {
+            Some(end) => &after_first[..end],
+            None => ".",
+        };
+
+        NumberFormat {
+            prefix: &format[..first],
+            separator,
+            suffix: &format[last + 1..],
+        }
+    }
+}
+
+// Renders the headings without ids as `<div role="heading">`, headings
+// with ids are rendered as divs by `apply`.
+pub fn as_divs<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    root: &'a AstNode<'a>,
+    comrak_options: &mut ComrakOptions,
+) {
+    let headings: Vec<_> = root
+        .descendants()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::Heading(_)))
+        .collect();
+
+    if headings.is_empty() {
+        return;
+    }
+
+    allow_injected_html(root, comrak_options);
+
+    for node in headings {
+        let level = match node.data.borrow().value {
+            NodeValue::Heading(ref heading) => heading.level,
+            _ => continue,
+        };
+
+        wrap_heading(
+            arena,
+            node,
+            level,
+            "",
+            &ExHeadingsAs::Div,
+            comrak_options.render.sourcepos,
+        );
+    }
+}
+
+// `role` and `aria-level` are only kept on the divs of `as_divs`.
+pub fn keep_div_attribute(element: &str, attribute: &str, value: &str) -> bool {
+    match (element, attribute) {
+        ("div", "role") => value == "heading",
+        ("div", "aria-level") => matches!(value, "1" | "2" | "3" | "4" | "5" | "6"),
+        _ => true,
+    }
+}
+
+// Renders the heading with an anchor when it has an `id`.
+fn wrap_heading<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    node: &'a AstNode<'a>,
+    level: u8,
+    id: &str,
+    headings_as: &ExHeadingsAs,
+    sourcepos: bool,
+) {
+    let mut ast = node.data.borrow_mut();
+    let sourcepos = match sourcepos {
+        true => format!(" data-sourcepos=\"{}\"", ast.sourcepos),
+        false => String::new(),
+    };
+    let (open, close) = match headings_as {
+        ExHeadingsAs::Heading => (
+            format!("<h{}{}>", level, sourcepos),
+            format!("</h{}>", level),
+        ),
+        ExHeadingsAs::Div => (
+            format!(
+                "<div role=\"heading\" aria-level=\"{}\"{}>",
+                level, sourcepos
+            ),
+            "</div>".to_string(),
+        ),
+    };
+    let anchor = match id {
+        "" => String::new(),
+        id => {
+            let id = escape_html(id);
+            format!(
+                "<a href=\"#{}\" aria-hidden=\"true\" class=\"anchor\" id=\"{}\"></a>",
+                id, id
+            )
+        }
+    };
+
+    // the node keeps its inlines but renders nothing by itself
+    ast.value = NodeValue::Document;
+    node.prepend(html_inline(arena, format!("{}{}", open, anchor)));
+    node.append(html_inline(arena, format!("{}\n", close)));
+}
+
+fn template_heading<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    node: &'a AstNode<'a>,
+    level: u8,
+    id: &str,
+    template: &TemplateHeadingAdapter,
+) {
+    node.data.borrow_mut().value = NodeValue::Document;
+    node.prepend(html_inline(arena, template.enter(level, id)));
+    node.append(html_inline(
+        arena,
+        format!("{}\n", template.exit(level, id)),
+    ));
+}
+
+// Removes a trailing `{#id}` attribute block from the heading text and
+// returns the id.
+fn take_attribute_id<'a>(node: &'a AstNode<'a>) -> Option<String> {
+    let block = take_attribute_block(node, |block| {
+        block.strip_prefix('#').is_some_and(|id| {
+            !id.is_empty()
+                && id
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ':' | '.'))
+        })
+    })?;
+
+    Some(block[1..].to_string())
+}
+
+// Removes a trailing `{-}` or `{.no-anchor}` from the heading text and
+// returns whether there was one.
+fn take_no_anchor<'a>(node: &'a AstNode<'a>) -> bool {
+    take_attribute_block(node, |block| matches!(block, "-" | ".no-anchor")).is_some()
+}
+
+// Removes a trailing `{...}` attribute block accepted by `accept` from the
+// heading text and returns its content. Trailing text nodes are merged first
+// since the block may be split in several nodes, for example around `_`.
+fn take_attribute_block<'a>(
+    node: &'a AstNode<'a>,
+    accept: impl Fn(&str) -> bool,
+) -> Option<String> {
+    let mut texts = Vec::new();
+    let mut child = node.last_child();
+
+    while let Some(text) =
+        child.filter(|child| matches!(child.data.borrow().value, NodeValue::Text(_)))
+    {
+        texts.insert(0, text);
+        child = text.previous_sibling();
+    }
+
+    let first = *texts.first()?;
+    let mut literal = String::new();
+
+    for text in &texts {
+        if let NodeValue::Text(ref part) = text.data.borrow().value {
+            literal.push_str(part);
+        }
+    }
+
+    let trimmed = literal.trim_end();
+    let start = trimmed.rfind('{')?;
+    let block = trimmed[start + 1..].strip_suffix('}')?;
+
+    if !accept(block) {
+        return None;
+    }
+
+    let block = block.to_string();
+    literal.truncate(literal[..start].trim_end().len());
+    first.data.borrow_mut().value = NodeValue::Text(literal);
+    texts[1..].iter().for_each(|text| text.detach());
+
+    Some(block)
+}
+
+// Ids pinned in the front matter under a `heading_ids` map of heading text
+// to id, one `text: id` pair per indented line, optionally quoted.
+fn front_matter_ids<'a>(root: &'a AstNode<'a>) -> HashMap<String, String> {
+    let mut ids = HashMap::new();
+
+    let front_matter = match root.first_child() {
+        Some(node) => match node.data.borrow().value {
+            NodeValue::FrontMatter(ref front_matter) => front_matter.clone(),
+            _ => return ids,
+        },
+        None => return ids,
+    };
+
+    let mut in_map = false;
+
+    for line in front_matter.lines() {
+        if !line.starts_with([' ', '\t']) {
+            in_map = line.trim_end() == "heading_ids:";
+            continue;
+        }
+
+        let line = line.trim();
+
+        if !in_map || line.starts_with('#') {
+            continue;
+        }
+
+        let (text, id) = match line.strip_prefix(['"', '\'']) {
+            Some(quoted) => {
+                let quote = line.as_bytes()[0] as char;
+                match quoted.split_once(quote) {
+                    Some((text, rest)) => (text, rest.trim_start().strip_prefix(':')),
+                    None => continue,
+                }
+            }
+            None => match line.split_once(':') {
+                Some((text, id)) => (text.trim_end(), Some(id)),
+                None => continue,
+            },
+        };
+
+        if let Some(id) = id {
+            let id = id.trim().trim_matches(['"', '\'']);
+
+            if !id.is_empty() {
+                ids.insert(text.to_string(), id.to_string());
+            }
+        }
+    }
+
+    ids
+}
+
+fn unique_id(ids: &HashSet<String>, id: &str) -> String {
+    (1..)
+        .map(|uniq| format!("{}-{}", id, uniq))
+        .find(|candidate| !ids.contains(candidate))
+        .unwrap()
+}
+
+// Same text comrak uses for the ids: text and code spans, with line breaks
+// as spaces.
+pub fn collect_text<'a>(node: &'a AstNode<'a>) -> String {
+    let mut text = String::new();
+
+    for descendant in node.descendants() {
+        match descendant.data.borrow().value {
+            NodeValue::Text(ref literal) | NodeValue::Code(NodeCode { ref literal, .. }) => {
+                text.push_str(literal)
+            }
+            NodeValue::LineBreak | NodeValue::SoftBreak => text.push(' '),
+            _ => (),
+        }
+    }
+
+    text
+}
+
+// The id comrak's `header_ids` would generate, without the suffix it adds
+// to repeated ids since collisions are resolved by the caller.
+pub fn anchorize(text: &str) -> String {
+    Anchorizer::new().anchorize(text.to_string())
+}
+
+// FNV-1a over the heading path, stable across runs and Rust versions so
+// anchors don't change between deploys.
+fn path_hash(path: &[(u8, String)], slug: &str) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+
+    for part in path.iter().map(|(_, slug)| slug.as_str()).chain([slug]) {
+        for byte in part.bytes().chain([b'/']) {
+            hash ^= byte as u32;
+            hash = hash.wrapping_mul(0x01000193);
+        }
+    }
+
+    hash & 0xffffff
+}
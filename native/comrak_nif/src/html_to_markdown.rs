@@ -0,0 +1,743 @@
+use crate::audit::parse_tag;
+use crate::entities;
+use crate::output::OutputBuffer;
+use crate::raw_html::new_node;
+use crate::render::{self, RenderError};
+use crate::types::options::{ExInputLimitsOptions, ExOptions};
+use comrak::nodes::{
+    AstNode, ListDelimType, ListType, NodeCode, NodeCodeBlock, NodeHeading, NodeLink, NodeList,
+    NodeValue, TableAlignment,
+};
+use comrak::{format_commonmark, Arena};
+
+// Elements without content or closing tag.
+const VOID: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+// Elements whose content is never converted, skipped up to their closing tag
+// since scripts and styles can contain anything.
+const SKIPPED: [&str; 9] = [
+    "head", "script", "style", "template", "title", "noscript", "iframe", "svg", "textarea",
+];
+
+// Elements that start a new block, the others are converted to inlines.
+const BLOCKS: [&str; 41] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "caption",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "html",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "ul",
+];
+
+// Converts html into a comrak document and renders it as CommonMark, for
+// importing content from CMSs and rich text editors. Headings, paragraphs,
+// lists, block quotes, code blocks, tables, links, images and emphasis are
+// converted, other elements are replaced by their content. Like the audit,
+// it's a tag scanner that accepts any html, not a conforming parser.
+//
+// The dom is built and converted recursively, so elements nested deeper than
+// `max_nesting_depth`, capped to the default, are replaced by their content
+// to keep deeply nested html from overflowing the scheduler stack.
+pub fn convert(html: &str, options: ExOptions) -> Result<String, RenderError> {
+    let (_, comrak_options, features) = render::resolve_options(options)?;
+    let deadline = render::deadline(&features);
+
+    if features.max_input_bytes.is_some_and(|max| html.len() > max) {
+        return Err(RenderError::InputTooLarge);
+    }
+
+    let max_depth = ExInputLimitsOptions::default().max_nesting_depth;
+    let max_depth = features
+        .input_limits
+        .as_ref()
+        .map_or(max_depth, |limits| limits.max_nesting_depth.min(max_depth));
+
+    let arena = Arena::new();
+    let root = new_node(&arena, NodeValue::Document);
+    let mut converter = Converter::new(&arena);
+
+    converter.blocks(root, &parse(html, max_depth).children);
+
+    let mut output = OutputBuffer::new(features.max_output_bytes, None, deadline);
+    format_commonmark(root, &comrak_options, &mut output)
+        .map_err(|err| render::output_error(&output, err))?;
+
+    String::from_utf8(output.as_bytes().to_vec()).map_err(|err| RenderError::Other(err.to_string()))
+}
+
+enum Dom {
+    Element(Element),
+    Text(String),
+}
+
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Dom>,
+}
+
+impl Element {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_ascii_lowercase(),
+            attributes: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn child_elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|child| match child {
+            Dom::Element(element) => Some(element),
+            Dom::Text(_) => None,
+        })
+    }
+
+    // Text of the element and its descendants, as written.
+    fn text(&self) -> String {
+        let mut text = String::new();
+
+        for child in &self.children {
+            match child {
+                Dom::Text(literal) => text.push_str(literal),
+                Dom::Element(element) if element.name == "br" => text.push('\n'),
+                Dom::Element(element) => text.push_str(&element.text()),
+            }
+        }
+
+        text
+    }
+}
+
+fn parse(html: &str, max_depth: usize) -> Element {
+    let mut stack = vec![Element::new("#root")];
+    // elements past `max_depth`, replaced by their content
+    let mut flattened: Vec<String> = Vec::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        push_text(&mut stack, &rest[..start]);
+        rest = &rest[start..];
+
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |end| end + 3);
+            rest = &rest[end..];
+            continue;
+        }
+
+        // doctypes and processing instructions
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            let end = rest.find('>').map_or(rest.len(), |end| end + 1);
+            rest = &rest[end..];
+            continue;
+        }
+
+        let tag = match parse_tag(rest) {
+            Some(tag) => tag,
+            None => {
+                push_text(&mut stack, "<");
+                rest = &rest[1..];
+                continue;
+            }
+        };
+
+        let name = tag.name.to_ascii_lowercase();
+        let len = tag.len;
+
+        if tag.closing {
+            if let Some(index) = flattened.iter().rposition(|open| *open == name) {
+                flattened.remove(index);
+            } else if let Some(index) = stack.iter().rposition(|element| element.name == name) {
+                close_to(&mut stack, index.max(1));
+            }
+
+            rest = &rest[len..];
+            continue;
+        }
+
+        if SKIPPED.contains(&name.as_str()) {
+            rest = &rest[skipped_end(rest, &name, len)..];
+            continue;
+        }
+
+        close_implied(&mut stack, &name);
+
+        let mut element = Element::new(&name);
+        element.attributes = tag
+            .attributes
+            .iter()
+            .map(|attribute| {
+                (
+                    attribute.name.to_ascii_lowercase(),
+                    entities::decode(attribute.value.unwrap_or_default()),
+                )
+            })
+            .collect();
+
+        if VOID.contains(&name.as_str()) || tag.self_closing {
+            push_child(&mut stack, Dom::Element(element));
+        } else if stack.len() > max_depth {
+            flattened.push(name);
+        } else {
+            stack.push(element);
+        }
+
+        rest = &rest[len..];
+    }
+
+    push_text(&mut stack, rest);
+    close_to(&mut stack, 1);
+    stack.pop().unwrap_or_else(|| Element::new("#root"))
+}
+
+fn push_text(stack: &mut [Element], text: &str) {
+    if !text.is_empty() {
+        push_child(stack, Dom::Text(entities::decode(text)));
+    }
+}
+
+fn push_child(stack: &mut [Element], child: Dom) {
+    if let Some(parent) = stack.last_mut() {
+        parent.children.push(child);
+    }
+}
+
+// Closes the elements from the top of the stack down to `index`, the root is
+// never closed.
+fn close_to(stack: &mut Vec<Element>, index: usize) {
+    while stack.len() > index {
+        if let Some(element) = stack.pop() {
+            push_child(stack, Dom::Element(element));
+        }
+    }
+}
+
+// Closes the elements that the opening tag ends without a closing tag, like
+// a list item opening the next one.
+fn close_implied(stack: &mut Vec<Element>, name: &str) {
+    let (closed, scopes): (&[&str], &[&str]) = match name {
+        "li" => (&["li"], &["ul", "ol"]),
+        "dt" | "dd" => (&["dt", "dd"], &["dl"]),
+        "tr" => (&["tr", "td", "th"], &["table", "thead", "tbody", "tfoot"]),
+        "td" | "th" => (&["td", "th"], &["tr", "table"]),
+        "thead" | "tbody" | "tfoot" => (&["thead", "tbody", "tfoot", "tr", "td", "th"], &["table"]),
+        _ if BLOCKS.contains(&name) => (&["p"], &[]),
+        _ => return,
+    };
+
+    // the outermost, a new row also closes the open cell and its row
+    let mut close = None;
+
+    for index in (1..stack.len()).rev() {
+        let open = stack[index].name.as_str();
+
+        if closed.contains(&open) {
+            close = Some(index);
+        } else if scopes.contains(&open) || BLOCKS.contains(&open) && open != "p" {
+            break;
+        }
+    }
+
+    if let Some(index) = close {
+        close_to(stack, index);
+    }
+}
+
+// End of the skipped element starting at `html`, including its closing tag,
+// or the end of the html if it's never closed.
+fn skipped_end(html: &str, name: &str, open_tag_len: usize) -> usize {
+    let mut offset = open_tag_len;
+
+    while let Some(start) = html[offset..].find("</") {
+        let start = offset + start;
+
+        if let Some(tag) = parse_tag(&html[start..]) {
+            if tag.name.eq_ignore_ascii_case(name) {
+                return start + tag.len;
+            }
+        }
+
+        offset = start + 2;
+    }
+
+    html.len()
+}
+
+// Builds the comrak nodes. Whitespace is collapsed like browsers do, a
+// pending space is only written before the next word of the same block and
+// outside of emphasis, since `** a**` isn't emphasis in markdown.
+struct Converter<'a> {
+    arena: &'a Arena<AstNode<'a>>,
+    pending_space: bool,
+    started: bool,
+    in_table: bool,
+}
+
+impl<'a> Converter<'a> {
+    fn new(arena: &'a Arena<AstNode<'a>>) -> Self {
+        Self {
+            arena,
+            pending_space: false,
+            started: false,
+            in_table: false,
+        }
+    }
+
+    fn append(&self, parent: &'a AstNode<'a>, value: NodeValue) -> &'a AstNode<'a> {
+        let node = new_node(self.arena, value);
+        parent.append(node);
+        node
+    }
+
+    fn blocks(&mut self, parent: &'a AstNode<'a>, children: &[Dom]) {
+        let mut paragraph = None;
+
+        for child in children {
+            match child {
+                Dom::Element(element) if BLOCKS.contains(&element.name.as_str()) => {
+                    if let Some(paragraph) = paragraph.take() {
+                        self.finish_inlines(paragraph);
+                    }
+
+                    self.block(parent, element);
+                }
+                Dom::Text(text) if paragraph.is_none() && text.trim_ascii().is_empty() => (),
+                child => {
+                    let paragraph = *paragraph
+                        .get_or_insert_with(|| self.start_inlines(parent, NodeValue::Paragraph));
+
+                    match child {
+                        Dom::Text(text) => self.text(paragraph, text),
+                        Dom::Element(element) => self.inline(paragraph, element),
+                    }
+                }
+            }
+        }
+
+        if let Some(paragraph) = paragraph {
+            self.finish_inlines(paragraph);
+        }
+    }
+
+    fn block(&mut self, parent: &'a AstNode<'a>, element: &Element) {
+        match element.name.as_str() {
+            "p" => {
+                let paragraph = self.start_inlines(parent, NodeValue::Paragraph);
+                self.inlines(paragraph, &element.children);
+                self.finish_inlines(paragraph);
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let heading = NodeHeading {
+                    level: element.name.as_bytes()[1] - b'0',
+                    setext: false,
+                };
+                let heading = self.start_inlines(parent, NodeValue::Heading(heading));
+                self.inlines(heading, &element.children);
+                self.finish_inlines(heading);
+            }
+            "ul" | "ol" => self.list(parent, element),
+            "blockquote" => {
+                let quote = self.append(parent, NodeValue::BlockQuote);
+                self.blocks(quote, &element.children);
+            }
+            "pre" => self.code_block(parent, element),
+            "hr" => {
+                self.append(parent, NodeValue::ThematicBreak);
+            }
+            "table" => self.table(parent, element),
+            _ => self.blocks(parent, &element.children),
+        }
+    }
+
+    fn list(&mut self, parent: &'a AstNode<'a>, element: &Element) {
+        let list_type = match element.name.as_str() {
+            "ol" => ListType::Ordered,
+            _ => ListType::Bullet,
+        };
+        let start = element
+            .attribute("start")
+            .and_then(|start| start.trim().parse().ok())
+            .unwrap_or(1);
+        // loose lists have their items in paragraphs
+        let tight = !element
+            .child_elements()
+            .flat_map(Element::child_elements)
+            .any(|child| child.name == "p");
+        let list = NodeList {
+            list_type,
+            marker_offset: 0,
+            padding: 0,
+            start,
+            delimiter: ListDelimType::Period,
+            bullet_char: b'-',
+            tight,
+        };
+        let list_node = self.append(parent, NodeValue::List(list));
+
+        for child in &element.children {
+            let content = match child {
+                Dom::Element(child) if child.name == "li" => &child.children[..],
+                Dom::Text(text) if text.trim_ascii().is_empty() => continue,
+                // content outside of items gets an item of its own
+                child => std::slice::from_ref(child),
+            };
+            let item = NodeList {
+                start: start + list_node.children().count(),
+                ..list
+            };
+            let item = self.append(list_node, NodeValue::Item(item));
+
+            self.blocks(item, content);
+        }
+
+        if list_node.first_child().is_none() {
+            list_node.detach();
+        }
+    }
+
+    // The language comes from a `language-` or `lang-` class of the `pre` or
+    // of the `code` inside it, like comrak and most highlighters write it.
+    fn code_block(&mut self, parent: &'a AstNode<'a>, element: &Element) {
+        let info = std::iter::once(element)
+            .chain(
+                element
+                    .child_elements()
+                    .filter(|child| child.name == "code"),
+            )
+            .filter_map(|element| element.attribute("class"))
+            .flat_map(str::split_ascii_whitespace)
+            .find_map(|class| {
+                class
+                    .strip_prefix("language-")
+                    .or_else(|| class.strip_prefix("lang-"))
+            })
+            .unwrap_or_default()
+            .to_string();
+
+        let text = element.text();
+        // browsers drop the newline right after `<pre>`
+        let text = text.strip_prefix('\n').unwrap_or(&text);
+        let mut literal = text.trim_end_matches('\n').to_string();
+        literal.push('\n');
+
+        self.append(
+            parent,
+            NodeValue::CodeBlock(NodeCodeBlock {
+                fenced: true,
+                fence_char: b'`',
+                fence_length: 3,
+                fence_offset: 0,
+                info,
+                literal,
+            }),
+        );
+    }
+
+    // Rows of `thead`, `tbody` and `tfoot` are all converted, the first row is
+    // the header since markdown tables always have one. Rows are padded to the
+    // same number of cells and the alignment comes from the header cells.
+    fn table(&mut self, parent: &'a AstNode<'a>, element: &Element) {
+        let rows: Vec<&Element> = element
+            .child_elements()
+            .flat_map(|child| match child.name.as_str() {
+                "tr" => vec![child],
+                "thead" | "tbody" | "tfoot" => child
+                    .child_elements()
+                    .filter(|row| row.name == "tr")
+                    .collect(),
+                _ => Vec::new(),
+            })
+            .collect();
+
+        let cells: Vec<Vec<&Element>> = rows
+            .iter()
+            .map(|row| {
+                row.child_elements()
+                    .filter(|cell| cell.name == "td" || cell.name == "th")
+                    .collect()
+            })
+            .collect();
+
+        let columns = cells.iter().map(Vec::len).max().unwrap_or(0);
+
+        if columns == 0 {
+            return;
+        }
+
+        let alignments = (0..columns)
+            .map(|column| {
+                cells[0]
+                    .get(column)
+                    .map_or(TableAlignment::None, |cell| alignment(cell))
+            })
+            .collect();
+        let table = self.append(parent, NodeValue::Table(alignments));
+        let in_table = std::mem::replace(&mut self.in_table, true);
+
+        for (index, row_cells) in cells.iter().enumerate() {
+            let row = self.append(table, NodeValue::TableRow(index == 0));
+
+            for column in 0..columns {
+                let cell = self.start_inlines(row, NodeValue::TableCell);
+
+                if let Some(element) = row_cells.get(column) {
+                    self.inlines(cell, &element.children);
+                }
+
+                self.pending_space = false;
+            }
+        }
+
+        self.in_table = in_table;
+    }
+
+    fn start_inlines(&mut self, parent: &'a AstNode<'a>, value: NodeValue) -> &'a AstNode<'a> {
+        self.pending_space = false;
+        self.started = false;
+        self.append(parent, value)
+    }
+
+    // Line breaks at the edges render nothing in html but would in markdown,
+    // and empty paragraphs and headings aren't kept.
+    fn finish_inlines(&mut self, node: &'a AstNode<'a>) {
+        self.pending_space = false;
+
+        while let Some(child) = node.first_child().filter(|child| is_line_break(child)) {
+            child.detach();
+        }
+
+        while let Some(child) = node.last_child().filter(|child| is_line_break(child)) {
+            child.detach();
+        }
+
+        if node.first_child().is_none() {
+            node.detach();
+        }
+    }
+
+    fn inlines(&mut self, parent: &'a AstNode<'a>, children: &[Dom]) {
+        for child in children {
+            match child {
+                Dom::Text(text) => self.text(parent, text),
+                Dom::Element(element) => self.inline(parent, element),
+            }
+        }
+    }
+
+    fn inline(&mut self, parent: &'a AstNode<'a>, element: &Element) {
+        let container = match element.name.as_str() {
+            "br" if self.in_table => {
+                self.pending_space = true;
+                return;
+            }
+            "br" => {
+                self.append(parent, NodeValue::LineBreak);
+                self.pending_space = false;
+                self.started = false;
+                return;
+            }
+            "img" => {
+                let image = NodeLink {
+                    url: element.attribute("src").unwrap_or_default().to_string(),
+                    title: element.attribute("title").unwrap_or_default().to_string(),
+                };
+
+                self.flush_space(parent);
+                let image = self.append(parent, NodeValue::Image(image));
+                let alt = collapse(element.attribute("alt").unwrap_or_default());
+
+                if !alt.is_empty() {
+                    self.append(image, NodeValue::Text(alt));
+                }
+
+                self.started = true;
+                return;
+            }
+            "code" | "kbd" | "samp" | "tt" => {
+                let literal = collapse(&element.text());
+
+                if !literal.is_empty() {
+                    self.flush_space(parent);
+                    self.append(
+                        parent,
+                        NodeValue::Code(NodeCode {
+                            num_backticks: 1,
+                            literal,
+                        }),
+                    );
+                    self.started = true;
+                }
+
+                return;
+            }
+            "a" => match element.attribute("href") {
+                Some(href) => NodeValue::Link(NodeLink {
+                    url: href.to_string(),
+                    title: element.attribute("title").unwrap_or_default().to_string(),
+                }),
+                None => return self.inlines(parent, &element.children),
+            },
+            // blocks within inlines, like paragraphs in table cells, are
+            // separated by a space
+            name if BLOCKS.contains(&name) => {
+                self.pending_space = true;
+                self.inlines(parent, &element.children);
+                self.pending_space = true;
+                return;
+            }
+            "strong" | "b" => NodeValue::Strong,
+            "em" | "i" => NodeValue::Emph,
+            "del" | "s" | "strike" => NodeValue::Strikethrough,
+            _ => return self.inlines(parent, &element.children),
+        };
+
+        self.flush_space(parent);
+        let node = self.append(parent, container);
+        self.inlines(node, &element.children);
+
+        if node.first_child().is_none() {
+            node.detach();
+        }
+    }
+
+    fn text(&mut self, parent: &'a AstNode<'a>, text: &str) {
+        for c in text.chars() {
+            if c.is_ascii_whitespace() {
+                self.pending_space = true;
+                continue;
+            }
+
+            self.flush_space(parent);
+            self.started = true;
+            append_char(self.arena, parent, c);
+        }
+    }
+
+    // Writes the pending space, in front of the emphasis or link it would be
+    // the first child of.
+    fn flush_space(&mut self, parent: &'a AstNode<'a>) {
+        if !std::mem::take(&mut self.pending_space) || !self.started {
+            return;
+        }
+
+        if parent.first_child().is_some() || !is_inline_container(parent) {
+            return append_char(self.arena, parent, ' ');
+        }
+
+        let mut container = parent;
+
+        while let Some(outer) = container.parent().filter(|outer| {
+            is_inline_container(outer)
+                && outer
+                    .first_child()
+                    .is_some_and(|first| std::ptr::eq(first, container))
+        }) {
+            container = outer;
+        }
+
+        match container.previous_sibling() {
+            Some(previous) if is_text(previous) => push_char(previous, ' '),
+            _ => container.insert_before(new_node(self.arena, NodeValue::Text(" ".to_string()))),
+        }
+    }
+}
+
+fn append_char<'a>(arena: &'a Arena<AstNode<'a>>, parent: &'a AstNode<'a>, c: char) {
+    match parent.last_child() {
+        Some(last) if is_text(last) => push_char(last, c),
+        _ => parent.append(new_node(arena, NodeValue::Text(c.to_string()))),
+    }
+}
+
+// Spaces are collapsed, also when written before emphasis that was already
+// preceded by one.
+fn push_char(node: &AstNode, c: char) {
+    if let NodeValue::Text(ref mut literal) = node.data.borrow_mut().value {
+        if c != ' ' || !literal.ends_with(' ') {
+            literal.push(c);
+        }
+    }
+}
+
+fn is_text(node: &AstNode) -> bool {
+    matches!(node.data.borrow().value, NodeValue::Text(_))
+}
+
+fn is_line_break(node: &AstNode) -> bool {
+    matches!(node.data.borrow().value, NodeValue::LineBreak)
+}
+
+fn is_inline_container(node: &AstNode) -> bool {
+    matches!(
+        node.data.borrow().value,
+        NodeValue::Strong | NodeValue::Emph | NodeValue::Strikethrough | NodeValue::Link(_)
+    )
+}
+
+fn alignment(cell: &Element) -> TableAlignment {
+    let style = cell
+        .attribute("style")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let align = style
+        .split(';')
+        .filter_map(|declaration| declaration.split_once(':'))
+        .find(|(property, _)| property.trim() == "text-align")
+        .map(|(_, value)| value.trim().to_string())
+        .or_else(|| cell.attribute("align").map(str::to_ascii_lowercase));
+
+    match align.as_deref() {
+        Some("left") => TableAlignment::Left,
+        Some("center") => TableAlignment::Center,
+        Some("right") => TableAlignment::Right,
+        _ => TableAlignment::None,
+    }
+}
+
+fn collapse(text: &str) -> String {
+    text.split_ascii_whitespace().collect::<Vec<_>>().join(" ")
+}
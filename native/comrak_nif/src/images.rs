@@ -0,0 +1,51 @@
+use crate::headings::collect_text;
+use crate::types::options::ExOptions;
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{
+    parse_document, Arena, ComrakExtensionOptions, ComrakOptions, ComrakParseOptions,
+    ComrakRenderOptions,
+};
+
+#[derive(Debug, NifMap)]
+pub struct ExImage {
+    pub url: String,
+    pub title: String,
+    pub sourcepos: ((usize, usize), (usize, usize)),
+}
+
+// Images without alt text, or with only whitespace. Inline nodes don't carry
+// reliable positions, so the sourcepos is the one of the enclosing block.
+pub fn missing_alt(md: &str, options: ExOptions) -> Vec<ExImage> {
+    let comrak_options = ComrakOptions {
+        extension: ComrakExtensionOptions::from(options.extension),
+        parse: ComrakParseOptions::from(options.parse),
+        render: ComrakRenderOptions::from(options.render),
+    };
+
+    let arena = Arena::new();
+    let root = parse_document(&arena, md, &comrak_options);
+
+    root.descendants()
+        .filter_map(|node| match node.data.borrow().value {
+            NodeValue::Image(ref link) if collect_text(node).trim().is_empty() => Some(ExImage {
+                url: link.url.clone(),
+                title: link.title.clone(),
+                sourcepos: block_sourcepos(node),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn block_sourcepos<'a>(node: &'a AstNode<'a>) -> ((usize, usize), (usize, usize)) {
+    let block = node
+        .ancestors()
+        .find(|ancestor| ancestor.data.borrow().value.block())
+        .unwrap_or(node);
+    let ast = block.data.borrow();
+
+    (
+        (ast.sourcepos.start.line, ast.sourcepos.start.column),
+        (ast.sourcepos.end.line, ast.sourcepos.end.column),
+    )
+}
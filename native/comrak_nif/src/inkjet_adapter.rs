@@ -1,24 +1,53 @@
-use autumn::themes;
+use crate::diff_words;
+use crate::grammar_registry;
+use crate::raw_html::escape_html;
+use crate::types::options::ExCodeBlockAttributesOptions;
 use autumn::themes::Theme;
 use comrak::adapters::SyntaxHighlighterAdapter;
 use inkjet::Language;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::io::{self, Write};
-use tree_sitter_highlight::Highlighter;
+use tree_sitter_highlight::{HighlightEvent, Highlighter};
 
 #[derive(Debug)]
 pub struct InkjetAdapter<'a> {
     theme: &'a Theme,
+    attributes: Option<&'a ExCodeBlockAttributesOptions>,
+    max_spans_per_block: Option<usize>,
+    diff_words: bool,
+    blocks: Cell<usize>,
+    highlighted: Cell<usize>,
+    // `(block index, spans)` of the blocks over `max_spans_per_block`
+    degraded: RefCell<Vec<(usize, usize)>>,
 }
 
 impl<'a> InkjetAdapter<'a> {
-    pub fn new(theme: &'a str) -> Self {
-        let theme = match themes::theme(theme) {
-            Some(theme) => theme,
-            None => themes::theme("onedark").unwrap(),
-        };
+    pub fn new(
+        theme: &'a Theme,
+        attributes: Option<&'a ExCodeBlockAttributesOptions>,
+        max_spans_per_block: Option<usize>,
+        diff_words: bool,
+    ) -> Self {
+        Self {
+            theme,
+            attributes,
+            max_spans_per_block,
+            diff_words,
+            blocks: Cell::new(0),
+            highlighted: Cell::new(0),
+            degraded: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn highlighted(&self) -> usize {
+        self.highlighted.get()
+    }
 
-        Self { theme }
+    // Code blocks rendered as plain text because they had too many spans, in
+    // the order they were rendered.
+    pub fn degraded(&self) -> Vec<(usize, usize)> {
+        self.degraded.take()
     }
 }
 
@@ -29,29 +58,54 @@ impl<'a> SyntaxHighlighterAdapter for InkjetAdapter<'a> {
         lang: Option<&str>,
         source: &str,
     ) -> io::Result<()> {
+        let block = self.blocks.get();
+        self.blocks.set(block + 1);
         let mut highlighter = Highlighter::new();
+        // blocks without a language are highlighted as diff but aren't marked
+        let mark_changes = self.diff_words && lang == Some("diff");
         let lang = lang.unwrap_or("diff");
-        let lang = Language::from_token(lang).unwrap_or(Language::Diff);
-        let config = lang.config();
+        let config = grammar_registry::config(lang).unwrap_or_else(|| Language::Diff.config());
 
         let highlights = highlighter
-            .highlight(
-                config,
-                source.as_bytes(),
-                None,
-                |token| match Language::from_token(token) {
-                    Some(lang) => Some(lang.config()),
-                    None => None,
-                },
-            )
-            // TODO: fallback to plain text
-            .expect("expected to generate the syntax highlight events");
+            .highlight(config, source.as_bytes(), None, |token| {
+                grammar_registry::config(token)
+            })
+            .and_then(|events| events.collect::<Result<Vec<_>, _>>())
+            // the formatter returns the error, which is returned by the nif
+            .map_err(|err| io::Error::other(format!("syntax highlight failed: {}", err)))?;
+
+        // minified sources produce a span for almost every token
+        if let Some(max_spans) = self.max_spans_per_block {
+            let spans = highlights
+                .iter()
+                .filter(|event| matches!(event, HighlightEvent::HighlightStart(_)))
+                .count();
+
+            if spans > max_spans {
+                self.degraded.borrow_mut().push((block, spans));
+                return write!(output, "{}", escape_html(source));
+            }
+        }
+
+        self.highlighted.set(self.highlighted.get() + 1);
+
+        let changed = match mark_changes {
+            true => diff_words::changed_ranges(source),
+            false => Vec::new(),
+        };
 
         for event in highlights {
-            // TODO: fallback to plain text
-            let event = event.expect("expected a highlight event");
-            let inner_highlights = autumn::inner_highlights(source, event, self.theme);
-            write!(output, "{}", inner_highlights)?
+            match event {
+                HighlightEvent::Source { start, end } if !changed.is_empty() => write!(
+                    output,
+                    "{}",
+                    diff_words::format_source(source, start..end, &changed)
+                )?,
+                event => {
+                    let inner_highlights = autumn::inner_highlights(source, event, self.theme);
+                    write!(output, "{}", inner_highlights)?
+                }
+            }
         }
 
         Ok(())
@@ -63,7 +117,14 @@ impl<'a> SyntaxHighlighterAdapter for InkjetAdapter<'a> {
         _attributes: HashMap<String, String>,
     ) -> io::Result<()> {
         let pre_tag = autumn::open_pre_tag(self.theme, None);
-        write!(output, "{}", pre_tag)
+
+        match self.attributes {
+            Some(attributes) => {
+                let pre_tag = pre_tag.strip_suffix('>').unwrap_or(&pre_tag);
+                write!(output, "{}{}>", pre_tag, format_attributes(&attributes.pre))
+            }
+            None => write!(output, "{}", pre_tag),
+        }
     }
 
     fn write_code_tag(
@@ -71,15 +132,43 @@ impl<'a> SyntaxHighlighterAdapter for InkjetAdapter<'a> {
         output: &mut dyn Write,
         attributes: HashMap<String, String>,
     ) -> io::Result<()> {
-        if attributes.contains_key("class") {
-            // lang does not matter since class will be replaced
-            let code_tag =
-                autumn::open_code_tag(Language::Diff, Some(attributes["class"].as_str()));
-            write!(output, "{}", code_tag)
-        } else {
-            // assume there's no language and fallbacks to plain text
-            let code_tag = autumn::open_code_tag(Language::Diff, Some("language-plain-text"));
-            write!(output, "{}", code_tag)
+        // assume there's no language and fallbacks to plain text
+        let class = attributes
+            .get("class")
+            .map_or("language-plain-text", String::as_str);
+        // comrak passes the language of the info string unescaped
+        let class = escape_html(class);
+
+        match self.attributes {
+            Some(options) => {
+                write!(output, "<code class=\"{}\"", class)?;
+
+                if let Some(ref translate) = options.translate {
+                    write!(output, " translate=\"{}\"", escape_html(translate))?;
+                }
+
+                if let Some(tabindex) = options.tabindex {
+                    write!(output, " tabindex=\"{}\"", tabindex)?;
+                }
+
+                write!(output, "{}>", format_attributes(&options.code))
+            }
+            None => {
+                // lang does not matter since class will be replaced
+                let code_tag = autumn::open_code_tag(Language::Diff, Some(&class));
+                write!(output, "{}", code_tag)
+            }
         }
     }
 }
+
+// Sorted so the output doesn't depend on the map iteration order.
+fn format_attributes(attributes: &HashMap<String, String>) -> String {
+    let mut attributes: Vec<_> = attributes.iter().collect();
+    attributes.sort();
+
+    attributes
+        .into_iter()
+        .map(|(name, value)| format!(" {}=\"{}\"", escape_html(name), escape_html(value)))
+        .collect()
+}
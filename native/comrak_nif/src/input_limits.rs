@@ -0,0 +1,121 @@
+use crate::heading_scanner::{closes_fence, open_fence, split_indent, Fence};
+use crate::types::options::ExInputLimitsOptions;
+
+// Line scan run before parsing to reject inputs that make the parser or the
+// formatter do a disproportionate amount of work for their size. Counts are
+// approximate and only ever overestimate, code blocks aside, since the input
+// is not parsed.
+pub fn exceeds_limits(md: &str, limits: &ExInputLimitsOptions) -> bool {
+    let mut fence: Option<Fence> = None;
+    let mut link_definitions = 0;
+    let mut table_cells = 0;
+    // inlines never span paragraphs, so these are reset on blank lines
+    let mut delimiter_runs = 0;
+    let mut bracket_depth: usize = 0;
+
+    for line in md.lines() {
+        let (indent, rest) = split_indent(line);
+
+        if let Some(ref open) = fence {
+            if indent < 4 && closes_fence(rest, open) {
+                fence = None;
+            }
+            continue;
+        }
+
+        if rest.trim().is_empty() {
+            delimiter_runs = 0;
+            bracket_depth = 0;
+            continue;
+        }
+
+        if indent < 4 {
+            if let Some(open) = open_fence(rest) {
+                fence = Some(open);
+                continue;
+            }
+        }
+
+        let (depth, content) = strip_containers(rest);
+
+        if depth > limits.max_nesting_depth {
+            return true;
+        }
+
+        if content.starts_with('[') && content.contains("]:") {
+            link_definitions += 1;
+        }
+
+        if content.contains('|') {
+            table_cells += content.bytes().filter(|&b| b == b'|').count() + 1;
+        }
+
+        let mut previous = None;
+        let mut escaped = false;
+
+        for byte in content.bytes() {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'*' | b'_' | b'~' if previous != Some(byte) => delimiter_runs += 1,
+                b'[' => bracket_depth += 1,
+                b']' => bracket_depth = bracket_depth.saturating_sub(1),
+                _ => (),
+            }
+
+            if bracket_depth > limits.max_nesting_depth {
+                return true;
+            }
+
+            previous = Some(byte);
+        }
+
+        if link_definitions > limits.max_link_definitions
+            || table_cells > limits.max_table_cells
+            || delimiter_runs > limits.max_delimiter_runs
+        {
+            return true;
+        }
+    }
+
+    false
+}
+
+// Strips block quote and list item markers from the start of the line,
+// returning how many containers it opens.
+fn strip_containers(rest: &str) -> (usize, &str) {
+    let mut depth = 0;
+    let mut rest = rest;
+
+    loop {
+        rest = rest.trim_start();
+
+        if let Some(after) = rest.strip_prefix('>') {
+            depth += 1;
+            rest = after;
+            continue;
+        }
+
+        let bytes = rest.as_bytes();
+        let marker_end = match bytes.first() {
+            Some(b'-' | b'*' | b'+') => 1,
+            Some(b'0'..=b'9') => {
+                let digits = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+
+                match bytes.get(digits) {
+                    Some(b'.' | b')') if digits <= 9 => digits + 1,
+                    _ => return (depth, rest),
+                }
+            }
+            _ => return (depth, rest),
+        };
+
+        match bytes.get(marker_end) {
+            Some(b' ' | b'\t') => {
+                depth += 1;
+                rest = &rest[marker_end..];
+            }
+            _ => return (depth, rest),
+        }
+    }
+}
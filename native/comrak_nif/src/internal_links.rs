@@ -0,0 +1,76 @@
+use crate::raw_html::{allow_injected_html, escape_html, html_inline};
+use crate::types::options::ExInternalLinksOptions;
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{Arena, ComrakOptions};
+
+// Prefixes the path of links to the site and adds the configured attributes,
+// like `data-phx-link`, so client side routers can handle them. Only link
+// nodes are changed, urls in code, text and raw html are left untouched.
+pub fn apply<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    root: &'a AstNode<'a>,
+    options: &ExInternalLinksOptions,
+    comrak_options: &mut ComrakOptions,
+) {
+    let links: Vec<_> = root
+        .descendants()
+        .filter(|node| match node.data.borrow().value {
+            NodeValue::Link(ref link) => is_internal(&link.url, &options.prefixes),
+            _ => false,
+        })
+        .collect();
+
+    if links.is_empty() {
+        return;
+    }
+
+    if !options.attributes.is_empty() {
+        allow_injected_html(root, comrak_options);
+    }
+
+    for node in links {
+        let mut ast = node.data.borrow_mut();
+
+        let link = match ast.value {
+            NodeValue::Link(ref mut link) => link,
+            _ => continue,
+        };
+
+        if let Some(ref path_prefix) = options.path_prefix {
+            link.url.insert_str(0, path_prefix.trim_end_matches('/'));
+        }
+
+        if options.attributes.is_empty() {
+            continue;
+        }
+
+        let mut tag = format!("<a href=\"{}\"", escape_html(&link.url));
+
+        if !link.title.is_empty() {
+            tag.push_str(&format!(" title=\"{}\"", escape_html(&link.title)));
+        }
+
+        for (name, value) in &options.attributes {
+            tag.push_str(&format!(
+                " {}=\"{}\"",
+                escape_html(name),
+                escape_html(value)
+            ));
+        }
+
+        tag.push('>');
+
+        // the node keeps its inlines but renders nothing by itself
+        ast.value = NodeValue::Document;
+        node.prepend(html_inline(arena, tag));
+        node.append(html_inline(arena, "</a>".to_string()));
+    }
+}
+
+// Protocol relative urls like `//example.com` point to other sites.
+fn is_internal(url: &str, prefixes: &[String]) -> bool {
+    !url.starts_with("//")
+        && prefixes
+            .iter()
+            .any(|prefix| url.starts_with(prefix.as_str()))
+}
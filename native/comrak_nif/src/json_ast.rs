@@ -0,0 +1,177 @@
+use crate::broken_links;
+use crate::render::{self, RenderError};
+use crate::types::options::ExOptions;
+use comrak::nodes::{AstNode, ListDelimType, ListType, NodeValue, TableAlignment};
+use comrak::Arena;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+// The parsed document as JSON, written by serde straight from the comrak
+// nodes without building an intermediate tree. Every node has a `type`, its
+// `sourcepos` as `[start_line, start_column, end_line, end_column]` and its
+// `children`, plus the fields of its type.
+pub fn to_json(md: &str, options: ExOptions) -> Result<String, RenderError> {
+    let (_, comrak_options, features) = render::resolve_options(options)?;
+
+    render::check_input(md, &features)?;
+
+    let arena = Arena::new();
+    let root = broken_links::parse_document(
+        &arena,
+        md,
+        &comrak_options,
+        features.broken_link_resolver.as_ref(),
+    );
+
+    serde_json::to_string(&JsonNode(root)).map_err(|err| RenderError::Other(err.to_string()))
+}
+
+pub struct JsonNode<'a>(pub &'a AstNode<'a>);
+
+struct JsonChildren<'a>(&'a AstNode<'a>);
+
+impl<'a> Serialize for JsonNode<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let ast = self.0.data.borrow();
+        let sourcepos = ast.sourcepos;
+        let mut map = serializer.serialize_map(None)?;
+
+        match ast.value {
+            NodeValue::Document => map.serialize_entry("type", "document")?,
+            NodeValue::FrontMatter(ref literal) => {
+                map.serialize_entry("type", "front_matter")?;
+                map.serialize_entry("literal", literal)?;
+            }
+            NodeValue::BlockQuote => map.serialize_entry("type", "block_quote")?,
+            NodeValue::List(ref list) | NodeValue::Item(ref list) => {
+                let node_type = match ast.value {
+                    NodeValue::List(_) => "list",
+                    _ => "item",
+                };
+
+                map.serialize_entry("type", node_type)?;
+                map.serialize_entry(
+                    "list_type",
+                    match list.list_type {
+                        ListType::Bullet => "bullet",
+                        ListType::Ordered => "ordered",
+                    },
+                )?;
+                map.serialize_entry("start", &list.start)?;
+                map.serialize_entry(
+                    "delimiter",
+                    match list.delimiter {
+                        ListDelimType::Period => "period",
+                        ListDelimType::Paren => "paren",
+                    },
+                )?;
+                map.serialize_entry(
+                    "bullet_char",
+                    &match list.list_type {
+                        ListType::Bullet => Some(char::from(list.bullet_char)),
+                        ListType::Ordered => None,
+                    },
+                )?;
+                map.serialize_entry("tight", &list.tight)?;
+            }
+            NodeValue::DescriptionList => map.serialize_entry("type", "description_list")?,
+            NodeValue::DescriptionItem(_) => map.serialize_entry("type", "description_item")?,
+            NodeValue::DescriptionTerm => map.serialize_entry("type", "description_term")?,
+            NodeValue::DescriptionDetails => map.serialize_entry("type", "description_details")?,
+            NodeValue::CodeBlock(ref block) => {
+                map.serialize_entry("type", "code_block")?;
+                map.serialize_entry("fenced", &block.fenced)?;
+                map.serialize_entry("info", &block.info)?;
+                map.serialize_entry("literal", &block.literal)?;
+            }
+            NodeValue::HtmlBlock(ref block) => {
+                map.serialize_entry("type", "html_block")?;
+                map.serialize_entry("literal", &block.literal)?;
+            }
+            NodeValue::Paragraph => map.serialize_entry("type", "paragraph")?,
+            NodeValue::Heading(ref heading) => {
+                map.serialize_entry("type", "heading")?;
+                map.serialize_entry("level", &heading.level)?;
+                map.serialize_entry("setext", &heading.setext)?;
+            }
+            NodeValue::ThematicBreak => map.serialize_entry("type", "thematic_break")?,
+            NodeValue::FootnoteDefinition(ref name) => {
+                map.serialize_entry("type", "footnote_definition")?;
+                map.serialize_entry("name", name)?;
+            }
+            NodeValue::Table(ref alignments) => {
+                let alignments: Vec<_> = alignments
+                    .iter()
+                    .map(|alignment| match alignment {
+                        TableAlignment::None => "none",
+                        TableAlignment::Left => "left",
+                        TableAlignment::Center => "center",
+                        TableAlignment::Right => "right",
+                    })
+                    .collect();
+
+                map.serialize_entry("type", "table")?;
+                map.serialize_entry("alignments", &alignments)?;
+            }
+            NodeValue::TableRow(header) => {
+                map.serialize_entry("type", "table_row")?;
+                map.serialize_entry("header", &header)?;
+            }
+            NodeValue::TableCell => map.serialize_entry("type", "table_cell")?,
+            NodeValue::Text(ref literal) => {
+                map.serialize_entry("type", "text")?;
+                map.serialize_entry("literal", literal)?;
+            }
+            NodeValue::TaskItem(symbol) => {
+                map.serialize_entry("type", "task_item")?;
+                map.serialize_entry("checked", &symbol.is_some())?;
+            }
+            NodeValue::SoftBreak => map.serialize_entry("type", "soft_break")?,
+            NodeValue::LineBreak => map.serialize_entry("type", "line_break")?,
+            NodeValue::Code(ref code) => {
+                map.serialize_entry("type", "code")?;
+                map.serialize_entry("literal", &code.literal)?;
+            }
+            NodeValue::HtmlInline(ref literal) => {
+                map.serialize_entry("type", "html_inline")?;
+                map.serialize_entry("literal", literal)?;
+            }
+            NodeValue::Emph => map.serialize_entry("type", "emph")?,
+            NodeValue::Strong => map.serialize_entry("type", "strong")?,
+            NodeValue::Strikethrough => map.serialize_entry("type", "strikethrough")?,
+            NodeValue::Superscript => map.serialize_entry("type", "superscript")?,
+            NodeValue::Link(ref link) | NodeValue::Image(ref link) => {
+                let node_type = match ast.value {
+                    NodeValue::Link(_) => "link",
+                    _ => "image",
+                };
+
+                map.serialize_entry("type", node_type)?;
+                map.serialize_entry("url", &link.url)?;
+                map.serialize_entry("title", &link.title)?;
+            }
+            NodeValue::FootnoteReference(ref name) => {
+                map.serialize_entry("type", "footnote_reference")?;
+                map.serialize_entry("name", name)?;
+            }
+            NodeValue::ShortCode(_) => map.serialize_entry("type", "short_code")?,
+        }
+
+        map.serialize_entry(
+            "sourcepos",
+            &[
+                sourcepos.start.line,
+                sourcepos.start.column,
+                sourcepos.end.line,
+                sourcepos.end.column,
+            ],
+        )?;
+        map.serialize_entry("children", &JsonChildren(self.0))?;
+        map.end()
+    }
+}
+
+impl<'a> Serialize for JsonChildren<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.children().map(JsonNode))
+    }
+}
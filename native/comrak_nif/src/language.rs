@@ -0,0 +1,160 @@
+use crate::raw_html::{allow_injected_html, escape_html, html_inline};
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{Arena, ComrakOptions};
+use whatlang::Lang;
+
+#[derive(Debug, NifMap)]
+pub struct ExLanguage {
+    pub lang: String,
+    pub name: String,
+    pub confidence: f64,
+    pub reliable: bool,
+}
+
+// Detects the natural language of the text of the document, ignoring code
+// and raw html, or `None` when there isn't enough text.
+pub fn detect<'a>(root: &'a AstNode<'a>) -> Option<ExLanguage> {
+    let info = whatlang::detect(&text(root))?;
+
+    Some(ExLanguage {
+        lang: tag(info.lang()).to_string(),
+        name: info.lang().eng_name().to_string(),
+        confidence: info.confidence(),
+        reliable: info.is_reliable(),
+    })
+}
+
+// Adds a `lang` attribute to the top level paragraphs reliably detected in
+// another language than the document.
+pub fn mark_paragraphs<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    root: &'a AstNode<'a>,
+    document_lang: Option<&str>,
+    comrak_options: &mut ComrakOptions,
+) {
+    let paragraphs: Vec<_> = root
+        .children()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::Paragraph))
+        .filter_map(|node| {
+            let info = whatlang::detect(&text(node))?;
+            let lang = tag(info.lang());
+
+            match info.is_reliable() && Some(lang) != document_lang {
+                true => Some((node, lang)),
+                false => None,
+            }
+        })
+        .collect();
+
+    if paragraphs.is_empty() {
+        return;
+    }
+
+    allow_injected_html(root, comrak_options);
+
+    for (node, lang) in paragraphs {
+        let mut ast = node.data.borrow_mut();
+        let sourcepos = match comrak_options.render.sourcepos {
+            true => format!(" data-sourcepos=\"{}\"", ast.sourcepos),
+            false => String::new(),
+        };
+
+        // the node keeps its inlines but renders nothing by itself
+        ast.value = NodeValue::Document;
+        node.prepend(html_inline(
+            arena,
+            format!("<p lang=\"{}\"{}>", escape_html(lang), sourcepos),
+        ));
+        node.append(html_inline(arena, "</p>\n".to_string()));
+    }
+}
+
+fn text<'a>(node: &'a AstNode<'a>) -> String {
+    let mut text = String::new();
+
+    for node in node.descendants() {
+        if let NodeValue::Text(ref literal) = node.data.borrow().value {
+            text.push_str(literal);
+            text.push(' ');
+        }
+    }
+
+    text
+}
+
+// The ISO 639-1 code used in `lang` attributes, or the ISO 639-3 code
+// whatlang uses for languages without one.
+pub fn tag(lang: Lang) -> &'static str {
+    match lang.code() {
+        "afr" => "af",
+        "aka" => "ak",
+        "amh" => "am",
+        "ara" => "ar",
+        "aze" => "az",
+        "bel" => "be",
+        "ben" => "bn",
+        "bul" => "bg",
+        "cat" => "ca",
+        "ces" => "cs",
+        "cmn" => "zh",
+        "dan" => "da",
+        "deu" => "de",
+        "ell" => "el",
+        "eng" => "en",
+        "epo" => "eo",
+        "est" => "et",
+        "fin" => "fi",
+        "fra" => "fr",
+        "guj" => "gu",
+        "heb" => "he",
+        "hin" => "hi",
+        "hrv" => "hr",
+        "hun" => "hu",
+        "hye" => "hy",
+        "ind" => "id",
+        "ita" => "it",
+        "jav" => "jv",
+        "jpn" => "ja",
+        "kan" => "kn",
+        "kat" => "ka",
+        "khm" => "km",
+        "kor" => "ko",
+        "lat" => "la",
+        "lav" => "lv",
+        "lit" => "lt",
+        "mal" => "ml",
+        "mar" => "mr",
+        "mkd" => "mk",
+        "mya" => "my",
+        "nep" => "ne",
+        "nld" => "nl",
+        "nob" => "nb",
+        "ori" => "or",
+        "pan" => "pa",
+        "pes" => "fa",
+        "pol" => "pl",
+        "por" => "pt",
+        "ron" => "ro",
+        "rus" => "ru",
+        "sin" => "si",
+        "slk" => "sk",
+        "slv" => "sl",
+        "sna" => "sn",
+        "spa" => "es",
+        "srp" => "sr",
+        "swe" => "sv",
+        "tam" => "ta",
+        "tel" => "te",
+        "tgl" => "tl",
+        "tha" => "th",
+        "tuk" => "tk",
+        "tur" => "tr",
+        "ukr" => "uk",
+        "urd" => "ur",
+        "uzb" => "uz",
+        "vie" => "vi",
+        "yid" => "yi",
+        "zul" => "zu",
+        code => code,
+    }
+}
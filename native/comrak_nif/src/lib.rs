@@ -1,57 +1,617 @@
 #[macro_use]
 extern crate rustler;
 
+mod abbreviations;
+mod anchor_report;
+mod ansi;
+mod attribute_rules;
+mod audit;
+mod batch;
+mod broken_links;
+mod cache;
+mod code_blocks;
+mod data_urls;
+mod description_list;
+mod diff_words;
+mod element_ids;
+mod entities;
+mod fingerprint;
+mod footnotes;
+mod front_matter;
+mod grammar_registry;
+mod heading_adapter;
+mod heading_path;
+mod heading_scanner;
+mod headings;
+mod html_to_markdown;
+mod images;
 mod inkjet_adapter;
+mod input_limits;
+mod internal_links;
+mod job;
+mod json_ast;
+mod language;
+mod link_titles;
+mod metrics;
+mod omit;
+mod output;
+mod pool;
+mod presets;
+mod raw_html;
+mod relative_urls;
+mod render;
+mod replacements;
+mod responsive_images;
+mod roundtrip;
+mod sanitize_preview;
+mod sanitizer;
+mod seo;
+mod short_links;
+mod svg;
+mod text_units;
+mod theme;
+mod toc;
+mod tokens;
 mod types;
+mod typography;
+mod urls;
+mod video_embeds;
+mod warnings;
+mod wrapper;
 
-use ammonia::clean;
-use comrak::{
-    markdown_to_html, markdown_to_html_with_plugins, ComrakExtensionOptions, ComrakOptions,
-    ComrakParseOptions, ComrakPlugins, ComrakRenderOptions,
+use cache::CacheResource;
+use heading_path::ExHeadingTarget;
+use heading_scanner::ExOutlineHeading;
+use images::ExImage;
+use job::{CancellationToken, RenderJob};
+use metrics::{ExMetrics, MetricsResource};
+use rustler::{
+    Atom, Binary, Encoder, Env, LocalPid, NifResult, OwnedBinary, OwnedEnv, ResourceArc, Term,
 };
-use inkjet_adapter::InkjetAdapter;
-use rustler::{Env, NifResult, Term};
-use serde_rustler::to_term;
+use sanitize_preview::ExSanitizePreview;
+use seo::ExSeoMetadata;
+use std::time::Duration;
 use types::options::*;
 
-rustler::init!("Elixir.MDEx.Native", [to_html, to_html_with_options]);
+mod atoms {
+    rustler::atoms! {
+        ok,
+        error,
+        mdex,
+        cancelled,
+        done,
+        duplicate_heading_id,
+        enomem,
+        input_too_large,
+        output_too_large,
+        pathological_input,
+        timeout,
+        unknown_theme
+    }
+}
+
+rustler::init!(
+    "Elixir.MDEx.Native",
+    [
+        to_html,
+        to_html_with_options,
+        to_html_with_metadata,
+        render_many_to_html,
+        markdown_to_html_batch,
+        render_async,
+        render_async_panic,
+        pooled_output_capacities,
+        render_batch_async,
+        cancel,
+        outline,
+        heading_path_at,
+        new_metrics,
+        get_metrics,
+        new_cache,
+        register_grammar,
+        register_incompatible_grammar,
+        highlight_code_svg,
+        highlight_tokens,
+        images_missing_alt,
+        seo_metadata,
+        sanitize_preview,
+        anchor_report,
+        render_options_fingerprint,
+        code_blocks_to_html,
+        to_json_ast,
+        parse_front_matter,
+        html_to_markdown,
+        verify_roundtrip,
+        markdown_to_ansi_with_options
+    ],
+    load = on_load
+);
+
+fn on_load(env: Env, _info: Term) -> bool {
+    rustler::resource!(MetricsResource, env);
+    rustler::resource!(CacheResource, env);
+    rustler::resource!(RenderJob, env);
+    true
+}
 
+// Same pipeline and defaults as `to_html_with_options`, so both arities
+// always render identically.
 #[rustler::nif(schedule = "DirtyCpu")]
-fn to_html(md: &str) -> String {
-    let inkjet_adapter = InkjetAdapter::new("onedark");
-    let mut plugins = ComrakPlugins::default();
-    plugins.render.codefence_syntax_highlighter = Some(&inkjet_adapter);
-    markdown_to_html_with_plugins(md, &ComrakOptions::default(), &plugins)
+fn to_html<'a>(env: Env<'a>, md: Binary<'a>) -> NifResult<Term<'a>> {
+    let md = decode_markdown(&md)?;
+    render_to_term(env, md, ExOptions::default())
 }
 
+// Options are decoded here rather than by rustler since the cache key is
+// computed from the options term.
 #[rustler::nif(schedule = "DirtyCpu")]
-fn to_html_with_options<'a>(env: Env<'a>, md: &str, options: ExOptions) -> NifResult<Term<'a>> {
-    let comrak_options = ComrakOptions {
-        extension: ComrakExtensionOptions::from(options.extension),
-        parse: ComrakParseOptions::from(options.parse),
-        render: ComrakRenderOptions::from(options.render),
+fn to_html_with_options<'a>(
+    env: Env<'a>,
+    md: Binary<'a>,
+    options: Term<'a>,
+) -> NifResult<Term<'a>> {
+    let md = decode_markdown(&md)?;
+    let mut ex_options: ExOptions = options.decode()?;
+
+    let cache = match ex_options.features.cache.take() {
+        Some(cache) => cache.0,
+        None => return render_to_term(env, md, ex_options),
     };
+    let key = cache::Key::new(md, options.to_binary().as_slice());
+
+    if let Some(html) = cache.get(&key) {
+        return encode_binary(env, &html);
+    }
 
-    match options.features.syntax_highlight_theme {
-        Some(theme) => {
-            let inkjet_adapter = InkjetAdapter::new(&theme);
-            let mut plugins = ComrakPlugins::default();
-            plugins.render.codefence_syntax_highlighter = Some(&inkjet_adapter);
-            let unsafe_html = markdown_to_html_with_plugins(md, &comrak_options, &plugins);
-            render(env, unsafe_html, options.features.sanitize)
+    match render::render_html(md, ex_options, None) {
+        Ok(html) => {
+            cache.insert(key, html.as_bytes());
+            encode_binary(env, html.as_bytes())
         }
-        None => {
-            let unsafe_html = markdown_to_html(md, &comrak_options);
-            render(env, unsafe_html, options.features.sanitize)
+        Err(err) => Ok(err.encode(env)),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn markdown_to_ansi_with_options<'a>(
+    env: Env<'a>,
+    md: Binary<'a>,
+    options: ExOptions,
+) -> NifResult<Term<'a>> {
+    let md = decode_markdown(&md)?;
+
+    match ansi::render(md, options) {
+        Ok(ansi) => encode_binary(env, ansi.as_bytes()),
+        Err(err) => Ok(err.encode(env)),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn to_html_with_metadata<'a>(
+    env: Env<'a>,
+    md: Binary<'a>,
+    options: ExOptions,
+) -> NifResult<Term<'a>> {
+    let md = decode_markdown(&md)?;
+
+    match render::render_html(md, options, None) {
+        Ok(html) => {
+            let binary = encode_binary(env, html.as_bytes())?;
+            let metadata = html.metadata.encode(env);
+            Ok((atoms::ok(), binary, metadata).encode(env))
         }
+        Err(err) => Ok(err.encode(env)),
+    }
+}
+
+// Returns the json as a binary, which is cheaper than encoding every node
+// as a term for large documents.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn to_json_ast<'a>(env: Env<'a>, md: Binary<'a>, options: ExOptions) -> NifResult<Term<'a>> {
+    let md = decode_markdown(&md)?;
+
+    match json_ast::to_json(md, options) {
+        Ok(json) => encode_binary(env, json.as_bytes()),
+        Err(err) => Ok(err.encode(env)),
+    }
+}
+
+fn render_to_term<'a>(env: Env<'a>, md: &str, options: ExOptions) -> NifResult<Term<'a>> {
+    match render::render_html(md, options, None) {
+        Ok(html) => encode_binary(env, html.as_bytes()),
+        Err(err) => Ok(err.encode(env)),
+    }
+}
+
+// Options are decoded once per document since rendering consumes them.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn render_many_to_html<'a>(
+    env: Env<'a>,
+    documents: Vec<(String, Binary<'a>)>,
+    options: Term<'a>,
+) -> NifResult<Term<'a>> {
+    let documents = documents
+        .iter()
+        .map(|(id, md)| Ok((id.clone(), decode_markdown(md)?, options.decode()?)))
+        .collect::<NifResult<Vec<_>>>()?;
+
+    match render::render_many(documents) {
+        Ok(html) => encode_binary(env, &html),
+        Err(err) => Ok(err.encode(env)),
+    }
+}
+
+// Options are decoded here, once per document, since terms can't be shared
+// with the render threads.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn markdown_to_html_batch<'a>(
+    env: Env<'a>,
+    documents: Vec<Binary<'a>>,
+    options: Term<'a>,
+    max_concurrency: usize,
+) -> NifResult<Term<'a>> {
+    let documents = documents
+        .iter()
+        .map(|md| Ok((decode_markdown(md)?, options.decode()?)))
+        .collect::<NifResult<Vec<_>>>()?;
+
+    batch::render(documents, max_concurrency)
+        .into_iter()
+        .map(|result| match result {
+            Ok(html) => encode_binary(env, html.as_bytes()),
+            Err(err) => Ok(err.encode(env)),
+        })
+        .collect::<NifResult<Vec<Term>>>()
+        .map(|results| results.encode(env))
+}
+
+// Renders on the shared render pool and sends `{:mdex, job, result}` to the
+// caller once done. Cancelled jobs stop at the next check and send nothing.
+#[rustler::nif]
+fn render_async<'a>(
+    env: Env<'a>,
+    md: Binary<'a>,
+    options: ExOptions,
+) -> NifResult<ResourceArc<RenderJob>> {
+    let md = decode_markdown(&md)?.to_string();
+
+    Ok(spawn_render(env.pid(), move |token| {
+        render::render_html(&md, options, Some(token))
+    }))
+}
+
+// Only used by the tests, to check a panicking render still sends a message.
+#[rustler::nif]
+fn render_async_panic(env: Env) -> ResourceArc<RenderJob> {
+    spawn_render(
+        env.pid(),
+        |_| -> Result<render::Html, render::RenderError> { panic!("render_async_panic") },
+    )
+}
+
+// Only used by the tests, renders every document on the calling thread and
+// returns the capacity of the pooled output buffer after each render.
+#[rustler::nif]
+fn pooled_output_capacities(documents: Vec<String>) -> Vec<Option<usize>> {
+    documents
+        .iter()
+        .map(|md| {
+            drop(render::render_html(md, ExOptions::default(), None));
+            output::pooled_capacity()
+        })
+        .collect()
+}
+
+// Runs `render` on the render pool and sends `{:mdex, job, result}` to `pid`
+// unless the job is cancelled. A panic is sent as `{:error, reason}` so the
+// caller isn't left waiting for a message.
+fn spawn_render<F>(pid: LocalPid, render: F) -> ResourceArc<RenderJob>
+where
+    F: FnOnce(CancellationToken) -> Result<render::Html, render::RenderError> + Send + 'static,
+{
+    let job = ResourceArc::new(RenderJob::default());
+    let job_ref = job.clone();
+
+    pool::spawn(move || {
+        let token = job_ref.token.clone();
+        let result = render::catch_panic(|| render(token.clone()));
+
+        if token.is_cancelled() {
+            return;
+        }
+
+        let mut msg_env = OwnedEnv::new();
+        msg_env.send_and_clear(&pid, |env| {
+            let result = match result {
+                Ok(html) => match encode_binary(env, html.as_bytes()) {
+                    Ok(html) => (atoms::ok(), html).encode(env),
+                    Err(_) => (atoms::error(), atoms::enomem()).encode(env),
+                },
+                Err(err) => err.encode(env),
+            };
+
+            (atoms::mdex(), job_ref, result).encode(env)
+        });
+    });
+
+    job
+}
+
+// Renders on the shared render pool and sends `{:mdex, job, {index, result}}`
+// for each document as soon as it's rendered, then
+// `{:mdex, job, {:done, summary}}` with `:ok` or the error of every document,
+// in order. Cancelled batches send nothing more.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn render_batch_async<'a>(
+    env: Env<'a>,
+    documents: Vec<Binary<'a>>,
+    options: Term<'a>,
+    max_concurrency: usize,
+) -> NifResult<ResourceArc<RenderJob>> {
+    let documents = documents
+        .iter()
+        .map(|md| Ok((decode_markdown(md)?.to_string(), options.decode()?)))
+        .collect::<NifResult<Vec<(String, ExOptions)>>>()?;
+    let job = ResourceArc::new(RenderJob::default());
+    let token = job.token.clone();
+    let pid = env.pid();
+    let (job_ref, done_ref) = (job.clone(), job.clone());
+
+    batch::spawn_each(
+        documents,
+        max_concurrency,
+        token.clone(),
+        move |index, result| {
+            if !token.is_cancelled() {
+                let mut msg_env = OwnedEnv::new();
+                msg_env.send_and_clear(&pid, |env| {
+                    let result = match result {
+                        Ok(ref html) => match encode_binary(env, html.as_bytes()) {
+                            Ok(html) => (atoms::ok(), html).encode(env),
+                            Err(_) => (atoms::error(), atoms::enomem()).encode(env),
+                        },
+                        Err(ref err) => err.encode(env),
+                    };
+
+                    (atoms::mdex(), job_ref.clone(), (index, result)).encode(env)
+                });
+            }
+
+            result.map(|_| ())
+        },
+        move |summary| {
+            let mut msg_env = OwnedEnv::new();
+            msg_env.send_and_clear(&pid, |env| {
+                let summary: Vec<Term> = summary
+                    .iter()
+                    .map(|result| match result {
+                        Ok(()) => atoms::ok().encode(env),
+                        Err(err) => err.encode(env),
+                    })
+                    .collect();
+
+                (atoms::mdex(), done_ref, (atoms::done(), summary)).encode(env)
+            });
+        },
+    );
+
+    Ok(job)
+}
+
+#[rustler::nif]
+fn cancel(job: ResourceArc<RenderJob>) -> Atom {
+    job.token.cancel();
+    atoms::ok()
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn code_blocks_to_html<'a>(
+    env: Env<'a>,
+    md: Binary<'a>,
+    options: ExOptions,
+) -> NifResult<Term<'a>> {
+    let md = decode_markdown(&md)?;
+
+    match code_blocks::to_html(md, options) {
+        Ok(code_blocks) => Ok(code_blocks.encode(env)),
+        Err(err) => Ok(err.encode(env)),
     }
 }
 
-fn render(env: Env, unsafe_html: String, sanitize: bool) -> NifResult<Term> {
-    let html = match sanitize {
-        true => clean(&unsafe_html),
-        false => unsafe_html,
+#[rustler::nif(schedule = "DirtyCpu")]
+fn verify_roundtrip<'a>(env: Env<'a>, md: Binary<'a>, options: ExOptions) -> NifResult<Term<'a>> {
+    let md = decode_markdown(&md)?;
+
+    match roundtrip::verify(md, options) {
+        Ok(report) => Ok((atoms::ok(), report).encode(env)),
+        Err(err) => Ok(err.encode(env)),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn html_to_markdown<'a>(env: Env<'a>, html: &str, options: ExOptions) -> NifResult<Term<'a>> {
+    match html_to_markdown::convert(html, options) {
+        Ok(markdown) => encode_binary(env, markdown.as_bytes()),
+        Err(err) => Ok(err.encode(env)),
+    }
+}
+
+// The front matter is encoded by serde_rustler, maps keep their string keys.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn parse_front_matter<'a>(env: Env<'a>, md: Binary<'a>, options: ExOptions) -> NifResult<Term<'a>> {
+    let md = decode_markdown(&md)?;
+
+    match front_matter::parse(md, options) {
+        Ok((front_matter, html)) => {
+            let front_matter = serde_rustler::to_term(env, front_matter)
+                .map_err(|err| rustler::Error::Term(Box::new(err.to_string())))?;
+            let html = encode_binary(env, html.as_bytes())?;
+
+            Ok((atoms::ok(), front_matter, html).encode(env))
+        }
+        Err(err) => Ok(err.encode(env)),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn outline(md: Binary, options: ExOptions) -> NifResult<Vec<ExOutlineHeading>> {
+    let md = decode_markdown(&md)?;
+    Ok(heading_scanner::outline(
+        md,
+        options.extension.front_matter_delimiter.as_deref(),
+    ))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn heading_path_at<'a>(
+    env: Env<'a>,
+    md: Binary<'a>,
+    target: ExHeadingTarget,
+    options: ExOptions,
+) -> NifResult<Term<'a>> {
+    let md = decode_markdown(&md)?;
+
+    match heading_path::heading_path_at(md, &target, options) {
+        Ok(path) => Ok(path.encode(env)),
+        Err(err) => Ok(err.encode(env)),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn images_missing_alt(md: Binary, options: ExOptions) -> NifResult<Vec<ExImage>> {
+    let md = decode_markdown(&md)?;
+    Ok(images::missing_alt(md, options))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn seo_metadata(
+    md: Binary,
+    options: ExOptions,
+    description_length: usize,
+    unit: ExCountUnit,
+) -> NifResult<ExSeoMetadata> {
+    let md = decode_markdown(&md)?;
+    Ok(seo::metadata(md, options, description_length, unit))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn anchor_report<'a>(
+    env: Env<'a>,
+    documents: Vec<(String, Binary<'a>)>,
+    options: Term<'a>,
+) -> NifResult<Term<'a>> {
+    let documents = documents
+        .iter()
+        .map(|(path, md)| Ok((path.clone(), decode_markdown(md)?, options.decode()?)))
+        .collect::<NifResult<Vec<_>>>()?;
+
+    match anchor_report::anchor_report(documents) {
+        Ok(report) => Ok((atoms::ok(), report).encode(env)),
+        Err(err) => Ok(err.encode(env)),
+    }
+}
+
+// Resolved in Rust so the defaults and preset overrides of the render are
+// part of the hash.
+#[rustler::nif]
+fn render_options_fingerprint(env: Env, options: ExOptions, version: String) -> Term {
+    match fingerprint::fingerprint(options, &version) {
+        Ok(fingerprint) => fingerprint.encode(env),
+        Err(err) => err.encode(env),
+    }
+}
+
+// Previews the policy `to_html/2` would sanitize with for these options.
+#[rustler::nif(schedule = "DirtyCpu")]
+fn sanitize_preview(html: &str, options: ExOptions) -> ExSanitizePreview {
+    let mut features = options.features;
+    features.sanitize = true;
+
+    let sanitizer = sanitizer::builder(options.preset.as_ref(), &features).unwrap_or_default();
+    sanitize_preview::preview(html, &sanitizer)
+}
+
+#[rustler::nif]
+fn new_metrics() -> ResourceArc<MetricsResource> {
+    ResourceArc::new(MetricsResource::default())
+}
+
+#[rustler::nif]
+fn get_metrics(metrics: ResourceArc<MetricsResource>) -> ExMetrics {
+    metrics.snapshot()
+}
+
+// `ttl` in milliseconds, entries never expire when `None`.
+#[rustler::nif]
+fn new_cache(max_entries: usize, ttl: Option<u64>) -> ResourceArc<CacheResource> {
+    let ttl = ttl.map(Duration::from_millis);
+    ResourceArc::new(CacheResource::new(max_entries, ttl))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn register_grammar<'a>(
+    env: Env<'a>,
+    name: String,
+    path: String,
+    symbol: Option<String>,
+    highlights_query: String,
+    injections_query: String,
+    locals_query: String,
+) -> Term<'a> {
+    let grammar = grammar_registry::Grammar {
+        name,
+        path,
+        symbol,
+        highlights_query,
+        injections_query,
+        locals_query,
     };
 
-    to_term(env, html).map_err(|err| err.into())
+    match grammar_registry::register(grammar) {
+        Ok(()) => atoms::ok().encode(env),
+        Err(reason) => (atoms::error(), reason).encode(env),
+    }
+}
+
+// Only used by the tests, to check highlight errors are returned.
+#[rustler::nif]
+fn register_incompatible_grammar(env: Env, name: String) -> Term {
+    match grammar_registry::register_incompatible(name) {
+        Ok(()) => atoms::ok().encode(env),
+        Err(reason) => (atoms::error(), reason).encode(env),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn highlight_code_svg<'a>(
+    env: Env<'a>,
+    source: &str,
+    lang: Option<&str>,
+    theme: &str,
+    options: ExSvgOptions,
+) -> Term<'a> {
+    match svg::highlight_code(source, lang, theme, &options) {
+        Ok(svg) => svg.encode(env),
+        Err(reason) => (atoms::error(), reason).encode(env),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn highlight_tokens<'a>(env: Env<'a>, source: &str, lang: &str) -> Term<'a> {
+    match tokens::highlight_tokens(source, lang) {
+        Ok(tokens) => tokens.encode(env),
+        Err(reason) => (atoms::error(), reason).encode(env),
+    }
+}
+
+// Borrows the markdown straight from the binary term instead of copying it.
+fn decode_markdown<'a>(md: &'a Binary) -> NifResult<&'a str> {
+    std::str::from_utf8(md.as_slice()).map_err(|_| rustler::Error::BadArg)
+}
+
+// Copies the rendered html once, straight into the binary returned to the VM.
+fn encode_binary<'a>(env: Env<'a>, bytes: &[u8]) -> NifResult<Term<'a>> {
+    let mut binary = OwnedBinary::new(bytes.len()).ok_or(rustler::Error::RaiseAtom("enomem"))?;
+    binary.as_mut_slice().copy_from_slice(bytes);
+    Ok(binary.release(env).encode(env))
 }
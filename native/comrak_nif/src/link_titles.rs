@@ -0,0 +1,85 @@
+use crate::headings::collect_text;
+use crate::raw_html::{allow_injected_html, escape_html, html_inline};
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{Arena, ComrakOptions};
+
+// Sets the `title` of links without one to their destination, and of
+// footnote references to the text of the footnote, so it shows on hover.
+pub fn apply<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    root: &'a AstNode<'a>,
+    comrak_options: &mut ComrakOptions,
+) {
+    let mut references = Vec::new();
+    let mut definitions = Vec::new();
+
+    for node in root.descendants() {
+        let text = match node.data.borrow().value {
+            NodeValue::Link(_) => collect_text(node),
+            NodeValue::FootnoteReference(_) => {
+                references.push(node);
+                continue;
+            }
+            NodeValue::FootnoteDefinition(_) => {
+                definitions.push(footnote_text(node));
+                continue;
+            }
+            _ => continue,
+        };
+
+        if let NodeValue::Link(ref mut link) = node.data.borrow_mut().value {
+            // autolinks already show the destination
+            if link.title.is_empty() && text != link.url {
+                link.title = link.url.clone();
+            }
+        }
+    }
+
+    if references.is_empty() {
+        return;
+    }
+
+    allow_injected_html(root, comrak_options);
+
+    // comrak numbers references after parsing and sorts the definitions in
+    // the same order, the reference holds its number
+    for reference in references {
+        let number = match reference.data.borrow().value {
+            NodeValue::FootnoteReference(ref number) => number.clone(),
+            _ => continue,
+        };
+
+        let definition = match number
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| definitions.get(number.wrapping_sub(1)))
+        {
+            Some(definition) => definition,
+            None => continue,
+        };
+
+        let number = escape_html(&number);
+        reference.insert_before(html_inline(
+            arena,
+            format!(
+                "<sup class=\"footnote-ref\"><a href=\"#fn{}\" id=\"fnref{}\" title=\"{}\">{}</a></sup>",
+                number,
+                number,
+                escape_html(definition),
+                number
+            ),
+        ));
+        reference.detach();
+    }
+}
+
+// Text of the footnote paragraphs, without the line breaks.
+fn footnote_text<'a>(node: &'a AstNode<'a>) -> String {
+    node.children()
+        .map(collect_text)
+        .collect::<Vec<_>>()
+        .join(" ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
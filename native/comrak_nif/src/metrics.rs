@@ -0,0 +1,70 @@
+use rustler::{Decoder, NifResult, ResourceArc, Term};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Default)]
+pub struct MetricsResource {
+    documents_rendered: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    code_blocks_highlighted: AtomicU64,
+    sanitized_documents: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+pub struct RenderMetrics {
+    pub bytes_in: usize,
+    pub bytes_out: usize,
+    pub code_blocks_highlighted: usize,
+    pub sanitized: bool,
+}
+
+impl MetricsResource {
+    pub fn record(&self, render: RenderMetrics) {
+        self.documents_rendered.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in
+            .fetch_add(render.bytes_in as u64, Ordering::Relaxed);
+        self.bytes_out
+            .fetch_add(render.bytes_out as u64, Ordering::Relaxed);
+        self.code_blocks_highlighted
+            .fetch_add(render.code_blocks_highlighted as u64, Ordering::Relaxed);
+
+        if render.sanitized {
+            self.sanitized_documents.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> ExMetrics {
+        ExMetrics {
+            documents_rendered: self.documents_rendered.load(Ordering::Relaxed),
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            code_blocks_highlighted: self.code_blocks_highlighted.load(Ordering::Relaxed),
+            sanitized_documents: self.sanitized_documents.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, NifMap)]
+pub struct ExMetrics {
+    pub documents_rendered: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub code_blocks_highlighted: u64,
+    pub sanitized_documents: u64,
+}
+
+// Wraps the resource so it can be carried in the options structs.
+pub struct MetricsRef(pub ResourceArc<MetricsResource>);
+
+impl fmt::Debug for MetricsRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MetricsRef").field(&*self.0).finish()
+    }
+}
+
+impl<'a> Decoder<'a> for MetricsRef {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        Ok(MetricsRef(term.decode()?))
+    }
+}
@@ -0,0 +1,66 @@
+use crate::types::options::ExNodeType;
+use comrak::nodes::{AstNode, NodeValue};
+
+// Removes the nodes of the given types, with their content, from the parsed
+// document. Paragraphs, links, emphasis, list items and block quotes left
+// without content are removed as well so they don't render empty tags.
+pub fn apply<'a>(root: &'a AstNode<'a>, types: &[ExNodeType]) {
+    let omitted: Vec<_> = root
+        .descendants()
+        .filter(|node| node_type(&node.data.borrow().value).is_some_and(|t| types.contains(&t)))
+        .collect();
+
+    for node in omitted {
+        let mut parent = node.parent();
+        node.detach();
+
+        while let Some(node) = parent.filter(|node| is_removable(node) && is_blank(node)) {
+            parent = node.parent();
+            node.detach();
+        }
+    }
+}
+
+fn node_type(value: &NodeValue) -> Option<ExNodeType> {
+    match value {
+        NodeValue::BlockQuote => Some(ExNodeType::BlockQuote),
+        NodeValue::List(_) => Some(ExNodeType::List),
+        NodeValue::CodeBlock(_) => Some(ExNodeType::CodeBlock),
+        NodeValue::HtmlBlock(_) => Some(ExNodeType::HtmlBlock),
+        NodeValue::Heading(_) => Some(ExNodeType::Heading),
+        NodeValue::ThematicBreak => Some(ExNodeType::ThematicBreak),
+        NodeValue::Table(_) => Some(ExNodeType::Table),
+        NodeValue::FootnoteDefinition(_) => Some(ExNodeType::FootnoteDefinition),
+        NodeValue::Code(_) => Some(ExNodeType::Code),
+        NodeValue::HtmlInline(_) => Some(ExNodeType::HtmlInline),
+        NodeValue::Link(_) => Some(ExNodeType::Link),
+        NodeValue::Image(_) => Some(ExNodeType::Image),
+        NodeValue::FootnoteReference(_) => Some(ExNodeType::FootnoteReference),
+        _ => None,
+    }
+}
+
+fn is_removable<'a>(node: &'a AstNode<'a>) -> bool {
+    matches!(
+        node.data.borrow().value,
+        NodeValue::Paragraph
+            | NodeValue::Link(_)
+            | NodeValue::Emph
+            | NodeValue::Strong
+            | NodeValue::Strikethrough
+            | NodeValue::Superscript
+            | NodeValue::Item(_)
+            | NodeValue::List(_)
+            | NodeValue::BlockQuote
+    )
+}
+
+// No children other than whitespace and line breaks.
+fn is_blank<'a>(node: &'a AstNode<'a>) -> bool {
+    node.children()
+        .all(|child| match child.data.borrow().value {
+            NodeValue::Text(ref literal) => literal.trim().is_empty(),
+            NodeValue::SoftBreak | NodeValue::LineBreak => true,
+            _ => false,
+        })
+}
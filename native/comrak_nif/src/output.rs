@@ -0,0 +1,118 @@
+use crate::job::CancellationToken;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::time::Instant;
+
+// Buffers larger than this are dropped instead of going back to the pool,
+// so a single huge document doesn't pin its memory on a scheduler thread.
+const MAX_POOLED_CAPACITY: usize = 1024 * 1024;
+
+thread_local! {
+    static BUFFER_POOL: RefCell<Option<Vec<u8>>> = RefCell::new(None);
+}
+
+// Collects the rendered html and stops the formatter as soon as the output
+// grows past `limit`, so a small input that expands enormously never
+// materializes as a giant binary. Every write is also a chance to notice a
+// cancelled render or a passed deadline, which aborts formatting at the next
+// block boundary.
+//
+// The underlying buffer is borrowed from a thread-local pool and given back
+// on drop, which avoids reallocating it for every call on chat-style
+// workloads rendering many small messages. Parse arenas aren't pooled:
+// comrak ties the lifetime of every node to the arena that allocated it, and
+// typed-arena has no way to clear an arena without dropping it, so the only
+// safe reuse would keep every node of every previous document alive.
+#[derive(Debug)]
+pub struct OutputBuffer {
+    buffer: Vec<u8>,
+    limit: Option<usize>,
+    exceeded: bool,
+    cancellation: Option<CancellationToken>,
+    deadline: Option<Instant>,
+}
+
+impl OutputBuffer {
+    pub fn new(
+        limit: Option<usize>,
+        cancellation: Option<CancellationToken>,
+        deadline: Option<Instant>,
+    ) -> Self {
+        let buffer = BUFFER_POOL
+            .with(|pool| pool.borrow_mut().take())
+            .unwrap_or_default();
+
+        Self {
+            buffer,
+            limit,
+            exceeded: false,
+            cancellation,
+            deadline,
+        }
+    }
+
+    pub fn exceeded(&self) -> bool {
+        self.exceeded
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+    }
+
+    pub fn timed_out(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+// Capacity of the buffer waiting in the pool of the current thread, if any.
+pub fn pooled_capacity() -> Option<usize> {
+    BUFFER_POOL.with(|pool| pool.borrow().as_ref().map(Vec::capacity))
+}
+
+impl Drop for OutputBuffer {
+    fn drop(&mut self) {
+        if self.buffer.capacity() > MAX_POOLED_CAPACITY {
+            return;
+        }
+
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.clear();
+        let _ = BUFFER_POOL.try_with(|pool| *pool.borrow_mut() = Some(buffer));
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Other, "render cancelled"));
+        }
+
+        if self.timed_out() {
+            return Err(io::Error::new(io::ErrorKind::Other, "render timed out"));
+        }
+
+        if let Some(limit) = self.limit {
+            if self.buffer.len() + buf.len() > limit {
+                self.exceeded = true;
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "output exceeded max_output_bytes",
+                ));
+            }
+        }
+
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::thread;
+
+type Task = Box<dyn FnOnce() + Send>;
+
+// Threads shared by the async renders, one per available core and started on
+// first use, so a burst of requests is queued instead of starting a thread
+// for each of them.
+static POOL: OnceLock<Mutex<Sender<Task>>> = OnceLock::new();
+
+pub fn spawn(task: impl FnOnce() + Send + 'static) {
+    POOL.get_or_init(start)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .send(Box::new(task))
+        .expect("render pool stopped");
+}
+
+fn start() -> Mutex<Sender<Task>> {
+    let (sender, receiver) = mpsc::channel();
+    let receiver = Arc::new(Mutex::new(receiver));
+    let size = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+
+    for index in 0..size {
+        let receiver = receiver.clone();
+
+        thread::Builder::new()
+            .name(format!("mdex-render-{index}"))
+            .spawn(move || work(&receiver))
+            .expect("failed to start render thread");
+    }
+
+    Mutex::new(sender)
+}
+
+// Tasks report their own panics, this only keeps the thread taking tasks.
+fn work(receiver: &Mutex<Receiver<Task>>) {
+    loop {
+        let task = receiver
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .recv();
+
+        match task {
+            Ok(task) => {
+                let _ = panic::catch_unwind(AssertUnwindSafe(task));
+            }
+            Err(_) => return,
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use crate::raw_html::new_node;
+use crate::types::options::{ExHeadingsAs, ExInputLimitsOptions, ExOptions, ExPreset};
+use ammonia::{Builder, UrlRelative};
+use comrak::nodes::{AstNode, NodeCode, NodeValue};
+use comrak::Arena;
+use std::collections::{HashMap, HashSet};
+
+const COMMENTS_MAX_INPUT_BYTES: usize = 10_000;
+
+const HARDENED_MAX_INPUT_BYTES: usize = 100_000;
+const HARDENED_MAX_OUTPUT_BYTES: usize = 1_000_000;
+const HARDENED_MAX_SPANS_PER_BLOCK: usize = 10_000;
+const HARDENED_TIMEOUT_MS: u64 = 1_000;
+const HARDENED_INPUT_LIMITS: ExInputLimitsOptions = ExInputLimitsOptions {
+    max_nesting_depth: 32,
+    max_delimiter_runs: 1_000,
+    max_link_definitions: 1_000,
+    max_table_cells: 10_000,
+};
+
+// Presets override the extension, parse and render options so they always
+// render the same way, and only fill the features left unset.
+pub fn configure(preset: &ExPreset, options: &mut ExOptions) {
+    match preset {
+        ExPreset::Comments => {
+            options.extension.autolink = true;
+            options.extension.shortcodes = true;
+            options.extension.table = false;
+            options.extension.tasklist = false;
+            options.extension.header_ids = None;
+            options.extension.footnotes = false;
+            options.extension.description_lists = false;
+            options.extension.front_matter_delimiter = None;
+            options.render.unsafe_ = false;
+            options.render.escape = true;
+            options.features.syntax_highlight_theme = None;
+            options.features.description_list = None;
+            options.features.heading_ids = None;
+            options.features.heading_template = None;
+            options.features.number_headings = None;
+            options.features.video_embeds = None;
+            options.features.headings_as = ExHeadingsAs::Heading;
+
+            let features = &mut options.features;
+            features
+                .max_input_bytes
+                .get_or_insert(COMMENTS_MAX_INPUT_BYTES);
+            features
+                .input_limits
+                .get_or_insert_with(ExInputLimitsOptions::default);
+        }
+        ExPreset::Feed => {
+            options.extension.tasklist = false;
+            options.extension.header_ids = None;
+            options.extension.footnotes = false;
+            // raw html is kept, the sanitizer removes what feed readers reject
+            options.render.unsafe_ = true;
+            options.render.escape = false;
+            options.features.heading_ids = None;
+            options.features.heading_template = None;
+            options.features.toc = None;
+            options.features.video_embeds = None;
+            options.features.headings_as = ExHeadingsAs::Heading;
+        }
+        ExPreset::Hardened => {
+            options.render.unsafe_ = false;
+            options.render.escape = true;
+
+            let features = &mut options.features;
+            features
+                .max_input_bytes
+                .get_or_insert(HARDENED_MAX_INPUT_BYTES);
+            features
+                .max_output_bytes
+                .get_or_insert(HARDENED_MAX_OUTPUT_BYTES);
+            features
+                .max_spans_per_block
+                .get_or_insert(HARDENED_MAX_SPANS_PER_BLOCK);
+            features.timeout.get_or_insert(HARDENED_TIMEOUT_MS);
+            features.input_limits.get_or_insert(HARDENED_INPUT_LIMITS);
+        }
+    }
+}
+
+pub fn transform<'a>(preset: &ExPreset, arena: &'a Arena<AstNode<'a>>, root: &'a AstNode<'a>) {
+    match preset {
+        ExPreset::Comments => {
+            flatten_blocks(arena, root);
+            remove_images(root);
+        }
+        ExPreset::Feed | ExPreset::Hardened => (),
+    }
+}
+
+pub fn sanitizer(preset: &ExPreset) -> Option<Builder<'static>> {
+    match preset {
+        ExPreset::Comments => {
+            let mut builder = Builder::default();
+            builder
+                .tags(HashSet::from([
+                    "a", "br", "code", "del", "em", "p", "strong",
+                ]))
+                .generic_attributes(HashSet::new())
+                .tag_attributes(HashMap::from([("a", HashSet::from(["href"]))]))
+                .url_schemes(HashSet::from(["http", "https", "mailto"]))
+                .link_rel(Some("nofollow noopener noreferrer"));
+            Some(builder)
+        }
+        ExPreset::Feed => {
+            // ammonia's defaults already remove scripts, iframes, forms and
+            // ids, the highlighted code keeps its inline styles. Relative urls
+            // are removed unless `base_url` is set, they don't resolve in feeds.
+            let mut builder = Builder::default();
+            builder
+                .add_tag_attributes("pre", ["style"])
+                .add_tag_attributes("span", ["style"])
+                .url_relative(UrlRelative::Deny);
+            Some(builder)
+        }
+        // raw html is already escaped, the sanitizer also covers the markup
+        // added by other features
+        ExPreset::Hardened => Some(Builder::default()),
+    }
+}
+
+// Turns the document into a sequence of paragraphs: containers are replaced
+// by their children, headings become paragraphs, code blocks and raw html
+// become inline code and text.
+fn flatten_blocks<'a>(arena: &'a Arena<AstNode<'a>>, root: &'a AstNode<'a>) {
+    let blocks: Vec<_> = root.descendants().skip(1).collect();
+
+    for node in blocks {
+        let inline = match node.data.borrow().value {
+            NodeValue::BlockQuote | NodeValue::List(_) | NodeValue::Item(_) => {
+                for child in node.children().collect::<Vec<_>>() {
+                    node.insert_before(child);
+                }
+
+                node.detach();
+                continue;
+            }
+            NodeValue::ThematicBreak => {
+                node.detach();
+                continue;
+            }
+            NodeValue::Heading(_) => None,
+            NodeValue::CodeBlock(ref block) => Some(NodeValue::Code(NodeCode {
+                num_backticks: 1,
+                literal: block.literal.trim_end_matches('\n').to_string(),
+            })),
+            NodeValue::HtmlBlock(ref block) => Some(NodeValue::Text(
+                block.literal.trim_end_matches('\n').to_string(),
+            )),
+            _ => continue,
+        };
+
+        node.data.borrow_mut().value = NodeValue::Paragraph;
+
+        if let Some(inline) = inline {
+            node.append(new_node(arena, inline));
+        }
+    }
+}
+
+// Images render as their alt text.
+fn remove_images<'a>(root: &'a AstNode<'a>) {
+    for node in root.descendants() {
+        let mut ast = node.data.borrow_mut();
+
+        if let NodeValue::Image(_) = ast.value {
+            ast.value = NodeValue::Document;
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use comrak::arena_tree::Node;
+use comrak::nodes::{Ast, AstNode, LineColumn, NodeHtmlBlock, NodeValue};
+use comrak::{Arena, ComrakOptions};
+use std::cell::RefCell;
+
+const TAGFILTER: [&str; 9] = [
+    "title",
+    "textarea",
+    "style",
+    "xmp",
+    "iframe",
+    "noembed",
+    "noframes",
+    "script",
+    "plaintext",
+];
+
+// Passes that emit their own markup insert HtmlBlock/HtmlInline nodes, which
+// comrak would omit or escape unless `unsafe_` is enabled. Before injecting
+// anything, raw html written by the user is replaced by exactly what comrak
+// would have rendered for it, and the formatter is then allowed to output raw
+// html as-is. Must be called before the first node is injected.
+pub fn allow_injected_html<'a>(root: &'a AstNode<'a>, options: &mut ComrakOptions) {
+    let escape = options.render.escape;
+    let unsafe_ = options.render.unsafe_;
+    let tagfilter = options.extension.tagfilter;
+
+    if unsafe_ && !escape && !tagfilter {
+        return;
+    }
+
+    for node in root.descendants() {
+        let mut ast = node.data.borrow_mut();
+
+        let literal = match ast.value {
+            NodeValue::HtmlBlock(ref mut block) => &mut block.literal,
+            NodeValue::HtmlInline(ref mut literal) => literal,
+            // comrak only checks urls when `unsafe_` is disabled
+            NodeValue::Link(ref mut link) | NodeValue::Image(ref mut link) => {
+                if !unsafe_ && is_dangerous_url(&link.url) {
+                    link.url.clear();
+                }
+
+                continue;
+            }
+            _ => continue,
+        };
+
+        *literal = if escape {
+            escape_html(literal)
+        } else if !unsafe_ {
+            "<!-- raw HTML omitted -->".to_string()
+        } else {
+            filter_tags(literal)
+        };
+    }
+
+    options.render.unsafe_ = true;
+    options.render.escape = false;
+    options.extension.tagfilter = false;
+}
+
+pub fn html_block<'a>(arena: &'a Arena<AstNode<'a>>, literal: String) -> &'a AstNode<'a> {
+    new_node(
+        arena,
+        NodeValue::HtmlBlock(NodeHtmlBlock {
+            block_type: 0,
+            literal,
+        }),
+    )
+}
+
+pub fn html_inline<'a>(arena: &'a Arena<AstNode<'a>>, literal: String) -> &'a AstNode<'a> {
+    new_node(arena, NodeValue::HtmlInline(literal))
+}
+
+pub fn new_node<'a>(arena: &'a Arena<AstNode<'a>>, value: NodeValue) -> &'a AstNode<'a> {
+    arena.alloc(Node::new(RefCell::new(Ast::new(
+        value,
+        LineColumn { line: 0, column: 0 },
+    ))))
+}
+
+// Same escaping as comrak's html formatter, also safe for attribute values.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("&quot;"),
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+// Same check comrak applies to link and image urls when `unsafe_` is
+// disabled: no script or file urls, and only data urls of common images.
+pub fn is_dangerous_url(url: &str) -> bool {
+    let lowercase = url.get(..15).unwrap_or(url).to_ascii_lowercase();
+
+    ["javascript:", "vbscript:", "file:", "data:"]
+        .iter()
+        .any(|scheme| lowercase.starts_with(scheme))
+        && ![
+            "data:image/png",
+            "data:image/gif",
+            "data:image/jpeg",
+            "data:image/webp",
+        ]
+        .iter()
+        .any(|image| lowercase.starts_with(image))
+}
+
+// Same escaping comrak applies to urls in `href` and `src` attributes.
+pub fn escape_href(url: &str) -> String {
+    let mut escaped = String::with_capacity(url.len());
+
+    for byte in url.bytes() {
+        match byte {
+            b'&' => escaped.push_str("&amp;"),
+            b'\'' => escaped.push_str("&#x27;"),
+            b'-' | b'_' | b'.' | b'+' | b'!' | b'*' | b'(' | b')' | b',' | b'%' | b'#' | b'@'
+            | b'?' | b'=' | b';' | b':' | b'/' | b'$' | b'~' => escaped.push(byte as char),
+            byte if byte.is_ascii_alphanumeric() => escaped.push(byte as char),
+            byte => escaped.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    escaped
+}
+
+// Mirrors the GFM tagfilter extension, which comrak only applies when it
+// renders raw html itself.
+fn filter_tags(literal: &str) -> String {
+    let mut filtered = String::with_capacity(literal.len());
+    let mut rest = literal;
+
+    while let Some(index) = rest.find('<') {
+        filtered.push_str(&rest[..index]);
+        let tag = &rest[index + 1..];
+        let name = tag.strip_prefix('/').unwrap_or(tag);
+
+        let filtered_tag = TAGFILTER.iter().any(|filtered_name| {
+            name.get(..filtered_name.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(filtered_name))
+                && matches!(
+                    name.as_bytes().get(filtered_name.len()),
+                    Some(b' ' | b'\t' | b'\n' | b'\r' | b'\x0c' | b'/' | b'>') | None
+                )
+        });
+
+        filtered.push_str(if filtered_tag { "&lt;" } else { "<" });
+        rest = tag;
+    }
+
+    filtered.push_str(rest);
+    filtered
+}
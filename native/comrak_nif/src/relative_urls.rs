@@ -0,0 +1,71 @@
+use crate::render::RenderError;
+use crate::replacements::{self, Pattern};
+use crate::types::options::ExReplacement;
+use ammonia::{Url, UrlRelativeEvaluate};
+use regex::Regex;
+use std::borrow::Cow;
+
+enum Rule {
+    Prefix(String, String),
+    Regex(Regex, String),
+}
+
+// What happens to relative urls no rule matches, the policy the sanitizer
+// would use without rules.
+pub enum Fallback {
+    Keep,
+    Remove,
+    Resolve(Url),
+}
+
+// Rewrites relative urls with the first rule that matches them. String
+// patterns replace a prefix of the url, regexes replace their first match.
+pub struct RelativeUrls {
+    rules: Vec<Rule>,
+    fallback: Fallback,
+}
+
+impl RelativeUrls {
+    pub fn new(rules: &[ExReplacement], fallback: Fallback) -> Result<Self, RenderError> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let replacement = rule.replacement.clone();
+
+                match replacements::compile(rule, "relative_urls")? {
+                    Pattern::String(prefix) => Ok(Rule::Prefix(prefix.to_string(), replacement)),
+                    Pattern::Regex(regex) => Ok(Rule::Regex(regex, replacement)),
+                }
+            })
+            .collect::<Result<_, RenderError>>()?;
+
+        Ok(RelativeUrls { rules, fallback })
+    }
+}
+
+impl UrlRelativeEvaluate for RelativeUrls {
+    fn evaluate<'a>(&self, url: &'a str) -> Option<Cow<'a, str>> {
+        for rule in &self.rules {
+            match rule {
+                Rule::Prefix(prefix, replacement) => {
+                    if let Some(rest) = url.strip_prefix(prefix.as_str()) {
+                        return Some(Cow::Owned(format!("{}{}", replacement, rest)));
+                    }
+                }
+                Rule::Regex(regex, replacement) if regex.is_match(url) => {
+                    return Some(regex.replace(url, replacement.as_str()));
+                }
+                Rule::Regex(..) => (),
+            }
+        }
+
+        match self.fallback {
+            Fallback::Keep => Some(Cow::Borrowed(url)),
+            Fallback::Remove => None,
+            Fallback::Resolve(ref base_url) => base_url
+                .join(url)
+                .ok()
+                .map(|url| Cow::Owned(url.to_string())),
+        }
+    }
+}
@@ -0,0 +1,569 @@
+use crate::abbreviations::{self, Abbreviation};
+use crate::atoms;
+use crate::audit;
+use crate::broken_links;
+use crate::data_urls;
+use crate::description_list;
+use crate::element_ids;
+use crate::entities;
+use crate::footnotes;
+use crate::heading_adapter::TemplateHeadingAdapter;
+use crate::headings;
+use crate::inkjet_adapter::InkjetAdapter;
+use crate::input_limits;
+use crate::internal_links;
+use crate::job::CancellationToken;
+use crate::language;
+use crate::link_titles;
+use crate::metrics::RenderMetrics;
+use crate::omit;
+use crate::output::OutputBuffer;
+use crate::presets;
+use crate::raw_html::escape_html;
+use crate::relative_urls::{Fallback, RelativeUrls};
+use crate::replacements;
+use crate::responsive_images;
+use crate::sanitizer;
+use crate::short_links;
+use crate::theme;
+use crate::toc;
+use crate::types::metadata::ExRenderMetadata;
+use crate::types::options::{
+    ExEntityEncoding, ExFeaturesOptions, ExHeadingIdsOptions, ExHeadingsAs, ExOptions, ExPreset,
+};
+use crate::typography;
+use crate::urls;
+use crate::video_embeds;
+use crate::warnings;
+use crate::wrapper;
+use ammonia::Url;
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{
+    format_html_with_plugins, Arena, ComrakExtensionOptions, ComrakOptions, ComrakParseOptions,
+    ComrakPlugins, ComrakRenderOptions,
+};
+use rustler::{Encoder, Env, Term};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub enum RenderError {
+    InputTooLarge,
+    OutputTooLarge,
+    PathologicalInput,
+    DuplicateHeadingId(String),
+    UnknownTheme(String, Vec<String>),
+    Cancelled,
+    Timeout,
+    Other(String),
+}
+
+impl Encoder for RenderError {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        match self {
+            RenderError::InputTooLarge => (atoms::error(), atoms::input_too_large()).encode(env),
+            RenderError::OutputTooLarge => (atoms::error(), atoms::output_too_large()).encode(env),
+            RenderError::PathologicalInput => {
+                (atoms::error(), atoms::pathological_input()).encode(env)
+            }
+            RenderError::DuplicateHeadingId(id) => {
+                (atoms::error(), (atoms::duplicate_heading_id(), id)).encode(env)
+            }
+            RenderError::UnknownTheme(name, suggestions) => {
+                (atoms::error(), (atoms::unknown_theme(), name, suggestions)).encode(env)
+            }
+            RenderError::Cancelled => (atoms::error(), atoms::cancelled()).encode(env),
+            RenderError::Timeout => (atoms::error(), atoms::timeout()).encode(env),
+            RenderError::Other(reason) => (atoms::error(), reason).encode(env),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Html {
+    output: OutputBuffer,
+    // set when the formatter output is rewritten after rendering
+    rewritten: Option<String>,
+    pub metadata: ExRenderMetadata,
+}
+
+impl Html {
+    pub fn as_bytes(&self) -> &[u8] {
+        match self.rewritten {
+            Some(ref rewritten) => rewritten.as_bytes(),
+            None => self.output.as_bytes(),
+        }
+    }
+}
+
+pub fn render_html(
+    md: &str,
+    options: ExOptions,
+    cancellation: Option<CancellationToken>,
+) -> Result<Html, RenderError> {
+    render_html_with_ids(md, options, cancellation, &mut HashSet::new())
+}
+
+// Renders each `(id, markdown, options)` document wrapped in a div with that
+// id. Heading ids are generated against the ids taken by the previous
+// documents and the wrappers, so anchors stay unique across the payload.
+pub fn render_many(documents: Vec<(String, &str, ExOptions)>) -> Result<Vec<u8>, RenderError> {
+    let mut ids: HashSet<String> = documents.iter().map(|(id, _, _)| id.clone()).collect();
+    let mut output = Vec::new();
+
+    for (id, md, mut options) in documents {
+        if options.extension.header_ids.is_some() && options.features.heading_ids.is_none() {
+            options.features.heading_ids = Some(ExHeadingIdsOptions::default());
+        }
+
+        let html = render_html_with_ids(md, options, None, &mut ids)?;
+
+        output.extend_from_slice(
+            format!(
+                "<div class=\"mdex-document\" id=\"{}\">\n",
+                escape_html(&id)
+            )
+            .as_bytes(),
+        );
+        output.extend_from_slice(html.as_bytes());
+        output.extend_from_slice(b"</div>\n");
+    }
+
+    Ok(output)
+}
+
+// The preset, comrak options and features after applying the preset and the
+// defaults implied by other features, shared with `fingerprint` so both see
+// the same configuration.
+pub fn resolve_options(
+    mut options: ExOptions,
+) -> Result<(Option<ExPreset>, ComrakOptions, ExFeaturesOptions), RenderError> {
+    let preset = options.preset.take();
+    if let Some(ref preset) = preset {
+        presets::configure(preset, &mut options);
+    }
+
+    let mut comrak_options = ComrakOptions {
+        extension: ComrakExtensionOptions::from(options.extension),
+        parse: ComrakParseOptions::from(options.parse),
+        render: ComrakRenderOptions::from(options.render),
+    };
+    let mut features = options.features;
+
+    if let Some(ref base_url) = features.base_url {
+        Url::parse(base_url)
+            .map_err(|err| RenderError::Other(format!("invalid base_url: {}", err)))?;
+    }
+
+    if !features.relative_urls.is_empty() {
+        RelativeUrls::new(&features.relative_urls, Fallback::Keep)?;
+    }
+
+    // `id_prefix` is the single prefix of heading ids, a different
+    // `header_ids` prefix would leave the anchors and the links to them apart
+    if let (Some(id_prefix), Some(header_prefix)) =
+        (&features.id_prefix, &comrak_options.extension.header_ids)
+    {
+        if !header_prefix.is_empty() && header_prefix != id_prefix {
+            return Err(RenderError::Other(format!(
+                "id_prefix {:?} conflicts with the header_ids prefix {:?}, set only one of them",
+                id_prefix, header_prefix
+            )));
+        }
+    }
+
+    // the table of contents links to the heading anchors, comrak can't add
+    // the anchors of `header_ids` to divs, and the sanitizer only keeps the
+    // anchors generated here when they're namespaced by `id_prefix`
+    let generated_anchors = comrak_options.extension.header_ids.is_some()
+        && (features.headings_as == ExHeadingsAs::Div
+            || features.sanitize && features.id_prefix.is_some());
+
+    if let Some(ref template) = features.heading_template {
+        TemplateHeadingAdapter::new(template)?;
+    }
+
+    if (features.toc.is_some() || generated_anchors || features.heading_template.is_some())
+        && features.heading_ids.is_none()
+    {
+        features.heading_ids = Some(ExHeadingIdsOptions::default());
+    }
+
+    // in front of every heading id, replacing the `header_ids` prefix checked
+    // above
+    if let Some(ref id_prefix) = features.id_prefix {
+        match (
+            &mut comrak_options.extension.header_ids,
+            &mut features.heading_ids,
+        ) {
+            (Some(prefix), _) => *prefix = id_prefix.clone(),
+            (None, Some(ExHeadingIdsOptions { prefix, .. })) => prefix.insert_str(0, id_prefix),
+            (None, None) => (),
+        }
+    }
+
+    Ok((preset, comrak_options, features))
+}
+
+pub fn check_input(md: &str, features: &ExFeaturesOptions) -> Result<(), RenderError> {
+    if let Some(max_input_bytes) = features.max_input_bytes {
+        if md.len() > max_input_bytes {
+            return Err(RenderError::InputTooLarge);
+        }
+    }
+
+    if let Some(ref limits) = features.input_limits {
+        if input_limits::exceeds_limits(md, limits) {
+            return Err(RenderError::PathologicalInput);
+        }
+    }
+
+    Ok(())
+}
+
+// Parses the markdown and applies the passes that remove content, returning
+// the root and the abbreviation definitions taken out of the markdown.
+// Shared with `headings::parse_headings` so both see the same headings.
+pub fn parse<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    md: &str,
+    preset: Option<&ExPreset>,
+    features: &ExFeaturesOptions,
+    comrak_options: &ComrakOptions,
+) -> (&'a AstNode<'a>, Vec<Abbreviation>) {
+    let (source, abbreviations) = match features.abbreviations {
+        true => abbreviations::extract(md),
+        false => (Cow::Borrowed(md), Vec::new()),
+    };
+    let root = broken_links::parse_document(
+        arena,
+        &source,
+        comrak_options,
+        features.broken_link_resolver.as_ref(),
+    );
+
+    if let Some(preset) = preset {
+        presets::transform(preset, arena, root);
+    }
+
+    if !features.omit.is_empty() {
+        omit::apply(root, &features.omit);
+    }
+
+    (root, abbreviations)
+}
+
+// The passes rewriting the links and text of the document, which run before
+// the heading ids are generated from the text.
+pub fn transform<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    root: &'a AstNode<'a>,
+    features: &ExFeaturesOptions,
+    abbreviations: &[Abbreviation],
+    comrak_options: &mut ComrakOptions,
+) -> Result<(), RenderError> {
+    if let Some(ref document_dir) = features.document_dir {
+        urls::resolve_document_dir(root, document_dir);
+    }
+
+    if let Some(ref options) = features.short_links {
+        short_links::apply(root, options);
+    }
+
+    if let Some(ref options) = features.data_images {
+        data_urls::filter_images(root, options);
+    }
+
+    if let Some(ref options) = features.video_embeds {
+        video_embeds::apply(arena, root, options, comrak_options);
+    }
+
+    if !features.replacements.is_empty() {
+        replacements::apply(root, &features.replacements)?;
+    }
+
+    if let Some(ref options) = features.typography {
+        typography::apply(root, options);
+    }
+
+    if !abbreviations.is_empty() {
+        abbreviations::apply(arena, root, abbreviations, comrak_options);
+    }
+
+    if let Some(ref options) = features.description_list {
+        description_list::apply(arena, root, options, comrak_options);
+    }
+
+    if let Some(ref options) = features.number_headings {
+        headings::number(arena, root, options, comrak_options);
+    }
+
+    Ok(())
+}
+
+fn render_html_with_ids(
+    md: &str,
+    options: ExOptions,
+    cancellation: Option<CancellationToken>,
+    ids: &mut HashSet<String>,
+) -> Result<Html, RenderError> {
+    let (preset, mut comrak_options, features) = resolve_options(options)?;
+    let deadline = deadline(&features);
+
+    check_input(md, &features)?;
+    // jobs cancelled while queued don't start
+    checkpoint(cancellation.as_ref(), deadline)?;
+
+    let inkjet_adapter = match features.syntax_highlight_theme {
+        Some(ref theme) => Some(InkjetAdapter::new(
+            theme::resolve(theme)?,
+            features.code_block_attributes.as_ref(),
+            features.max_spans_per_block,
+            features.diff_words,
+        )),
+        None => None,
+    };
+    let mut plugins = ComrakPlugins::default();
+    if let Some(ref inkjet_adapter) = inkjet_adapter {
+        plugins.render.codefence_syntax_highlighter = Some(inkjet_adapter);
+    }
+
+    let arena = Arena::new();
+    let (root, abbreviations) = parse(&arena, md, preset.as_ref(), &features, &comrak_options);
+    checkpoint(cancellation.as_ref(), deadline)?;
+
+    let mut warnings = warnings::collect(root, &comrak_options, &features);
+    let detect_lang = features
+        .lang
+        .as_ref()
+        .is_some_and(|options| options.tag.is_none());
+    let language = match features.detect_language || detect_lang {
+        true => language::detect(root),
+        false => None,
+    };
+    // a wrong `lang` is worse than none, so unreliable detections are ignored
+    let lang = match features.lang {
+        Some(ref options) => options.tag.clone().or_else(|| {
+            language
+                .as_ref()
+                .filter(|language| language.reliable)
+                .map(|language| language.lang.clone())
+        }),
+        None => None,
+    };
+    let language = language.filter(|_| features.detect_language);
+    let toc_markers = match features.toc {
+        Some(_) => toc::markers(root),
+        None => Vec::new(),
+    };
+
+    transform(&arena, root, &features, &abbreviations, &mut comrak_options)?;
+
+    let heading_template = match features.heading_template {
+        Some(ref template) => Some(TemplateHeadingAdapter::new(template)?),
+        None => None,
+    };
+    let headings = match features.heading_ids {
+        Some(ref options) => headings::apply(
+            &arena,
+            root,
+            options,
+            &features.headings_as,
+            heading_template.as_ref(),
+            ids,
+            &mut comrak_options,
+        )?,
+        None => Vec::new(),
+    };
+
+    checkpoint(cancellation.as_ref(), deadline)?;
+
+    if features.headings_as == ExHeadingsAs::Div {
+        headings::as_divs(&arena, root, &mut comrak_options);
+    }
+
+    if let Some(ref options) = features.toc {
+        toc::apply(
+            &arena,
+            root,
+            toc_markers,
+            options,
+            &headings,
+            &mut comrak_options,
+        );
+    }
+
+    if let Some(ref options) = features.responsive_images {
+        responsive_images::apply(&arena, root, options, &mut comrak_options);
+    }
+
+    if features.link_titles {
+        link_titles::apply(&arena, root, &mut comrak_options);
+    }
+
+    if let Some(ref options) = features.internal_links {
+        internal_links::apply(&arena, root, options, &mut comrak_options);
+    }
+
+    if features
+        .lang
+        .as_ref()
+        .is_some_and(|options| options.per_block)
+    {
+        language::mark_paragraphs(&arena, root, lang.as_deref(), &mut comrak_options);
+    }
+
+    checkpoint(cancellation.as_ref(), deadline)?;
+
+    let mut output = OutputBuffer::new(features.max_output_bytes, cancellation.clone(), deadline);
+    format_html(root, &comrak_options, &mut output, &plugins)?;
+
+    if let (Some(adapter), Some(max_spans)) = (&inkjet_adapter, features.max_spans_per_block) {
+        let degraded = adapter.degraded();
+
+        if !degraded.is_empty() {
+            // the formatter highlights the code blocks in document order
+            let code_blocks: Vec<_> = root
+                .descendants()
+                .filter(|node| matches!(node.data.borrow().value, NodeValue::CodeBlock(_)))
+                .collect();
+
+            for (block, spans) in degraded {
+                if let Some(node) = code_blocks.get(block) {
+                    warnings.push(warnings::too_many_spans(node, spans, max_spans));
+                }
+            }
+        }
+    }
+
+    let mut html = Html {
+        output,
+        rewritten: None,
+        metadata: ExRenderMetadata {
+            warnings,
+            language,
+            ..ExRenderMetadata::default()
+        },
+    };
+
+    checkpoint(cancellation.as_ref(), deadline)?;
+    let sanitizer = sanitizer::builder(preset.as_ref(), &features);
+
+    if let Some(ref id_prefix) = features.id_prefix {
+        if comrak_options.extension.footnotes {
+            let prefixed = footnotes::prefix_ids(as_str(html.as_bytes())?, id_prefix);
+            html.rewritten = Some(prefixed);
+        }
+    }
+
+    if features.audit {
+        let (audited, violations) = audit::audit(as_str(html.as_bytes())?);
+        if audited.is_some() {
+            html.rewritten = audited;
+        }
+        html.metadata.violations = violations;
+    }
+
+    if let Some(ref sanitizer) = sanitizer {
+        let input = as_str(html.as_bytes())?;
+        let sanitized = sanitizer.clean(input).to_string();
+        html.metadata.was_sanitized = sanitized != input;
+        html.rewritten = Some(sanitized);
+    }
+
+    if let Some(wrapped) = wrapper::wrap(as_str(html.as_bytes())?, &features, lang.as_deref())? {
+        html.rewritten = Some(wrapped);
+    }
+
+    // before entity encoding, which would encode the ids too
+    if features.collect_ids {
+        html.metadata.ids = Some(element_ids::collect(as_str(html.as_bytes())?));
+    }
+
+    if features.entity_encoding != ExEntityEncoding::Utf8 {
+        let encoded = entities::encode(as_str(html.as_bytes())?, &features.entity_encoding);
+        html.rewritten = Some(encoded);
+    }
+
+    if let Some(max_output_bytes) = features.max_output_bytes {
+        if html.as_bytes().len() > max_output_bytes {
+            return Err(RenderError::OutputTooLarge);
+        }
+    }
+
+    if let Some(ref metrics) = features.metrics {
+        metrics.0.record(RenderMetrics {
+            bytes_in: md.len(),
+            bytes_out: html.as_bytes().len(),
+            code_blocks_highlighted: inkjet_adapter.as_ref().map_or(0, |a| a.highlighted()),
+            sanitized: html.metadata.was_sanitized,
+        });
+    }
+
+    Ok(html)
+}
+
+fn format_html<'a>(
+    root: &'a AstNode<'a>,
+    options: &ComrakOptions,
+    output: &mut OutputBuffer,
+    plugins: &ComrakPlugins,
+) -> Result<(), RenderError> {
+    format_html_with_plugins(root, options, output, plugins)
+        .map_err(|err| output_error(output, err))
+}
+
+// Called between the passes, since comrak's parser and the AST passes can't be
+// stopped midway like the writes to the output buffer.
+fn checkpoint(
+    cancellation: Option<&CancellationToken>,
+    deadline: Option<Instant>,
+) -> Result<(), RenderError> {
+    if cancellation.is_some_and(|token| token.is_cancelled()) {
+        return Err(RenderError::Cancelled);
+    }
+
+    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        return Err(RenderError::Timeout);
+    }
+
+    Ok(())
+}
+
+// Turns a panic of `render` into an error, for renders sent as messages, whose
+// callers would otherwise wait for a message that never comes.
+pub fn catch_panic<T>(render: impl FnOnce() -> Result<T, RenderError>) -> Result<T, RenderError> {
+    panic::catch_unwind(AssertUnwindSafe(render)).unwrap_or_else(|panic| {
+        let reason = match panic.downcast_ref::<&str>() {
+            Some(reason) => reason.to_string(),
+            None => panic.downcast_ref::<String>().cloned().unwrap_or_default(),
+        };
+
+        Err(RenderError::Other(format!("render panicked: {}", reason)))
+    })
+}
+
+// The error of a formatter that stopped writing to `output`.
+pub fn output_error(output: &OutputBuffer, err: std::io::Error) -> RenderError {
+    if output.exceeded() {
+        RenderError::OutputTooLarge
+    } else if output.cancelled() {
+        RenderError::Cancelled
+    } else if output.timed_out() {
+        RenderError::Timeout
+    } else {
+        RenderError::Other(err.to_string())
+    }
+}
+
+pub fn deadline(features: &ExFeaturesOptions) -> Option<Instant> {
+    features
+        .timeout
+        .and_then(|timeout| Instant::now().checked_add(Duration::from_millis(timeout)))
+}
+
+fn as_str(bytes: &[u8]) -> Result<&str, RenderError> {
+    std::str::from_utf8(bytes).map_err(|err| RenderError::Other(err.to_string()))
+}
@@ -0,0 +1,76 @@
+use crate::render::RenderError;
+use crate::types::options::{ExPatternKind, ExReplacement};
+use comrak::nodes::{AstNode, NodeValue};
+use regex::{Regex, RegexBuilder};
+
+// Compiled regexes are limited in size, the regex crate already matches in
+// linear time.
+const MAX_REGEX_SIZE: usize = 1 << 20;
+
+pub enum Pattern<'r> {
+    String(&'r str),
+    Regex(Regex),
+}
+
+// Applies the replacements in order to the text of the document, leaving
+// code, raw html and the text of links and images untouched.
+pub fn apply<'a>(root: &'a AstNode<'a>, replacements: &[ExReplacement]) -> Result<(), RenderError> {
+    let patterns = replacements
+        .iter()
+        .map(|replacement| {
+            compile(replacement, "replacement").map(|pattern| (pattern, &replacement.replacement))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for node in root.descendants() {
+        if !matches!(node.data.borrow().value, NodeValue::Text(_)) || in_link(node) {
+            continue;
+        }
+
+        let mut ast = node.data.borrow_mut();
+
+        let text = match ast.value {
+            NodeValue::Text(ref mut text) => text,
+            _ => continue,
+        };
+
+        for (pattern, replacement) in &patterns {
+            let replaced = match pattern {
+                Pattern::String(pattern) => text.replace(pattern, replacement),
+                Pattern::Regex(regex) => regex.replace_all(text, replacement.as_str()).into_owned(),
+            };
+
+            *text = replaced;
+        }
+    }
+
+    Ok(())
+}
+
+// `option` names the option in the error messages.
+pub fn compile<'r>(
+    replacement: &'r ExReplacement,
+    option: &str,
+) -> Result<Pattern<'r>, RenderError> {
+    match replacement.kind {
+        ExPatternKind::String if replacement.pattern.is_empty() => Err(RenderError::Other(
+            format!("invalid {} pattern: empty string", option),
+        )),
+        ExPatternKind::String => Ok(Pattern::String(&replacement.pattern)),
+        ExPatternKind::Regex => RegexBuilder::new(&replacement.pattern)
+            .size_limit(MAX_REGEX_SIZE)
+            .dfa_size_limit(MAX_REGEX_SIZE)
+            .build()
+            .map(Pattern::Regex)
+            .map_err(|err| RenderError::Other(format!("invalid {} pattern: {}", option, err))),
+    }
+}
+
+fn in_link<'a>(node: &'a AstNode<'a>) -> bool {
+    node.ancestors().any(|ancestor| {
+        matches!(
+            ancestor.data.borrow().value,
+            NodeValue::Link(_) | NodeValue::Image(_)
+        )
+    })
+}
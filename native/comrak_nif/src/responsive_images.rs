@@ -0,0 +1,72 @@
+use crate::headings::collect_text;
+use crate::raw_html::{allow_injected_html, escape_href, escape_html, html_inline};
+use crate::types::options::ExResponsiveImagesOptions;
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{Arena, ComrakOptions};
+
+// Renders images with a `srcset` built from the url template, replacing each
+// image by the same `<img>` tag comrak would write plus `srcset` and `sizes`.
+// Runs after the heading ids are generated since it drops the alt text nodes.
+pub fn apply<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    root: &'a AstNode<'a>,
+    options: &ExResponsiveImagesOptions,
+    comrak_options: &mut ComrakOptions,
+) {
+    let images: Vec<_> = root
+        .descendants()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::Image(_)))
+        .collect();
+
+    if images.is_empty() || options.widths.is_empty() {
+        return;
+    }
+
+    // also clears the urls comrak wouldn't render
+    allow_injected_html(root, comrak_options);
+
+    for node in images {
+        let (url, title) = match node.data.borrow().value {
+            NodeValue::Image(ref link) => (link.url.clone(), link.title.clone()),
+            _ => continue,
+        };
+
+        if url.is_empty() {
+            continue;
+        }
+
+        let srcset: Vec<_> = options
+            .widths
+            .iter()
+            .map(|width| {
+                let variant = options
+                    .url
+                    .replace("{url}", &url)
+                    .replace("{width}", &width.to_string());
+
+                format!("{} {}w", escape_href(&variant), width)
+            })
+            .collect();
+
+        let mut img = format!(
+            "<img src=\"{}\" srcset=\"{}\"",
+            escape_href(&url),
+            srcset.join(", ")
+        );
+
+        if let Some(ref sizes) = options.sizes {
+            img.push_str(&format!(" sizes=\"{}\"", escape_html(sizes)));
+        }
+
+        img.push_str(&format!(" alt=\"{}\"", escape_html(&collect_text(node))));
+
+        if !title.is_empty() {
+            img.push_str(&format!(" title=\"{}\"", escape_html(&title)));
+        }
+
+        img.push_str(" />");
+
+        node.insert_before(html_inline(arena, img));
+        node.detach();
+    }
+}
@@ -0,0 +1,162 @@
+use crate::broken_links;
+use crate::json_ast::JsonNode;
+use crate::render::{self, RenderError};
+use crate::types::options::ExOptions;
+use comrak::nodes::AstNode;
+use comrak::{format_commonmark, Arena};
+use serde_json::{json, Value};
+
+// Fields recording how a node was written rather than what it is, which the
+// formatter is free to change, like setext headings written as ATX headings.
+const SYNTAX_FIELDS: [&str; 4] = ["sourcepos", "setext", "fenced", "bullet_char"];
+
+#[derive(Debug, NifMap)]
+pub struct ExRoundtrip {
+    pub equivalent: bool,
+    pub markdown: String,
+    pub path: Option<Vec<usize>>,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+// Formats the document as CommonMark and parses it back with the same
+// options. When the trees differ, `path` has the child indexes from the
+// document to the first node that differs, depth first, and `expected` and
+// `actual` the types of that node in the original and in the formatted
+// document, `None` where one of them has no node.
+pub fn verify(md: &str, options: ExOptions) -> Result<ExRoundtrip, RenderError> {
+    let (_, comrak_options, features) = render::resolve_options(options)?;
+    let resolver = features.broken_link_resolver.as_ref();
+
+    render::check_input(md, &features)?;
+
+    let arena = Arena::new();
+    let root = broken_links::parse_document(&arena, md, &comrak_options, resolver);
+    let mut output = Vec::new();
+
+    format_commonmark(root, &comrak_options, &mut output)
+        .map_err(|err| RenderError::Other(err.to_string()))?;
+
+    let markdown = String::from_utf8(output).map_err(|err| RenderError::Other(err.to_string()))?;
+    let formatted = broken_links::parse_document(&arena, &markdown, &comrak_options, resolver);
+    let mut path = Vec::new();
+
+    match diverge(
+        Some(&to_value(root)?),
+        Some(&to_value(formatted)?),
+        &mut path,
+    ) {
+        Some((expected, actual)) => Ok(ExRoundtrip {
+            equivalent: false,
+            markdown,
+            path: Some(path),
+            expected,
+            actual,
+        }),
+        None => Ok(ExRoundtrip {
+            equivalent: true,
+            markdown,
+            path: None,
+            expected: None,
+            actual: None,
+        }),
+    }
+}
+
+fn to_value<'a>(node: &'a AstNode<'a>) -> Result<Value, RenderError> {
+    let mut value =
+        serde_json::to_value(JsonNode(node)).map_err(|err| RenderError::Other(err.to_string()))?;
+    normalize(&mut value);
+    Ok(value)
+}
+
+// Drops the syntax fields and merges adjacent text nodes, which the parser
+// splits at escapes and brackets, so the same text compares equal however
+// it was escaped. Soft breaks compare as spaces since `render: [width: n]`
+// reflows paragraphs.
+fn normalize(node: &mut Value) {
+    if node["type"] == "soft_break" {
+        *node = json!({"type": "text", "literal": " "});
+    }
+
+    let map = match node.as_object_mut() {
+        Some(map) => map,
+        None => return,
+    };
+
+    for field in SYNTAX_FIELDS {
+        map.remove(field);
+    }
+
+    if let Some(Value::Array(children)) = map.get_mut("children") {
+        let mut merged: Vec<Value> = Vec::with_capacity(children.len());
+
+        for mut child in children.drain(..) {
+            normalize(&mut child);
+
+            if is_text(&child) && merged.last().map_or(false, is_text) {
+                let literal = child["literal"].as_str().unwrap_or_default();
+
+                if let Some(Value::String(previous)) = merged
+                    .last_mut()
+                    .and_then(|previous| previous.get_mut("literal"))
+                {
+                    previous.push_str(literal);
+                }
+            } else {
+                merged.push(child);
+            }
+        }
+
+        *children = merged;
+    }
+}
+
+fn diverge(
+    expected: Option<&Value>,
+    actual: Option<&Value>,
+    path: &mut Vec<usize>,
+) -> Option<(Option<String>, Option<String>)> {
+    let (expected, actual) = match (expected, actual) {
+        (Some(expected), Some(actual)) if fields(expected) == fields(actual) => (expected, actual),
+        _ => return Some((node_type(expected), node_type(actual))),
+    };
+
+    let expected_children = children(expected);
+    let actual_children = children(actual);
+
+    for index in 0..expected_children.len().max(actual_children.len()) {
+        path.push(index);
+
+        if let Some(divergence) = diverge(
+            expected_children.get(index),
+            actual_children.get(index),
+            path,
+        ) {
+            return Some(divergence);
+        }
+
+        path.pop();
+    }
+
+    None
+}
+
+fn fields(node: &Value) -> Vec<(&String, &Value)> {
+    node.as_object()
+        .map(|map| map.iter().filter(|(key, _)| *key != "children").collect())
+        .unwrap_or_default()
+}
+
+fn children(node: &Value) -> &[Value] {
+    node["children"].as_array().map_or(&[], Vec::as_slice)
+}
+
+fn is_text(node: &Value) -> bool {
+    node["type"] == "text"
+}
+
+fn node_type(node: Option<&Value>) -> Option<String> {
+    node.and_then(|node| node["type"].as_str())
+        .map(str::to_string)
+}
@@ -0,0 +1,231 @@
+use crate::audit::{parse_tag, Tag};
+use ammonia::Builder;
+use std::collections::{HashMap, HashSet};
+
+// How far ahead to look for a tag in the sanitized html, skipping the
+// closing tags ammonia adds to balance the fragment.
+const LOOKAHEAD: usize = 8;
+
+#[derive(Debug, NifUnitEnum)]
+pub enum ExRemovalKind {
+    Element,
+    Attribute,
+    Url,
+    Comment,
+}
+
+#[derive(Debug, NifMap)]
+pub struct ExRemoval {
+    pub kind: ExRemovalKind,
+    pub name: String,
+    pub snippet: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, NifMap)]
+pub struct ExSanitizePreview {
+    pub html: String,
+    pub removed: Vec<ExRemoval>,
+}
+
+enum Item<'a> {
+    Tag(Tag<'a>),
+    Comment(&'a str),
+}
+
+// The attributes the policy allows, to tell attributes removed for their
+// url, like `javascript:` links or schemes the policy doesn't allow, from
+// attributes removed for their name.
+struct Allowed<'a> {
+    tag_attributes: HashMap<&'a str, HashSet<&'a str>>,
+    generic_attributes: HashSet<&'a str>,
+    generic_prefixes: Option<HashSet<&'a str>>,
+}
+
+impl<'a> Allowed<'a> {
+    fn new(sanitizer: &Builder<'a>) -> Self {
+        Allowed {
+            tag_attributes: sanitizer.clone_tag_attributes(),
+            generic_attributes: sanitizer.clone_generic_attributes(),
+            generic_prefixes: sanitizer.clone_generic_attribute_prefixes(),
+        }
+    }
+
+    fn url(&self, element: &str, attribute: &str) -> bool {
+        is_url_attribute(element, attribute)
+            && (self.generic_attributes.contains(attribute)
+                || self
+                    .tag_attributes
+                    .get(element)
+                    .is_some_and(|attributes| attributes.contains(attribute))
+                || self.generic_prefixes.as_ref().is_some_and(|prefixes| {
+                    prefixes.iter().any(|prefix| attribute.starts_with(prefix))
+                }))
+    }
+}
+
+// Sanitizes the html and lists what was removed, by lining up the tags of
+// the input with the tags of the output. Best effort: ammonia rewrites the
+// markup it keeps, so only element, attribute and comment removals are
+// reported, not changes to text or attribute values.
+pub fn preview(html: &str, sanitizer: &Builder) -> ExSanitizePreview {
+    let sanitized = sanitizer.clean(html).to_string();
+    let input = scan(html);
+    let output = scan(&sanitized);
+    let allowed = Allowed::new(sanitizer);
+    let mut removed = Vec::new();
+    let mut position = Position::new(html);
+    let mut next = 0;
+
+    for (offset, item) in &input {
+        let (line, column) = position.at(*offset);
+
+        let tag = match item {
+            Item::Comment(comment) => {
+                removed.push(ExRemoval {
+                    kind: ExRemovalKind::Comment,
+                    name: String::new(),
+                    snippet: comment.to_string(),
+                    line,
+                    column,
+                });
+                continue;
+            }
+            Item::Tag(tag) => tag,
+        };
+
+        match find(tag, &output[next..]) {
+            Some((index, kept)) => {
+                next += index + 1;
+
+                for attribute in &tag.attributes {
+                    if !kept
+                        .attributes
+                        .iter()
+                        .any(|kept| kept.name.eq_ignore_ascii_case(attribute.name))
+                    {
+                        let element = tag.name.to_ascii_lowercase();
+                        let name = attribute.name.to_ascii_lowercase();
+                        let kind = match allowed.url(&element, &name) {
+                            true => ExRemovalKind::Url,
+                            false => ExRemovalKind::Attribute,
+                        };
+
+                        removed.push(ExRemoval {
+                            kind,
+                            name,
+                            snippet: attribute.raw.to_string(),
+                            line,
+                            column,
+                        });
+                    }
+                }
+            }
+            None if !tag.closing => removed.push(ExRemoval {
+                kind: ExRemovalKind::Element,
+                name: tag.name.to_ascii_lowercase(),
+                snippet: html[*offset..*offset + tag.len].to_string(),
+                line,
+                column,
+            }),
+            None => (),
+        }
+    }
+
+    ExSanitizePreview {
+        html: sanitized,
+        removed,
+    }
+}
+
+fn find<'a, 'b>(tag: &Tag, output: &'b [(usize, Item<'a>)]) -> Option<(usize, &'b Tag<'a>)> {
+    for (index, (_, item)) in output.iter().take(LOOKAHEAD).enumerate() {
+        let kept = match item {
+            Item::Tag(kept) => kept,
+            Item::Comment(_) => continue,
+        };
+
+        if kept.closing == tag.closing && kept.name.eq_ignore_ascii_case(tag.name) {
+            return Some((index, kept));
+        }
+
+        if !kept.closing {
+            return None;
+        }
+    }
+
+    None
+}
+
+// The attributes ammonia checks as urls.
+pub fn is_url_attribute(element: &str, attribute: &str) -> bool {
+    matches!(attribute, "href" | "src")
+        || matches!(
+            (element, attribute),
+            ("form", "action")
+                | ("object", "data")
+                | ("button" | "input", "formaction")
+                | ("a", "ping")
+                | ("video", "poster")
+        )
+}
+
+fn scan(html: &str) -> Vec<(usize, Item<'_>)> {
+    let mut items = Vec::new();
+    let mut offset = 0;
+
+    while let Some(start) = html[offset..].find('<') {
+        let start = offset + start;
+        let rest = &html[start..];
+
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |end| end + 3);
+            items.push((start, Item::Comment(&rest[..end])));
+            offset = start + end;
+            continue;
+        }
+
+        match parse_tag(rest) {
+            Some(tag) => {
+                offset = start + tag.len;
+                items.push((start, Item::Tag(tag)));
+            }
+            None => offset = start + 1,
+        }
+    }
+
+    items
+}
+
+// 1-based line and column of increasing byte offsets.
+struct Position<'a> {
+    html: &'a str,
+    offset: usize,
+    line: usize,
+    line_start: usize,
+}
+
+impl<'a> Position<'a> {
+    fn new(html: &'a str) -> Self {
+        Position {
+            html,
+            offset: 0,
+            line: 1,
+            line_start: 0,
+        }
+    }
+
+    fn at(&mut self, offset: usize) -> (usize, usize) {
+        for (index, byte) in self.html[self.offset..offset].bytes().enumerate() {
+            if byte == b'\n' {
+                self.line += 1;
+                self.line_start = self.offset + index + 1;
+            }
+        }
+
+        self.offset = offset;
+        let column = self.html[self.line_start..offset].chars().count() + 1;
+        (self.line, column)
+    }
+}
@@ -0,0 +1,104 @@
+use crate::attribute_rules;
+use crate::data_urls;
+use crate::headings;
+use crate::presets;
+use crate::relative_urls::{Fallback, RelativeUrls};
+use crate::types::options::{ExFeaturesOptions, ExHeadingsAs, ExPreset};
+use crate::video_embeds;
+use ammonia::{Builder, Url, UrlRelative};
+use std::borrow::Cow;
+
+// The ammonia builder for the render, or `None` when the output isn't
+// sanitized. Features that need to get content past the sanitizer extend
+// the preset policy or ammonia's defaults, and share a single attribute
+// filter since ammonia only takes one, which applies `attribute_rules` last.
+// Heading anchors keep their ids, which
+// are namespaced by `id_prefix` like ammonia's own `id_prefix` would do, but
+// without prefixing the generated ids twice.
+pub fn builder(
+    preset: Option<&ExPreset>,
+    features: &ExFeaturesOptions,
+) -> Option<Builder<'static>> {
+    let mut builder = match preset.and_then(presets::sanitizer) {
+        Some(builder) => builder,
+        None if features.sanitize => Builder::default(),
+        None => return None,
+    };
+
+    let attribute_rules = features.attribute_rules.clone();
+    let data_images = features.data_images.clone();
+    let video_embeds = features.video_embeds.clone();
+    let heading_divs = features.headings_as == ExHeadingsAs::Div;
+    let id_prefix = match features.heading_ids {
+        Some(_) => features.id_prefix.clone(),
+        None => None,
+    };
+
+    let base_url = features
+        .base_url
+        .as_deref()
+        .and_then(|url| Url::parse(url).ok());
+
+    if !features.relative_urls.is_empty() {
+        let fallback = match base_url {
+            Some(base_url) => Fallback::Resolve(base_url),
+            None if builder.is_url_relative_deny() => Fallback::Remove,
+            None => Fallback::Keep,
+        };
+
+        // invalid rules are reported by `render::resolve_options`
+        if let Ok(relative_urls) = RelativeUrls::new(&features.relative_urls, fallback) {
+            builder.url_relative(UrlRelative::Custom(Box::new(relative_urls)));
+        }
+    } else if let Some(base_url) = base_url {
+        builder.url_relative(UrlRelative::RewriteWithBase(base_url));
+    }
+
+    if data_images.is_some() {
+        builder.add_url_schemes(["data"]);
+    }
+
+    if let Some(ref options) = video_embeds {
+        video_embeds::allow_iframes(&mut builder, options);
+    }
+
+    if heading_divs {
+        builder
+            .add_tags(["div"])
+            .add_tag_attributes("div", ["role", "aria-level"]);
+    }
+
+    if id_prefix.is_some() {
+        builder.add_tag_attributes("a", ["id"]);
+    }
+
+    if data_images.is_some()
+        || video_embeds.is_some()
+        || heading_divs
+        || id_prefix.is_some()
+        || !attribute_rules.is_empty()
+    {
+        let url_schemes = builder.clone_url_schemes();
+
+        builder.attribute_filter(move |element, attribute, value| {
+            let keep = data_images.as_ref().is_none_or(|options| {
+                data_urls::keep_attribute(element, attribute, value, options)
+            }) && video_embeds.as_ref().is_none_or(|options| {
+                video_embeds::keep_attribute(element, attribute, value, options)
+            }) && (!heading_divs
+                || headings::keep_div_attribute(element, attribute, value));
+
+            let value = match (keep, id_prefix.as_deref()) {
+                (false, _) => return None,
+                (true, Some(prefix)) if attribute == "id" && !value.starts_with(prefix) => {
+                    Cow::Owned(format!("{}{}", prefix, value))
+                }
+                (true, _) => Cow::Borrowed(value),
+            };
+
+            attribute_rules::apply(&attribute_rules, &url_schemes, element, attribute, value)
+        });
+    }
+
+    Some(builder)
+}
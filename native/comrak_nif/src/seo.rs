@@ -0,0 +1,149 @@
+use crate::broken_links;
+use crate::text_units;
+use crate::types::options::{ExCountUnit, ExOptions};
+use ammonia::Url;
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{
+    Arena, ComrakExtensionOptions, ComrakOptions, ComrakParseOptions, ComrakRenderOptions,
+};
+
+const ELLIPSIS: &str = "…";
+
+#[derive(Debug, NifMap)]
+pub struct ExSeoMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub images: Vec<ExSeoImage>,
+    pub links: Vec<String>,
+}
+
+#[derive(Debug, NifMap)]
+pub struct ExSeoImage {
+    pub url: String,
+    pub alt: String,
+    pub title: String,
+}
+
+// Title, description, images and links for meta tags and sitemaps, in a
+// single pass over the document. Relative urls are resolved against
+// `base_url` when set, links are the unique absolute http urls without
+// their fragment.
+pub fn metadata(
+    md: &str,
+    options: ExOptions,
+    description_length: usize,
+    unit: ExCountUnit,
+) -> ExSeoMetadata {
+    let comrak_options = ComrakOptions {
+        extension: ComrakExtensionOptions::from(options.extension),
+        parse: ComrakParseOptions::from(options.parse),
+        render: ComrakRenderOptions::from(options.render),
+    };
+    let base_url = options
+        .features
+        .base_url
+        .as_deref()
+        .and_then(|url| Url::parse(url).ok());
+
+    let arena = Arena::new();
+    let root = broken_links::parse_document(
+        &arena,
+        md,
+        &comrak_options,
+        options.features.broken_link_resolver.as_ref(),
+    );
+
+    let mut metadata = ExSeoMetadata {
+        title: None,
+        description: None,
+        images: Vec::new(),
+        links: Vec::new(),
+    };
+
+    for node in root.descendants() {
+        match node.data.borrow().value {
+            NodeValue::Heading(ref heading) if heading.level == 1 && metadata.title.is_none() => {
+                metadata.title = Some(plain_text(node)).filter(|text| !text.is_empty());
+            }
+            NodeValue::Paragraph if metadata.description.is_none() && is_top_level(node) => {
+                let text = plain_text(node);
+
+                if !text.is_empty() {
+                    metadata.description = Some(truncate(&text, description_length, &unit));
+                }
+            }
+            NodeValue::Image(ref link) => metadata.images.push(ExSeoImage {
+                url: resolve(base_url.as_ref(), &link.url)
+                    .map_or_else(|| link.url.clone(), |url| url.to_string()),
+                alt: plain_text(node),
+                title: link.title.clone(),
+            }),
+            NodeValue::Link(ref link) => {
+                let url = resolve(base_url.as_ref(), &link.url)
+                    .filter(|url| matches!(url.scheme(), "http" | "https"));
+
+                if let Some(mut url) = url {
+                    url.set_fragment(None);
+                    let url = url.to_string();
+
+                    if !metadata.links.contains(&url) {
+                        metadata.links.push(url);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    metadata
+}
+
+fn is_top_level<'a>(node: &'a AstNode<'a>) -> bool {
+    node.parent()
+        .is_some_and(|parent| matches!(parent.data.borrow().value, NodeValue::Document))
+}
+
+fn resolve(base_url: Option<&Url>, url: &str) -> Option<Url> {
+    match base_url {
+        Some(base_url) => base_url.join(url).ok(),
+        None => Url::parse(url).ok(),
+    }
+}
+
+// The text of the node with whitespace collapsed, images excluded except
+// for the alt text of the node itself.
+fn plain_text<'a>(node: &'a AstNode<'a>) -> String {
+    let mut text = String::new();
+    push_text(node, &mut text);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn push_text<'a>(node: &'a AstNode<'a>, text: &mut String) {
+    for child in node.children() {
+        match child.data.borrow().value {
+            NodeValue::Text(ref literal) => text.push_str(literal),
+            NodeValue::Code(ref code) => text.push_str(&code.literal),
+            NodeValue::LineBreak | NodeValue::SoftBreak => text.push(' '),
+            NodeValue::Image(_) => continue,
+            _ => push_text(child, text),
+        }
+    }
+}
+
+// Cuts the text at the last word that fits in `max` units, including the
+// ellipsis.
+fn truncate(text: &str, max: usize, unit: &ExCountUnit) -> String {
+    if text_units::count(text, unit) <= max {
+        return text.to_string();
+    }
+
+    let ellipsis = text_units::count(ELLIPSIS, unit);
+    let end = text_units::end_of(text, max.saturating_sub(ellipsis), unit);
+    let cut = &text[..end];
+    let cut = match text[end..].starts_with(' ') {
+        true => cut,
+        false => cut.rfind(' ').map_or(cut, |space| &cut[..space]),
+    };
+
+    format!("{}{}", cut.trim_end(), ELLIPSIS)
+}
@@ -0,0 +1,72 @@
+use crate::types::options::{ExShortLinkRule, ExShortLinksOptions};
+use comrak::nodes::{AstNode, NodeValue};
+
+// Shortens the text of autolinks to GitHub issues, pull requests and commits
+// like GitHub does for pasted links. Only links whose text is the url are
+// changed, links with their own text are kept as written.
+pub fn apply<'a>(root: &'a AstNode<'a>, options: &ExShortLinksOptions) {
+    for node in root.descendants() {
+        let url = match node.data.borrow().value {
+            NodeValue::Link(ref link) => link.url.clone(),
+            _ => continue,
+        };
+
+        let text = match node.first_child() {
+            Some(text) if text.next_sibling().is_none() => text,
+            _ => continue,
+        };
+
+        let mut ast = text.data.borrow_mut();
+
+        if let NodeValue::Text(ref mut literal) = ast.value {
+            if *literal == url {
+                if let Some(short) = shorten(&url, &options.rules) {
+                    *literal = short;
+                }
+            }
+        }
+    }
+}
+
+// `https://github.com/org/repo/issues/1` is `org/repo#1` and
+// `https://github.com/org/repo/commit/<sha>` the first 7 characters of the
+// sha. Urls with a query or fragment are kept, they point to something else.
+fn shorten(url: &str, rules: &[ExShortLinkRule]) -> Option<String> {
+    let path = [
+        "https://github.com/",
+        "http://github.com/",
+        "https://www.github.com/",
+    ]
+    .iter()
+    .find_map(|prefix| url.strip_prefix(prefix))?;
+
+    if path.contains(['?', '#']) {
+        return None;
+    }
+
+    let segments: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+
+    let (org, repo, kind, id) = match segments[..] {
+        [org, repo, kind, id] if !org.is_empty() && !repo.is_empty() => (org, repo, kind, id),
+        _ => return None,
+    };
+
+    let is_number = !id.is_empty() && id.chars().all(|c| c.is_ascii_digit());
+
+    match kind {
+        "issues" if is_number && rules.contains(&ExShortLinkRule::GithubIssues) => {
+            Some(format!("{}/{}#{}", org, repo, id))
+        }
+        "pull" if is_number && rules.contains(&ExShortLinkRule::GithubPullRequests) => {
+            Some(format!("{}/{}#{}", org, repo, id))
+        }
+        "commit"
+            if (7..=40).contains(&id.len())
+                && id.chars().all(|c| c.is_ascii_hexdigit())
+                && rules.contains(&ExShortLinkRule::GithubCommits) =>
+        {
+            Some(id[..7].to_string())
+        }
+        _ => None,
+    }
+}
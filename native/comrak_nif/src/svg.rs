@@ -0,0 +1,134 @@
+use crate::grammar_registry;
+use crate::raw_html::escape_html;
+use crate::theme::declarations;
+use crate::types::options::ExSvgOptions;
+use autumn::themes::{self, Theme};
+use inkjet::constants::HIGHLIGHT_NAMES;
+use inkjet::Language;
+use std::fmt::Write;
+use tree_sitter_highlight::{HighlightEvent, Highlighter};
+
+// Monospace fonts are roughly 0.6em wide, close enough to size the canvas.
+const CHAR_WIDTH: f64 = 0.6;
+
+pub fn highlight_code(
+    source: &str,
+    lang: Option<&str>,
+    theme: &str,
+    options: &ExSvgOptions,
+) -> Result<String, String> {
+    let source = source.trim_end_matches('\n');
+    let theme = themes::theme(theme).ok_or_else(|| format!("unknown theme: {}", theme))?;
+    let config = lang
+        .and_then(grammar_registry::config)
+        .unwrap_or_else(|| Language::Diff.config());
+
+    let mut highlighter = Highlighter::new();
+    let highlights = highlighter
+        .highlight(config, source.as_bytes(), None, |token| {
+            grammar_registry::config(token)
+        })
+        .map_err(|err| err.to_string())?;
+
+    let font_size = options.font_size as f64;
+    let padding = options.padding as f64;
+    let line_height = font_size * options.line_height;
+    let lines = source.split('\n');
+    let columns = lines.clone().map(|line| line.chars().count()).max();
+    let width = columns.unwrap_or(0) as f64 * font_size * CHAR_WIDTH + padding * 2.0;
+    let height = lines.count() as f64 * line_height + padding * 2.0;
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+        w = width,
+        h = height
+    );
+    let _ = write!(
+        svg,
+        "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>",
+        color(theme, "background", "background-color").unwrap_or("#ffffff")
+    );
+    let _ = write!(
+        svg,
+        "<text font-family=\"{}\" font-size=\"{}\" fill=\"{}\" xml:space=\"preserve\">",
+        escape_html(&options.font_family),
+        options.font_size,
+        color(theme, "text", "color").unwrap_or("#000000")
+    );
+
+    let mut line = 0;
+    let mut open: Vec<String> = Vec::new();
+    open_line(&mut svg, options, line_height, line);
+
+    for event in highlights {
+        match event.map_err(|err| err.to_string())? {
+            HighlightEvent::Source { start, end } => {
+                let text = source.get(start..end).unwrap_or_default();
+                let mut parts = text.split('\n').peekable();
+
+                while let Some(part) = parts.next() {
+                    svg.push_str(&escape_html(part));
+
+                    // each line is its own tspan, so spans crossing lines are
+                    // closed at the end of the line and reopened on the next one
+                    if parts.peek().is_some() {
+                        open.iter().for_each(|_| svg.push_str("</tspan>"));
+                        svg.push_str("</tspan>");
+                        line += 1;
+                        open_line(&mut svg, options, line_height, line);
+                        open.iter().for_each(|tspan| svg.push_str(tspan));
+                    }
+                }
+            }
+            HighlightEvent::HighlightStart(highlight) => {
+                let tspan = open_tspan(theme, HIGHLIGHT_NAMES[highlight.0]);
+                svg.push_str(&tspan);
+                open.push(tspan);
+            }
+            HighlightEvent::HighlightEnd => {
+                open.pop();
+                svg.push_str("</tspan>");
+            }
+        }
+    }
+
+    svg.push_str("</tspan></text></svg>");
+    Ok(svg)
+}
+
+fn open_line(svg: &mut String, options: &ExSvgOptions, line_height: f64, line: usize) {
+    // baseline of the first line sits one font size below the top padding
+    let y = (options.padding + options.font_size) as f64 + line as f64 * line_height;
+    let _ = write!(svg, "<tspan x=\"{}\" y=\"{}\">", options.padding, y);
+}
+
+// Translates the CSS declarations of a theme scope into SVG presentation
+// attributes, ignoring the ones that don't apply to text.
+fn open_tspan(theme: &Theme, scope: &str) -> String {
+    let (_class, style) = theme.get_scope(scope);
+    let mut tspan = String::from("<tspan");
+
+    for (property, value) in declarations(style) {
+        let attribute = match property {
+            "color" => "fill",
+            "font-weight" | "font-style" | "text-decoration" => property,
+            _ => continue,
+        };
+
+        let _ = write!(tspan, " {}=\"{}\"", attribute, escape_html(value));
+    }
+
+    tspan.push('>');
+    tspan
+}
+
+fn color<'a>(theme: &'a Theme, scope: &str, property: &str) -> Option<&'a str> {
+    let (_class, style) = theme.get_scope(scope);
+
+    declarations(style)
+        .filter(|(name, _)| *name == property)
+        .map(|(_, value)| value)
+        .last()
+}
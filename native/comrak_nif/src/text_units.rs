@@ -0,0 +1,45 @@
+use crate::types::options::ExCountUnit;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Length of the text in the unit. Graphemes count emoji sequences and
+// combining marks as the single character users see.
+pub fn count(text: &str, unit: &ExCountUnit) -> usize {
+    match unit {
+        ExCountUnit::Grapheme => text.graphemes(true).count(),
+        ExCountUnit::Char => text.chars().count(),
+        ExCountUnit::Byte => text.len(),
+    }
+}
+
+// Byte index of the end of the first `max` units of the text, which never
+// splits a grapheme or char.
+pub fn end_of(text: &str, max: usize, unit: &ExCountUnit) -> usize {
+    match unit {
+        ExCountUnit::Grapheme => text
+            .grapheme_indices(true)
+            .nth(max)
+            .map_or(text.len(), |(end, _)| end),
+        ExCountUnit::Char => text
+            .char_indices()
+            .nth(max)
+            .map_or(text.len(), |(end, _)| end),
+        ExCountUnit::Byte => {
+            let mut end = max.min(text.len());
+
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+
+            end
+        }
+    }
+}
+
+// How lengths in the unit are written in messages.
+pub fn name(unit: &ExCountUnit) -> &'static str {
+    match unit {
+        ExCountUnit::Grapheme => "characters",
+        ExCountUnit::Char => "code points",
+        ExCountUnit::Byte => "bytes",
+    }
+}
@@ -0,0 +1,186 @@
+use crate::render::RenderError;
+use autumn::themes::{self, Theme};
+
+const MAX_SUGGESTIONS: usize = 3;
+
+// The vendored autumn crate doesn't list its themes, so suggestions come from
+// this copy of the names. Names missing after a vendor update are skipped.
+const NAMES: [&str; 109] = [
+    "acme",
+    "adwaita_dark",
+    "amberwood",
+    "autumn",
+    "autumn_night",
+    "ayu_dark",
+    "ayu_evolve",
+    "ayu_light",
+    "ayu_mirage",
+    "base16_default_dark",
+    "base16_default_light",
+    "base16_terminal",
+    "base16_tomorrow",
+    "base16_tomorrow_night",
+    "base16_transparent",
+    "bogster",
+    "bogster_light",
+    "boo_berry",
+    "catppuccin_frappe",
+    "catppuccin_latte",
+    "catppuccin_macchiato",
+    "catppuccin_mocha",
+    "cyan_light",
+    "darcula",
+    "darcula_solid",
+    "dark_high_contrast",
+    "dark_plus",
+    "doom_acario_dark",
+    "dracula",
+    "dracula_at_night",
+    "emacs",
+    "everblush",
+    "everforest_dark",
+    "everforest_light",
+    "ferra",
+    "flatwhite",
+    "fleet_dark",
+    "github_dark",
+    "github_dark_colorblind",
+    "github_dark_dimmed",
+    "github_dark_high_contrast",
+    "github_dark_tritanopia",
+    "github_light",
+    "github_light_colorblind",
+    "github_light_high_contrast",
+    "github_light_tritanopia",
+    "gruvbox",
+    "gruvbox_dark_hard",
+    "gruvbox_dark_soft",
+    "gruvbox_light",
+    "heisenberg",
+    "hex_lavender",
+    "hex_steel",
+    "hex_toxic",
+    "ingrid",
+    "jellybeans",
+    "kanagawa",
+    "kaolin_dark",
+    "kaolin_light",
+    "kaolin_valley_dark",
+    "material_darker",
+    "material_deep_ocean",
+    "material_oceanic",
+    "material_palenight",
+    "meliora",
+    "mellow",
+    "merionette",
+    "molokai",
+    "monokai",
+    "monokai_aqua",
+    "monokai_pro",
+    "monokai_pro_machine",
+    "monokai_pro_octagon",
+    "monokai_pro_ristretto",
+    "monokai_pro_spectrum",
+    "naysayer",
+    "new_moon",
+    "night_owl",
+    "nightfox",
+    "noctis",
+    "noctis_bordo",
+    "nord",
+    "nord_light",
+    "onedark",
+    "onedarker",
+    "onelight",
+    "papercolor_dark",
+    "papercolor_light",
+    "penumbra",
+    "pop_dark",
+    "rasmus",
+    "rose_pine",
+    "rose_pine_dawn",
+    "rose_pine_moon",
+    "serika_dark",
+    "serika_light",
+    "snazzy",
+    "solarized_dark",
+    "solarized_light",
+    "sonokai",
+    "spacebones_light",
+    "tokyonight",
+    "tokyonight_storm",
+    "varua",
+    "vim_dark_high_contrast",
+    "yellowed",
+    "zed_onedark",
+    "zed_onelight",
+    "zenburn",
+];
+
+pub fn resolve(name: &str) -> Result<&Theme, RenderError> {
+    themes::theme(name)
+        .ok_or_else(|| RenderError::UnknownTheme(name.to_string(), suggestions(name)))
+}
+
+// `(property, value)` of the CSS declarations of a theme scope style.
+pub fn declarations(style: &str) -> impl Iterator<Item = (&str, &str)> {
+    style.split(';').filter_map(|declaration| {
+        let (property, value) = declaration.split_once(':')?;
+        let value = value.trim();
+
+        match value.is_empty() {
+            true => None,
+            false => Some((property.trim(), value)),
+        }
+    })
+}
+
+// Themes with a similar name, closest first. Names are compared the way
+// they're usually misspelled: with dashes or spaces instead of underscores.
+fn suggestions(name: &str) -> Vec<String> {
+    let name: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if matches!(c, '-' | ' ') { '_' } else { c })
+        .collect();
+    let max_distance = (name.chars().count() / 3).max(2);
+
+    let mut candidates: Vec<_> = NAMES
+        .into_iter()
+        .filter(|candidate| themes::theme(candidate).is_some())
+        .filter_map(|candidate| {
+            let distance = distance(&name, candidate);
+
+            match distance <= max_distance || (name.len() >= 3 && candidate.contains(&name)) {
+                true => Some((distance, candidate)),
+                false => None,
+            }
+        })
+        .collect();
+
+    candidates.sort();
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+// Levenshtein distance.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
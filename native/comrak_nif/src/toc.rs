@@ -0,0 +1,121 @@
+use crate::headings::HeadingId;
+use crate::raw_html::{allow_injected_html, escape_html, html_block};
+use crate::types::options::ExTocOptions;
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{Arena, ComrakOptions};
+
+// `<!-- toc -->` and `[[toc]]` markers of the document. Must be called before
+// other passes inject html, which rewrites the raw html of the document.
+pub fn markers<'a>(root: &'a AstNode<'a>) -> Vec<&'a AstNode<'a>> {
+    root.descendants().filter(|node| is_marker(node)).collect()
+}
+
+// Replaces the markers with a nested list of links to the headings.
+pub fn apply<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    root: &'a AstNode<'a>,
+    markers: Vec<&'a AstNode<'a>>,
+    options: &ExTocOptions,
+    headings: &[HeadingId<'a>],
+    comrak_options: &mut ComrakOptions,
+) {
+    if markers.is_empty() {
+        return;
+    }
+
+    allow_injected_html(root, comrak_options);
+
+    let toc = render(options, headings);
+
+    for marker in markers {
+        if !toc.is_empty() {
+            marker.insert_before(html_block(arena, toc.clone()));
+        }
+
+        marker.detach();
+    }
+}
+
+// An html block with only the comment, or a paragraph with only the text,
+// which comrak may split in several text nodes around the brackets.
+fn is_marker<'a>(node: &'a AstNode<'a>) -> bool {
+    match node.data.borrow().value {
+        NodeValue::HtmlBlock(ref block) => block
+            .literal
+            .trim()
+            .strip_prefix("<!--")
+            .and_then(|comment| comment.strip_suffix("-->"))
+            .is_some_and(|comment| comment.trim().eq_ignore_ascii_case("toc")),
+        NodeValue::Paragraph => {
+            let mut text = String::new();
+
+            for child in node.children() {
+                match child.data.borrow().value {
+                    NodeValue::Text(ref literal) => text.push_str(literal),
+                    _ => return false,
+                }
+            }
+
+            text.trim().eq_ignore_ascii_case("[[toc]]")
+        }
+        _ => false,
+    }
+}
+
+// Headings deeper than the previous one are nested in its item, and
+// shallower ones close the lists until one at the same or a lower level.
+fn render(options: &ExTocOptions, headings: &[HeadingId]) -> String {
+    let first = options.levels.first.max(1);
+    let last = options.levels.last.min(6);
+    let mut toc = String::new();
+    let mut levels: Vec<u8> = Vec::new();
+
+    for heading in headings {
+        if heading.level < first || heading.level > last {
+            continue;
+        }
+
+        match levels.last() {
+            None => {
+                toc.push_str("<nav class=\"toc\">\n<ul>\n");
+                levels.push(heading.level);
+            }
+            Some(&level) if heading.level > level => {
+                toc.push_str("\n<ul>\n");
+                levels.push(heading.level);
+            }
+            Some(_) => {
+                toc.push_str("</li>\n");
+
+                while levels.len() > 1 && heading.level <= levels[levels.len() - 2] {
+                    levels.pop();
+                    toc.push_str("</ul>\n</li>\n");
+                }
+            }
+        }
+
+        let id = escape_html(&heading.id);
+        toc.push_str(&format!(
+            "<li><a href=\"#{}\">{}</a>",
+            id,
+            escape_html(heading.text.trim())
+        ));
+    }
+
+    if levels.is_empty() {
+        return toc;
+    }
+
+    toc.push_str("</li>\n");
+
+    for nested in (0..levels.len()).rev() {
+        toc.push_str("</ul>\n");
+
+        if nested > 0 {
+            toc.push_str("</li>\n");
+        }
+    }
+
+    toc.push_str("</nav>\n");
+    toc
+}
@@ -0,0 +1,37 @@
+use crate::grammar_registry;
+use inkjet::constants::HIGHLIGHT_NAMES;
+use tree_sitter_highlight::{HighlightEvent, Highlighter};
+
+// Byte ranges of the highlighted spans with their innermost scope, the same
+// scopes the html highlighter turns into classes. Text outside any highlight
+// is left out.
+pub fn highlight_tokens(source: &str, lang: &str) -> Result<Vec<((usize, usize), String)>, String> {
+    let config =
+        grammar_registry::config(lang).ok_or_else(|| format!("unknown language: {}", lang))?;
+
+    let mut highlighter = Highlighter::new();
+    let highlights = highlighter
+        .highlight(config, source.as_bytes(), None, |token| {
+            grammar_registry::config(token)
+        })
+        .map_err(|err| err.to_string())?;
+
+    let mut tokens = Vec::new();
+    let mut scopes = Vec::new();
+
+    for event in highlights {
+        match event.map_err(|err| err.to_string())? {
+            HighlightEvent::Source { start, end } => {
+                if let Some(scope) = scopes.last() {
+                    tokens.push(((start, end), HIGHLIGHT_NAMES[*scope].to_string()));
+                }
+            }
+            HighlightEvent::HighlightStart(highlight) => scopes.push(highlight.0),
+            HighlightEvent::HighlightEnd => {
+                scopes.pop();
+            }
+        }
+    }
+
+    Ok(tokens)
+}
@@ -0,0 +1,12 @@
+use crate::audit::ExViolation;
+use crate::language::ExLanguage;
+use crate::warnings::ExWarning;
+
+#[derive(Debug, Default, NifMap)]
+pub struct ExRenderMetadata {
+    pub was_sanitized: bool,
+    pub violations: Vec<ExViolation>,
+    pub warnings: Vec<ExWarning>,
+    pub language: Option<ExLanguage>,
+    pub ids: Option<Vec<String>>,
+}
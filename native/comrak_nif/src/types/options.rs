@@ -1,6 +1,12 @@
+use crate::cache::CacheRef;
+use crate::metrics::MetricsRef;
 use comrak::{ComrakExtensionOptions, ComrakParseOptions, ComrakRenderOptions, ListStyleType};
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Debug, NifStruct)]
+// Defaults mirror the Elixir structs in `lib/mdex/types/options.ex`.
+
+#[derive(Debug, Default, NifStruct, Serialize)]
 #[module = "MDEx.Types.ExtensionOptions"]
 pub struct ExExtensionOptions {
     pub strikethrough: bool,
@@ -13,6 +19,7 @@ pub struct ExExtensionOptions {
     pub footnotes: bool,
     pub description_lists: bool,
     pub front_matter_delimiter: Option<String>,
+    pub shortcodes: bool,
 }
 
 impl From<ExExtensionOptions> for ComrakExtensionOptions {
@@ -28,11 +35,12 @@ impl From<ExExtensionOptions> for ComrakExtensionOptions {
             footnotes: options.footnotes,
             description_lists: options.description_lists,
             front_matter_delimiter: options.front_matter_delimiter,
+            shortcodes: options.shortcodes,
         }
     }
 }
 
-#[derive(Debug, NifStruct)]
+#[derive(Debug, Default, NifStruct, Serialize)]
 #[module = "MDEx.Types.ParseOptions"]
 pub struct ExParseOptions {
     pub smart: bool,
@@ -50,13 +58,19 @@ impl From<ExParseOptions> for ComrakParseOptions {
     }
 }
 
-#[derive(Debug, NifUnitEnum)]
+#[derive(Debug, NifUnitEnum, Serialize)]
 pub enum ExListStyleType {
     Dash,
     Plus,
     Star,
 }
 
+impl Default for ExListStyleType {
+    fn default() -> Self {
+        ExListStyleType::Dash
+    }
+}
+
 impl From<ExListStyleType> for ListStyleType {
     fn from(list_style_type: ExListStyleType) -> Self {
         match list_style_type {
@@ -67,7 +81,7 @@ impl From<ExListStyleType> for ListStyleType {
     }
 }
 
-#[derive(Debug, NifStruct)]
+#[derive(Debug, Default, NifStruct, Serialize)]
 #[module = "MDEx.Types.RenderOptions"]
 pub struct ExRenderOptions {
     pub hardbreaks: bool,
@@ -95,18 +109,495 @@ impl From<ExRenderOptions> for ComrakRenderOptions {
     }
 }
 
-#[derive(Debug, NifStruct)]
+#[derive(Debug, Default, NifStruct, Serialize)]
+#[module = "MDEx.Types.DescriptionListOptions"]
+pub struct ExDescriptionListOptions {
+    pub dl_class: Option<String>,
+    pub dt_class: Option<String>,
+    pub dd_class: Option<String>,
+    pub tight: bool,
+}
+
+#[derive(Debug, NifStruct, Serialize)]
+#[module = "MDEx.Types.CodeBlockAttributesOptions"]
+pub struct ExCodeBlockAttributesOptions {
+    pub translate: Option<String>,
+    pub tabindex: Option<i32>,
+    #[serde(serialize_with = "sorted")]
+    pub pre: HashMap<String, String>,
+    #[serde(serialize_with = "sorted")]
+    pub code: HashMap<String, String>,
+}
+
+impl Default for ExCodeBlockAttributesOptions {
+    fn default() -> Self {
+        ExCodeBlockAttributesOptions {
+            translate: Some("no".to_string()),
+            tabindex: None,
+            pre: HashMap::new(),
+            code: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, NifStruct, Serialize)]
+#[module = "MDEx.Types.InputLimitsOptions"]
+pub struct ExInputLimitsOptions {
+    pub max_nesting_depth: usize,
+    pub max_delimiter_runs: usize,
+    pub max_link_definitions: usize,
+    pub max_table_cells: usize,
+}
+
+impl Default for ExInputLimitsOptions {
+    fn default() -> Self {
+        ExInputLimitsOptions {
+            max_nesting_depth: 100,
+            max_delimiter_runs: 5_000,
+            max_link_definitions: 10_000,
+            max_table_cells: 100_000,
+        }
+    }
+}
+
+#[derive(Debug, NifUnitEnum, Serialize)]
+pub enum ExHeadingCollisions {
+    Number,
+    Hash,
+    Error,
+}
+
+impl Default for ExHeadingCollisions {
+    fn default() -> Self {
+        ExHeadingCollisions::Number
+    }
+}
+
+#[derive(Debug, NifUnitEnum, Serialize)]
+pub enum ExHeadingIdSource {
+    Text,
+    Explicit,
+    FrontMatterMap,
+}
+
+impl Default for ExHeadingIdSource {
+    fn default() -> Self {
+        ExHeadingIdSource::Text
+    }
+}
+
+#[derive(Debug, Default, NifStruct, Serialize)]
+#[module = "MDEx.Types.HeadingIdsOptions"]
+pub struct ExHeadingIdsOptions {
+    pub prefix: String,
+    pub suffix: String,
+    pub collisions: ExHeadingCollisions,
+    pub source: ExHeadingIdSource,
+    pub exclude: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, NifUnitEnum, Serialize)]
+pub enum ExHeadingsAs {
+    Heading,
+    Div,
+}
+
+impl Default for ExHeadingsAs {
+    fn default() -> Self {
+        ExHeadingsAs::Heading
+    }
+}
+
+#[derive(Debug, NifStruct, Serialize)]
+#[module = "Range"]
+pub struct ExRange {
+    pub first: u8,
+    pub last: u8,
+}
+
+#[derive(Debug, NifStruct, Serialize)]
+#[module = "MDEx.Types.NumberHeadingsOptions"]
+pub struct ExNumberHeadingsOptions {
+    pub levels: ExRange,
+    pub format: String,
+    pub anchors: bool,
+}
+
+#[derive(Debug, NifStruct, Serialize)]
+#[module = "MDEx.Types.TocOptions"]
+pub struct ExTocOptions {
+    pub levels: ExRange,
+}
+
+impl Default for ExTocOptions {
+    fn default() -> Self {
+        ExTocOptions {
+            levels: ExRange { first: 1, last: 6 },
+        }
+    }
+}
+
+#[derive(Debug, NifUnitEnum, Serialize)]
+pub enum ExCountUnit {
+    Grapheme,
+    Char,
+    Byte,
+}
+
+impl Default for ExCountUnit {
+    fn default() -> Self {
+        ExCountUnit::Grapheme
+    }
+}
+
+#[derive(Debug, NifStruct, Serialize)]
+#[module = "MDEx.Types.HeadingLintOptions"]
+pub struct ExHeadingLintOptions {
+    pub duplicates: bool,
+    pub max_length: Option<usize>,
+    pub unit: ExCountUnit,
+}
+
+impl Default for ExHeadingLintOptions {
+    fn default() -> Self {
+        ExHeadingLintOptions {
+            duplicates: true,
+            max_length: None,
+            unit: ExCountUnit::default(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, NifUnitEnum, Serialize)]
+pub enum ExEntityEncoding {
+    Utf8,
+    Numeric,
+    Named,
+}
+
+impl Default for ExEntityEncoding {
+    fn default() -> Self {
+        ExEntityEncoding::Utf8
+    }
+}
+
+#[derive(Clone, Debug, NifStruct, Serialize)]
+#[module = "MDEx.Types.DataImagesOptions"]
+pub struct ExDataImagesOptions {
+    pub max_bytes: usize,
+    pub mime_types: Vec<String>,
+}
+
+impl Default for ExDataImagesOptions {
+    fn default() -> Self {
+        ExDataImagesOptions {
+            max_bytes: 16_384,
+            mime_types: ["image/png", "image/jpeg", "image/gif", "image/webp"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, NifStruct, Serialize)]
+#[module = "MDEx.Types.ResponsiveImagesOptions"]
+pub struct ExResponsiveImagesOptions {
+    pub widths: Vec<u32>,
+    pub url: String,
+    pub sizes: Option<String>,
+}
+
+impl Default for ExResponsiveImagesOptions {
+    fn default() -> Self {
+        ExResponsiveImagesOptions {
+            widths: vec![480, 960, 1440],
+            url: "{url}?w={width}".to_string(),
+            sizes: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, NifUnitEnum, Serialize)]
+pub enum ExVideoProvider {
+    Youtube,
+    Vimeo,
+}
+
+#[derive(Clone, Debug, NifStruct, Serialize)]
+#[module = "MDEx.Types.VideoEmbedsOptions"]
+pub struct ExVideoEmbedsOptions {
+    pub providers: Vec<ExVideoProvider>,
+    pub wrapper_class: Option<String>,
+}
+
+impl Default for ExVideoEmbedsOptions {
+    fn default() -> Self {
+        ExVideoEmbedsOptions {
+            providers: vec![ExVideoProvider::Youtube, ExVideoProvider::Vimeo],
+            wrapper_class: Some("video-embed".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, NifStruct, Serialize)]
+#[module = "MDEx.Types.TypographyOptions"]
+pub struct ExTypographyOptions {
+    pub collapse_spaces: bool,
+    pub trim_trailing: bool,
+    pub non_breaking_spaces: bool,
+    pub space_before_punctuation: bool,
+}
+
+impl Default for ExTypographyOptions {
+    fn default() -> Self {
+        ExTypographyOptions {
+            collapse_spaces: true,
+            trim_trailing: true,
+            non_breaking_spaces: true,
+            space_before_punctuation: true,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, NifUnitEnum, Serialize)]
+pub enum ExShortLinkRule {
+    GithubIssues,
+    GithubPullRequests,
+    GithubCommits,
+}
+
+#[derive(Debug, NifStruct, Serialize)]
+#[module = "MDEx.Types.ShortLinksOptions"]
+pub struct ExShortLinksOptions {
+    pub rules: Vec<ExShortLinkRule>,
+}
+
+impl Default for ExShortLinksOptions {
+    fn default() -> Self {
+        ExShortLinksOptions {
+            rules: vec![
+                ExShortLinkRule::GithubIssues,
+                ExShortLinkRule::GithubPullRequests,
+                ExShortLinkRule::GithubCommits,
+            ],
+        }
+    }
+}
+
+#[derive(Debug, NifStruct, Serialize)]
+#[module = "MDEx.Types.InternalLinksOptions"]
+pub struct ExInternalLinksOptions {
+    pub prefixes: Vec<String>,
+    pub path_prefix: Option<String>,
+    // `[{name, value}]`, normalized to strings by `MDEx`
+    pub attributes: Vec<(String, String)>,
+}
+
+impl Default for ExInternalLinksOptions {
+    fn default() -> Self {
+        ExInternalLinksOptions {
+            prefixes: vec!["/".to_string()],
+            path_prefix: None,
+            attributes: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Default, NifStruct, Serialize)]
+#[module = "MDEx.Types.LangOptions"]
+pub struct ExLangOptions {
+    pub tag: Option<String>,
+    pub per_block: bool,
+}
+
+#[derive(Debug, NifUnitEnum, Serialize)]
+pub enum ExPatternKind {
+    String,
+    Regex,
+}
+
+// `{kind, pattern, replacement}`, regexes converted to their source by `MDEx`.
+#[derive(Debug, NifTuple, Serialize)]
+pub struct ExReplacement {
+    pub kind: ExPatternKind,
+    pub pattern: String,
+    pub replacement: String,
+}
+
+#[derive(Clone, Copy, Debug, NifUnitEnum, Serialize)]
+pub enum ExAttributeAction {
+    Keep,
+    Drop,
+    Rewrite,
+}
+
+// `{tag, attribute, action, template}`, converted from `{tag, attribute, action}`
+// by `MDEx`, with the template of `{:rewrite, template}` actions.
+#[derive(Clone, Debug, NifTuple, Serialize)]
+pub struct ExAttributeRule {
+    pub tag: String,
+    pub attribute: String,
+    pub action: ExAttributeAction,
+    pub template: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, NifUnitEnum, Serialize)]
+pub enum ExNodeType {
+    BlockQuote,
+    List,
+    CodeBlock,
+    HtmlBlock,
+    Heading,
+    ThematicBreak,
+    Table,
+    FootnoteDefinition,
+    Code,
+    HtmlInline,
+    Link,
+    Image,
+    FootnoteReference,
+}
+
+// A url template with a `{ref}` placeholder or a map of labels to urls.
+#[derive(Debug, NifUntaggedEnum, Serialize)]
+pub enum ExBrokenLinkResolver {
+    Template(String),
+    Links(#[serde(serialize_with = "sorted")] HashMap<String, String>),
+}
+
+// `{tag, [{name, value}]}`, attributes normalized to strings by `MDEx`.
+#[derive(Clone, Debug, NifTuple, Serialize)]
+pub struct ExWrapper {
+    pub tag: String,
+    pub attributes: Vec<(String, String)>,
+}
+
+#[derive(Debug, NifStruct, Serialize)]
 #[module = "MDEx.Types.FeaturesOptions"]
 pub struct ExFeaturesOptions {
     pub sanitize: bool,
     pub syntax_highlight_theme: Option<String>,
+    pub max_input_bytes: Option<usize>,
+    pub max_output_bytes: Option<usize>,
+    #[serde(skip)]
+    pub metrics: Option<MetricsRef>,
+    pub description_list: Option<ExDescriptionListOptions>,
+    pub entity_encoding: ExEntityEncoding,
+    pub code_block_attributes: Option<ExCodeBlockAttributesOptions>,
+    pub input_limits: Option<ExInputLimitsOptions>,
+    pub heading_ids: Option<ExHeadingIdsOptions>,
+    pub number_headings: Option<ExNumberHeadingsOptions>,
+    pub document_dir: Option<String>,
+    pub audit: bool,
+    pub before_html: Option<String>,
+    pub after_html: Option<String>,
+    pub wrapper: Option<ExWrapper>,
+    pub data_images: Option<ExDataImagesOptions>,
+    #[serde(skip)]
+    pub cache: Option<CacheRef>,
+    pub responsive_images: Option<ExResponsiveImagesOptions>,
+    pub video_embeds: Option<ExVideoEmbedsOptions>,
+    pub typography: Option<ExTypographyOptions>,
+    pub toc: Option<ExTocOptions>,
+    pub link_titles: bool,
+    pub max_spans_per_block: Option<usize>,
+    pub headings_as: ExHeadingsAs,
+    pub short_links: Option<ExShortLinksOptions>,
+    pub detect_language: bool,
+    pub lang: Option<ExLangOptions>,
+    pub id_prefix: Option<String>,
+    pub replacements: Vec<ExReplacement>,
+    pub abbreviations: bool,
+    pub omit: Vec<ExNodeType>,
+    pub base_url: Option<String>,
+    pub heading_lint: Option<ExHeadingLintOptions>,
+    pub internal_links: Option<ExInternalLinksOptions>,
+    pub diff_words: bool,
+    pub heading_template: Option<String>,
+    pub collect_ids: bool,
+    pub broken_link_resolver: Option<ExBrokenLinkResolver>,
+    pub timeout: Option<u64>,
+    pub relative_urls: Vec<ExReplacement>,
+    pub attribute_rules: Vec<ExAttributeRule>,
+}
+
+impl Default for ExFeaturesOptions {
+    fn default() -> Self {
+        ExFeaturesOptions {
+            sanitize: false,
+            syntax_highlight_theme: Some("onedark".to_string()),
+            max_input_bytes: None,
+            max_output_bytes: None,
+            metrics: None,
+            description_list: None,
+            entity_encoding: ExEntityEncoding::default(),
+            code_block_attributes: None,
+            input_limits: None,
+            heading_ids: None,
+            number_headings: None,
+            document_dir: None,
+            audit: false,
+            before_html: None,
+            after_html: None,
+            wrapper: None,
+            data_images: None,
+            cache: None,
+            responsive_images: None,
+            video_embeds: None,
+            typography: None,
+            toc: None,
+            link_titles: false,
+            max_spans_per_block: None,
+            headings_as: ExHeadingsAs::default(),
+            short_links: None,
+            detect_language: false,
+            lang: None,
+            id_prefix: None,
+            replacements: Vec::new(),
+            abbreviations: false,
+            omit: Vec::new(),
+            base_url: None,
+            heading_lint: None,
+            internal_links: None,
+            diff_words: false,
+            heading_template: None,
+            collect_ids: false,
+            broken_link_resolver: None,
+            timeout: None,
+            relative_urls: Vec::new(),
+            attribute_rules: Vec::new(),
+        }
+    }
+}
+
+// Serializes hash maps with sorted keys, since their iteration order is
+// random and `fingerprint` hashes the serialized options.
+fn sorted<S: Serializer>(map: &HashMap<String, String>, serializer: S) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
 }
 
 #[derive(Debug, NifStruct)]
+#[module = "MDEx.Types.SvgOptions"]
+pub struct ExSvgOptions {
+    pub font_family: String,
+    pub font_size: u32,
+    pub line_height: f64,
+    pub padding: u32,
+}
+
+#[derive(Debug, NifUnitEnum, Serialize)]
+pub enum ExPreset {
+    Comments,
+    Feed,
+    Hardened,
+}
+
+#[derive(Debug, Default, NifStruct, Serialize)]
 #[module = "MDEx.Types.Options"]
 pub struct ExOptions {
     pub extension: ExExtensionOptions,
     pub parse: ExParseOptions,
     pub render: ExRenderOptions,
     pub features: ExFeaturesOptions,
+    pub preset: Option<ExPreset>,
 }
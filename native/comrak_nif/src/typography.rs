@@ -0,0 +1,103 @@
+use crate::types::options::ExTypographyOptions;
+use comrak::nodes::{AstNode, NodeValue};
+
+// Non-breaking spaces, including the narrow and figure variants.
+const NON_BREAKING_SPACES: [char; 3] = ['\u{a0}', '\u{202f}', '\u{2007}'];
+
+// Normalizes the whitespace of text nodes. Code spans, code blocks and raw
+// html are left untouched.
+pub fn apply<'a>(root: &'a AstNode<'a>, options: &ExTypographyOptions) {
+    for node in root.descendants() {
+        let ends_line = ends_line(node);
+        let mut ast = node.data.borrow_mut();
+
+        let text = match ast.value {
+            NodeValue::Text(ref mut text) => text,
+            _ => continue,
+        };
+
+        if options.non_breaking_spaces && text.contains(NON_BREAKING_SPACES) {
+            *text = text.replace(NON_BREAKING_SPACES, " ");
+        }
+
+        if options.collapse_spaces {
+            *text = collapse_spaces(text);
+        }
+
+        if options.space_before_punctuation {
+            *text = remove_space_before_punctuation(text);
+        }
+
+        if options.trim_trailing && ends_line {
+            text.truncate(text.trim_end_matches([' ', '\t']).len());
+        }
+    }
+}
+
+// Whether the node is the last inline of a line: followed by a line break or
+// the end of its block.
+fn ends_line<'a>(node: &'a AstNode<'a>) -> bool {
+    match node.next_sibling() {
+        Some(next) => matches!(
+            next.data.borrow().value,
+            NodeValue::SoftBreak | NodeValue::LineBreak
+        ),
+        None => node
+            .parent()
+            .is_some_and(|parent| parent.data.borrow().value.block()),
+    }
+}
+
+fn collapse_spaces(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut previous_space = false;
+
+    for c in text.chars() {
+        let space = matches!(c, ' ' | '\t');
+
+        if !(space && previous_space) {
+            collapsed.push(if space { ' ' } else { c });
+        }
+
+        previous_space = space;
+    }
+
+    collapsed
+}
+
+// Removes the spaces before `,` `.` `;` `:` `!` `?` and `)` when the mark ends
+// a word, so `wait ...` is fixed but numbers like `.5` are kept.
+fn remove_space_before_punctuation(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut fixed = String::with_capacity(text.len());
+    let mut index = 0;
+
+    while index < chars.len() {
+        if matches!(chars[index], ' ' | '\t') {
+            let end = index
+                + chars[index..]
+                    .iter()
+                    .take_while(|c| matches!(c, ' ' | '\t'))
+                    .count();
+            let marks = chars[end..]
+                .iter()
+                .take_while(|c| is_punctuation(**c))
+                .count();
+            let ends_word = chars.get(end + marks).is_none_or(|c| c.is_whitespace());
+
+            if marks > 0 && ends_word {
+                index = end;
+                continue;
+            }
+        }
+
+        fixed.push(chars[index]);
+        index += 1;
+    }
+
+    fixed
+}
+
+fn is_punctuation(c: char) -> bool {
+    matches!(c, ',' | '.' | ';' | ':' | '!' | '?' | ')')
+}
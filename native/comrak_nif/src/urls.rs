@@ -0,0 +1,75 @@
+use comrak::nodes::{AstNode, NodeValue};
+
+// Resolves relative link and image urls against the directory of the
+// document, like a browser would if the rendered page was served from there.
+// Urls in raw html are left untouched.
+pub fn resolve_document_dir<'a>(root: &'a AstNode<'a>, document_dir: &str) {
+    for node in root.descendants() {
+        let mut ast = node.data.borrow_mut();
+
+        if let NodeValue::Link(ref mut link) | NodeValue::Image(ref mut link) = ast.value {
+            if is_relative(&link.url) {
+                link.url = join(document_dir, &link.url);
+            }
+        }
+    }
+}
+
+fn is_relative(url: &str) -> bool {
+    !(url.is_empty() || url.starts_with(['/', '#', '?']) || has_scheme(url))
+}
+
+fn has_scheme(url: &str) -> bool {
+    match url.find([':', '/', '?', '#']) {
+        Some(end) if url[end..].starts_with(':') => {
+            let scheme = &url[..end];
+
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        _ => false,
+    }
+}
+
+// Joins the path of the url to the directory and removes `.` and `..`
+// segments, keeping the query and fragment as is.
+fn join(dir: &str, url: &str) -> String {
+    let (path, rest) = url.split_at(url.find(['?', '#']).unwrap_or(url.len()));
+    let absolute = dir.starts_with('/');
+    let mut segments: Vec<&str> = Vec::new();
+
+    for segment in dir.split('/').chain(path.split('/')) {
+        match segment {
+            "" | "." => (),
+            ".." if segments.last().is_some_and(|last| *last != "..") => {
+                segments.pop();
+            }
+            // can't go above the root of an absolute path
+            ".." if absolute => (),
+            segment => segments.push(segment),
+        }
+    }
+
+    let trailing_slash = matches!(path.rsplit('/').next(), Some("" | "." | ".."));
+
+    let mut joined = String::with_capacity(dir.len() + url.len() + 1);
+
+    if absolute {
+        joined.push('/');
+    }
+
+    joined.push_str(&segments.join("/"));
+
+    if trailing_slash && !segments.is_empty() {
+        joined.push('/');
+    }
+
+    if joined.is_empty() {
+        joined.push_str("./");
+    }
+
+    joined.push_str(rest);
+    joined
+}
@@ -0,0 +1,228 @@
+use crate::headings::collect_text;
+use crate::raw_html::{allow_injected_html, escape_html, html_block, html_inline};
+use crate::types::options::{ExVideoEmbedsOptions, ExVideoProvider};
+use ammonia::Builder;
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{Arena, ComrakOptions};
+use std::ptr;
+
+const YOUTUBE_EMBED: &str = "https://www.youtube-nocookie.com/embed/";
+const VIMEO_EMBED: &str = "https://player.vimeo.com/video/";
+
+struct Video {
+    provider: ExVideoProvider,
+    id: String,
+}
+
+impl Video {
+    fn src(&self) -> String {
+        match self.provider {
+            ExVideoProvider::Youtube => format!("{}{}", YOUTUBE_EMBED, self.id),
+            ExVideoProvider::Vimeo => format!("{}{}?dnt=1", VIMEO_EMBED, self.id),
+        }
+    }
+
+    fn default_title(&self) -> &'static str {
+        match self.provider {
+            ExVideoProvider::Youtube => "YouTube video",
+            ExVideoProvider::Vimeo => "Vimeo video",
+        }
+    }
+}
+
+// Replaces paragraphs with just a video url, or a `![title](youtube:ID)`
+// image, by an embed in the wrapper, and `youtube:` and `vimeo:` images
+// within text by a bare embed. Embeds use the privacy friendly player urls.
+pub fn apply<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    root: &'a AstNode<'a>,
+    options: &ExVideoEmbedsOptions,
+    comrak_options: &mut ComrakOptions,
+) {
+    let mut blocks = Vec::new();
+    let mut inlines = Vec::new();
+
+    for node in root.descendants() {
+        match node.data.borrow().value {
+            NodeValue::Paragraph => {
+                if let Some(video) = paragraph_video(node, options) {
+                    blocks.push((node, video));
+                }
+            }
+            NodeValue::Image(ref link) => {
+                if let Some(video) = scheme_video(&link.url, options) {
+                    inlines.push((node, video));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    inlines.retain(|(node, _)| {
+        !blocks
+            .iter()
+            .any(|(block, _)| node.parent().is_some_and(|parent| ptr::eq(parent, *block)))
+    });
+
+    if blocks.is_empty() && inlines.is_empty() {
+        return;
+    }
+
+    allow_injected_html(root, comrak_options);
+
+    for (node, video) in blocks {
+        let iframe = iframe(&video, &title(node, &video));
+        let html = match options.wrapper_class {
+            Some(ref class) => format!("<div class=\"{}\">{}</div>\n", escape_html(class), iframe),
+            None => format!("{}\n", iframe),
+        };
+
+        node.insert_before(html_block(arena, html));
+        node.detach();
+    }
+
+    for (node, video) in inlines {
+        node.insert_before(html_inline(arena, iframe(&video, &title(node, &video))));
+        node.detach();
+    }
+}
+
+// Lets the embeds through the sanitizer, see `keep_attribute`.
+pub fn allow_iframes(builder: &mut Builder, options: &ExVideoEmbedsOptions) {
+    builder
+        .add_tags(["iframe"])
+        .add_tag_attributes("iframe", ["src", "title", "loading", "allowfullscreen"]);
+
+    if options.wrapper_class.is_some() {
+        builder
+            .add_tags(["div"])
+            .add_tag_attributes("div", ["class"]);
+    }
+}
+
+// Iframes are only kept with the player urls of the allowed providers, and
+// the wrapper is the only class allowed on divs.
+pub fn keep_attribute(
+    element: &str,
+    attribute: &str,
+    value: &str,
+    options: &ExVideoEmbedsOptions,
+) -> bool {
+    match (element, attribute) {
+        ("iframe", "src") => options.providers.iter().any(|provider| match provider {
+            ExVideoProvider::Youtube => value.starts_with(YOUTUBE_EMBED),
+            ExVideoProvider::Vimeo => value.starts_with(VIMEO_EMBED),
+        }),
+        ("div", "class") => options.wrapper_class.as_deref() == Some(value),
+        _ => true,
+    }
+}
+
+fn iframe(video: &Video, title: &str) -> String {
+    format!(
+        "<iframe src=\"{}\" title=\"{}\" loading=\"lazy\" allowfullscreen></iframe>",
+        escape_html(&video.src()),
+        escape_html(title)
+    )
+}
+
+fn title<'a>(node: &'a AstNode<'a>, video: &Video) -> String {
+    let text = match node.data.borrow().value {
+        NodeValue::Image(_) => collect_text(node),
+        _ => node
+            .first_child()
+            .filter(|child| matches!(child.data.borrow().value, NodeValue::Image(_)))
+            .map(collect_text)
+            .unwrap_or_default(),
+    };
+
+    match text.trim().is_empty() {
+        true => video.default_title().to_string(),
+        false => text.trim().to_string(),
+    }
+}
+
+// A paragraph with a single bare url, autolinked or not, or a single image.
+fn paragraph_video<'a>(node: &'a AstNode<'a>, options: &ExVideoEmbedsOptions) -> Option<Video> {
+    let child = node.first_child()?;
+
+    if child.next_sibling().is_some() {
+        return None;
+    }
+
+    let ast = child.data.borrow();
+
+    match ast.value {
+        NodeValue::Text(ref text) => url_video(text.trim(), options),
+        NodeValue::Link(ref link) if collect_text(child) == link.url => {
+            url_video(&link.url, options)
+        }
+        NodeValue::Image(ref link) => scheme_video(&link.url, options),
+        _ => None,
+    }
+}
+
+fn scheme_video(url: &str, options: &ExVideoEmbedsOptions) -> Option<Video> {
+    let (scheme, id) = url.split_once(':')?;
+
+    let provider = match scheme {
+        "youtube" => ExVideoProvider::Youtube,
+        "vimeo" => ExVideoProvider::Vimeo,
+        _ => return None,
+    };
+
+    video(provider, id, options)
+}
+
+fn url_video(url: &str, options: &ExVideoEmbedsOptions) -> Option<Video> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let host = host
+        .strip_prefix("www.")
+        .or_else(|| host.strip_prefix("m."))
+        .unwrap_or(host);
+
+    let (provider, id) = match host {
+        "youtube.com" => {
+            let id = match path.strip_prefix("watch?") {
+                Some(query) => query
+                    .split('&')
+                    .find_map(|param| param.strip_prefix("v="))?,
+                None => path
+                    .strip_prefix("embed/")
+                    .or_else(|| path.strip_prefix("shorts/"))?,
+            };
+
+            (ExVideoProvider::Youtube, id)
+        }
+        "youtu.be" => (ExVideoProvider::Youtube, path),
+        "vimeo.com" => (ExVideoProvider::Vimeo, path),
+        "player.vimeo.com" => (ExVideoProvider::Vimeo, path.strip_prefix("video/")?),
+        _ => return None,
+    };
+
+    let id = id.split(['?', '#', '&', '/']).next().unwrap_or_default();
+    video(provider, id, options)
+}
+
+fn video(provider: ExVideoProvider, id: &str, options: &ExVideoEmbedsOptions) -> Option<Video> {
+    let valid = match provider {
+        ExVideoProvider::Youtube => {
+            id.len() == 11
+                && id
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_'))
+        }
+        ExVideoProvider::Vimeo => !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()),
+    };
+
+    match valid && options.providers.contains(&provider) {
+        true => Some(Video {
+            provider,
+            id: id.to_string(),
+        }),
+        false => None,
+    }
+}
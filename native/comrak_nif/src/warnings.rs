@@ -0,0 +1,223 @@
+use crate::data_urls;
+use crate::grammar_registry;
+use crate::headings::collect_text;
+use crate::raw_html::is_dangerous_url;
+use crate::text_units;
+use crate::types::options::{ExFeaturesOptions, ExHeadingLintOptions};
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::ComrakOptions;
+use std::collections::HashMap;
+
+// Non-fatal issues found while rendering, returned in the render metadata
+// instead of being silently ignored.
+
+#[derive(Debug, NifUnitEnum)]
+pub enum ExWarningKind {
+    UnknownLanguage,
+    DroppedUrl,
+    UndefinedFootnote,
+    TooManySpans,
+    DuplicateHeading,
+    LongHeading,
+}
+
+#[derive(Debug, NifMap)]
+pub struct ExWarning {
+    pub kind: ExWarningKind,
+    pub message: String,
+    pub sourcepos: ((usize, usize), (usize, usize)),
+}
+
+// Must be called before the passes that change the document.
+pub fn collect<'a>(
+    root: &'a AstNode<'a>,
+    options: &ComrakOptions,
+    features: &ExFeaturesOptions,
+) -> Vec<ExWarning> {
+    let mut warnings = Vec::new();
+    // `(level, text)` of the headings seen so far and their line
+    let mut headings: HashMap<(u8, String), usize> = HashMap::new();
+
+    for node in root.descendants() {
+        let ast = node.data.borrow();
+
+        match ast.value {
+            NodeValue::Heading(ref heading) => {
+                if let Some(ref lint) = features.heading_lint {
+                    lint_heading(node, heading.level, lint, &mut headings, &mut warnings);
+                }
+            }
+            NodeValue::CodeBlock(ref block) if block.fenced => {
+                let lang = block.info.split_whitespace().next().unwrap_or_default();
+
+                if features.syntax_highlight_theme.is_some()
+                    && !lang.is_empty()
+                    && grammar_registry::config(lang).is_none()
+                {
+                    warnings.push(warning(
+                        node,
+                        ExWarningKind::UnknownLanguage,
+                        format!("unknown language {}, highlighted as plain text", lang),
+                    ));
+                }
+            }
+            NodeValue::Link(ref link) | NodeValue::Image(ref link) => {
+                let dropped_data_image = matches!(ast.value, NodeValue::Image(_))
+                    && features
+                        .data_images
+                        .as_ref()
+                        .is_some_and(|options| data_urls::is_dropped(&link.url, options));
+
+                if dropped_data_image || (!options.render.unsafe_ && is_dangerous_url(&link.url)) {
+                    warnings.push(warning(
+                        node,
+                        ExWarningKind::DroppedUrl,
+                        format!("url {} removed by the scheme policy", truncate(&link.url)),
+                    ));
+                }
+            }
+            NodeValue::Text(_) if options.extension.footnotes => {
+                // unmatched references stay as text, which may be split
+                // across adjacent text nodes
+                if node.previous_sibling().is_some_and(|previous| {
+                    matches!(previous.data.borrow().value, NodeValue::Text(_))
+                }) {
+                    continue;
+                }
+
+                for label in undefined_footnotes(&text_run(node)) {
+                    warnings.push(warning(
+                        node,
+                        ExWarningKind::UndefinedFootnote,
+                        format!("footnote [^{}] has no definition", label),
+                    ));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    warnings
+}
+
+// For a code block rendered as plain text by the highlighter.
+pub fn too_many_spans<'a>(node: &'a AstNode<'a>, spans: usize, max_spans: usize) -> ExWarning {
+    warning(
+        node,
+        ExWarningKind::TooManySpans,
+        format!(
+            "{} highlight spans, over the limit of {}, highlighted as plain text",
+            spans, max_spans
+        ),
+    )
+}
+
+// Headings over the length limit, or with the text of a previous heading of
+// the same level.
+fn lint_heading<'a>(
+    node: &'a AstNode<'a>,
+    level: u8,
+    lint: &ExHeadingLintOptions,
+    headings: &mut HashMap<(u8, String), usize>,
+    warnings: &mut Vec<ExWarning>,
+) {
+    let text = collect_text(node).trim().to_string();
+    let length = text_units::count(&text, &lint.unit);
+
+    if let Some(max_length) = lint.max_length.filter(|max_length| length > *max_length) {
+        warnings.push(warning(
+            node,
+            ExWarningKind::LongHeading,
+            format!(
+                "heading is {} {} long, over the limit of {}",
+                length,
+                text_units::name(&lint.unit),
+                max_length
+            ),
+        ));
+    }
+
+    if !lint.duplicates {
+        return;
+    }
+
+    match headings.get(&(level, text.clone())) {
+        Some(first_line) => warnings.push(warning(
+            node,
+            ExWarningKind::DuplicateHeading,
+            format!(
+                "heading {} duplicates the level {} heading on line {}",
+                text, level, first_line
+            ),
+        )),
+        None => {
+            let line = node.data.borrow().sourcepos.start.line;
+            headings.insert((level, text), line);
+        }
+    }
+}
+
+fn warning<'a>(node: &'a AstNode<'a>, kind: ExWarningKind, message: String) -> ExWarning {
+    ExWarning {
+        kind,
+        message,
+        sourcepos: sourcepos(node),
+    }
+}
+
+// Inline nodes may not have a position, in which case the position of the
+// enclosing block is used.
+fn sourcepos<'a>(node: &'a AstNode<'a>) -> ((usize, usize), (usize, usize)) {
+    node.ancestors()
+        .map(|node| node.data.borrow().sourcepos)
+        .find(|sourcepos| sourcepos.start.line > 0)
+        .map_or(((0, 0), (0, 0)), |sourcepos| {
+            (
+                (sourcepos.start.line, sourcepos.start.column),
+                (sourcepos.end.line, sourcepos.end.column),
+            )
+        })
+}
+
+fn text_run<'a>(node: &'a AstNode<'a>) -> String {
+    let mut text = String::new();
+    let mut next = Some(node);
+
+    while let Some(node) = next {
+        match node.data.borrow().value {
+            NodeValue::Text(ref literal) => text.push_str(literal),
+            _ => break,
+        }
+
+        next = node.next_sibling();
+    }
+
+    text
+}
+
+fn undefined_footnotes(text: &str) -> Vec<&str> {
+    let mut labels = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("[^") {
+        rest = &rest[start + 2..];
+
+        if let Some(end) = rest.find(']') {
+            let label = &rest[..end];
+
+            if !label.is_empty() && !label.contains(|c: char| c.is_whitespace() || c == '[') {
+                labels.push(label);
+                rest = &rest[end + 1..];
+            }
+        }
+    }
+
+    labels
+}
+
+fn truncate(url: &str) -> &str {
+    match url.char_indices().nth(40) {
+        Some((index, _)) => &url[..index],
+        None => url,
+    }
+}
@@ -0,0 +1,86 @@
+use crate::raw_html::escape_html;
+use crate::render::RenderError;
+use crate::types::options::{ExFeaturesOptions, ExWrapper};
+
+// Surrounds the rendered fragment with `before_html` and `after_html`, inside
+// the `wrapper` element when set. A `lang` is added to the wrapper, or to a
+// `div` without one. Returns `None` when there's nothing to add.
+pub fn wrap(
+    html: &str,
+    features: &ExFeaturesOptions,
+    lang: Option<&str>,
+) -> Result<Option<String>, RenderError> {
+    let wrapper = match (&features.wrapper, lang) {
+        (Some(wrapper), Some(lang))
+            if !wrapper.attributes.iter().any(|(name, _)| name == "lang") =>
+        {
+            let mut wrapper = wrapper.clone();
+            wrapper
+                .attributes
+                .push(("lang".to_string(), lang.to_string()));
+            Some(wrapper)
+        }
+        (None, Some(lang)) => Some(ExWrapper {
+            tag: "div".to_string(),
+            attributes: vec![("lang".to_string(), lang.to_string())],
+        }),
+        (wrapper, _) => wrapper.clone(),
+    };
+
+    if features.before_html.is_none() && features.after_html.is_none() && wrapper.is_none() {
+        return Ok(None);
+    }
+
+    let mut wrapped = String::with_capacity(html.len());
+
+    if let Some(ref wrapper) = wrapper {
+        wrapped.push_str(&open_tag(wrapper)?);
+    }
+
+    wrapped.push_str(features.before_html.as_deref().unwrap_or_default());
+    wrapped.push_str(html);
+    wrapped.push_str(features.after_html.as_deref().unwrap_or_default());
+
+    if let Some(ref wrapper) = wrapper {
+        wrapped.push_str(&format!("</{}>\n", wrapper.tag));
+    }
+
+    Ok(Some(wrapped))
+}
+
+// Values are escaped, but names can't be, so they're validated instead.
+fn open_tag(wrapper: &ExWrapper) -> Result<String, RenderError> {
+    let mut chars = wrapper.tag.chars();
+    let valid_tag = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-');
+
+    if !valid_tag {
+        return Err(RenderError::Other(format!(
+            "invalid wrapper tag: {}",
+            wrapper.tag
+        )));
+    }
+
+    let mut tag = format!("<{}", wrapper.tag);
+
+    for (name, value) in &wrapper.attributes {
+        let valid_name = !name.is_empty()
+            && name.chars().all(|c| {
+                !c.is_whitespace()
+                    && !c.is_control()
+                    && !matches!(c, '"' | '\'' | '<' | '>' | '/' | '=')
+            });
+
+        if !valid_name {
+            return Err(RenderError::Other(format!(
+                "invalid wrapper attribute: {}",
+                name
+            )));
+        }
+
+        tag.push_str(&format!(" {}=\"{}\"", name, escape_html(value)));
+    }
+
+    tag.push_str(">\n");
+    Ok(tag)
+}
@@ -8,6 +8,30 @@ defmodule MDExTest do
     assert html == expected
   end
 
+  defp rendered_headings(markdown, opts) do
+    ~r/<h([1-6])>(.*?)<\/h\1>/
+    |> Regex.scan(MDEx.to_html(markdown, opts), capture: :all_but_first)
+    |> Enum.map(fn [level, text] -> {String.to_integer(level), text} end)
+  end
+
+  defp outlined_headings(markdown, opts) do
+    markdown
+    |> MDEx.outline(opts)
+    |> Enum.map(&{&1.level, &1.text})
+  end
+
+  test "to_html/1 and to_html/2 share the same defaults" do
+    markdown = ~S"""
+    # MDEx
+
+    ```elixir
+    {:mdex, "~> 0.1"}
+    ```
+    """
+
+    assert MDEx.to_html(markdown) == MDEx.to_html(markdown, [])
+  end
+
   describe "syntax highlighting" do
     test "enabled by default" do
       assert_output(
@@ -80,5 +104,1637 @@ defmodule MDExTest do
         """
       )
     end
+
+    test "returns highlight errors" do
+      assert MDEx.Native.register_incompatible_grammar("mdex-incompatible") == :ok
+      code = "```mdex-incompatible\nIO.puts(:ok)\n```"
+
+      assert MDEx.to_html(code, []) == {:error, "syntax highlight failed: Invalid language"}
+      assert {:error, "syntax highlight failed: Invalid language"} = MDEx.to_html_with_metadata(code, [])
+      assert MDEx.to_html("```elixir\nIO.puts(:ok)\n```", []) =~ "<pre"
+    end
+  end
+
+  describe "binaries" do
+    test "renders sub-binaries and returns binaries" do
+      <<_prefix::binary-size(4), markdown::binary>> = "skip# Olá\n\n" <> String.duplicate("ção ", 100)
+
+      assert <<"<h1>Olá</h1>\n<p>ção ", _::binary>> = html = MDEx.to_html(markdown)
+      assert is_binary(html)
+      assert MDEx.to_html(markdown, []) == html
+    end
+
+    test "raises for invalid utf-8" do
+      assert_raise ArgumentError, fn -> MDEx.to_html(<<"# ", 0xFF>>) end
+      assert_raise ArgumentError, fn -> MDEx.to_html(<<"# ", 0xFF>>, []) end
+    end
+  end
+
+  describe "max_output_bytes" do
+    test "renders when output fits" do
+      assert_output("# MDEx", "<h1>MDEx</h1>\n", features: [max_output_bytes: 100])
+    end
+
+    test "returns error when output is too large" do
+      assert MDEx.to_html(String.duplicate("* a\n", 100), features: [max_output_bytes: 100]) ==
+               {:error, :output_too_large}
+    end
+
+    test "returns errors from highlighted code blocks and keeps rendering" do
+      code = "```elixir\n" <> String.duplicate("IO.puts(:ok)\n", 50) <> "```"
+
+      assert MDEx.to_html(code, features: [max_output_bytes: 200]) == {:error, :output_too_large}
+      assert MDEx.to_html(code) =~ "<pre"
+    end
+  end
+
+  describe "output buffer pool" do
+    test "reuses the buffer on the same thread and drops buffers over the limit" do
+      medium = String.duplicate("paragraph text\n\n", 500)
+      huge = String.duplicate("a\n\n", 200_000)
+
+      assert [medium_capacity, reused_capacity, nil, small_capacity] =
+               MDEx.Native.pooled_output_capacities([medium, "# MDEx", huge, "# MDEx"])
+
+      assert medium_capacity >= 11_000
+      assert reused_capacity == medium_capacity
+      assert small_capacity < medium_capacity
+    end
+  end
+
+  describe "description_list" do
+    @description_list ~S"""
+    Term
+
+    : Details
+    """
+
+    test "add classes" do
+      html =
+        MDEx.to_html(@description_list,
+          extension: [description_lists: true],
+          features: [description_list: [dl_class: "terms", dt_class: "term", dd_class: "details"]]
+        )
+
+      assert html =~ ~s(<dl class="terms">)
+      assert html =~ ~s(<dt class="term">)
+      assert html =~ ~s(<dd class="details">)
+    end
+
+    test "tight" do
+      html =
+        MDEx.to_html(@description_list,
+          extension: [description_lists: true],
+          features: [description_list: [tight: true]]
+        )
+
+      assert html =~ "<dd>"
+      refute html =~ "<p>"
+    end
+
+    test "keeps raw html omitted" do
+      html =
+        MDEx.to_html("<b>raw</b>\n\n" <> @description_list,
+          extension: [description_lists: true],
+          features: [description_list: [dl_class: "terms"]]
+        )
+
+      assert html =~ "<!-- raw HTML omitted -->"
+      refute html =~ "<b>raw</b>"
+    end
+  end
+
+  describe "to_html_with_metadata" do
+    test "was_sanitized" do
+      assert {:ok, "<p>safe</p>\n", %{was_sanitized: false}} =
+               MDEx.to_html_with_metadata("safe", features: [sanitize: true])
+
+      assert {:ok, "<p><em>unsafe</em></p>\n", %{was_sanitized: true}} =
+               MDEx.to_html_with_metadata("<em onclick=\"alert(1)\">unsafe</em>", render: [unsafe_: true], features: [sanitize: true])
+    end
+  end
+
+  describe "render_async" do
+    test "sends the result to the caller" do
+      job = MDEx.render_async("# MDEx", features: [max_output_bytes: 100])
+      assert_receive {:mdex, ^job, {:ok, "<h1>MDEx</h1>\n"}}
+    end
+
+    test "sends errors to the caller" do
+      job = MDEx.render_async(String.duplicate("* a\n", 100), features: [max_output_bytes: 100])
+      assert_receive {:mdex, ^job, {:error, :output_too_large}}
+    end
+
+    test "cancel" do
+      job = MDEx.render_async(String.duplicate("* a\n", 1_000_000))
+      assert MDEx.cancel(job) == :ok
+      refute_receive {:mdex, ^job, _}
+    end
+
+    test "sends an error when the render panics" do
+      job = MDEx.Native.render_async_panic()
+      assert_receive {:mdex, ^job, {:error, "render panicked: render_async_panic"}}
+    end
+
+    test "queues bursts of jobs" do
+      jobs = for index <- 1..1_000, do: {MDEx.render_async("# #{index}"), index}
+
+      for {job, index} <- jobs do
+        html = "<h1>#{index}</h1>\n"
+        assert_receive {:mdex, ^job, {:ok, ^html}}, 5_000
+      end
+    end
+  end
+
+  describe "render_batch_async" do
+    test "sends each result and an ordered summary" do
+      documents = ["# one", String.duplicate("a", 100), "# three"]
+      job = MDEx.render_batch_async(documents, max_concurrency: 2, features: [max_input_bytes: 10])
+
+      assert_receive {:mdex, ^job, {0, {:ok, "<h1>one</h1>\n"}}}
+      assert_receive {:mdex, ^job, {1, {:error, :input_too_large}}}
+      assert_receive {:mdex, ^job, {2, {:ok, "<h1>three</h1>\n"}}}
+      assert_receive {:mdex, ^job, {:done, [:ok, {:error, :input_too_large}, :ok]}}
+    end
+
+    test "sends the summary for empty batches" do
+      job = MDEx.render_batch_async([])
+      assert_receive {:mdex, ^job, {:done, []}}
+    end
+
+    test "cancel" do
+      job = MDEx.render_batch_async(List.duplicate(String.duplicate("* a\n", 100_000), 50), max_concurrency: 1)
+      assert MDEx.cancel(job) == :ok
+      refute_receive {:mdex, ^job, {:done, _}}
+    end
+  end
+
+  describe "outline" do
+    test "skips headings inside code blocks" do
+      markdown = ~S"""
+      # Title ##
+
+      ```markdown
+      # Not a heading
+      ```
+
+          # Indented code
+
+      Setext
+      ======
+
+      ### Section `code`
+      """
+
+      assert MDEx.outline(markdown) == [
+               %{level: 1, text: "Title", sourcepos: {{1, 1}, {1, 10}}},
+               %{level: 1, text: "Setext", sourcepos: {{9, 1}, {10, 6}}},
+               %{level: 3, text: "Section `code`", sourcepos: {{12, 1}, {12, 18}}}
+             ]
+    end
+
+    test "skips the front matter" do
+      markdown = "---\ntitle: x\n---\n\n# Title\n"
+      opts = [extension: [front_matter_delimiter: "---"]]
+
+      assert outlined_headings(markdown, opts) == [{1, "Title"}]
+      assert outlined_headings(markdown, opts) == rendered_headings(markdown, opts)
+      assert outlined_headings(markdown, []) == rendered_headings(markdown, [])
+    end
+
+    test "skips html blocks" do
+      markdown = ~S"""
+      <pre>
+      # Not a heading
+      </pre>
+
+      <div>
+      # Not a heading
+      Not a setext heading
+      ---
+      </div>
+
+      <!--
+      # Not a heading
+      -->
+
+      # Title
+
+      <custom-element>
+      # Not a heading
+
+      Section
+      -------
+      """
+
+      assert outlined_headings(markdown, []) == [{1, "Title"}, {2, "Section"}]
+      assert outlined_headings(markdown, []) == rendered_headings(markdown, render: [unsafe_: true])
+    end
+
+    test "skips headings inside list items and block quotes" do
+      markdown = ~S"""
+      - item
+
+        # Not top level
+
+      1. item
+         lazy continuation
+
+         ## Not top level
+
+      > quote
+      lazy continuation
+      >
+      > # Not top level
+
+      # Title
+      - item
+      ## Section
+      """
+
+      assert outlined_headings(markdown, []) == [{1, "Title"}, {2, "Section"}]
+    end
+  end
+
+  describe "metrics" do
+    test "collects stats across calls" do
+      metrics = MDEx.new_metrics()
+
+      MDEx.to_html("```elixir\n:ok\n```", features: [metrics: metrics])
+      MDEx.to_html("<script>alert(1)</script>", render: [unsafe_: true], features: [sanitize: true, metrics: metrics])
+
+      assert %{documents_rendered: 2, code_blocks_highlighted: 1, sanitized_documents: 1} = MDEx.get_metrics(metrics)
+    end
+  end
+
+  describe "entity_encoding" do
+    test "numeric" do
+      assert MDEx.to_html("Café & “quotes”", features: [entity_encoding: :numeric]) ==
+               "<p>Caf&#233; &#38; &#8220;quotes&#8221;</p>\n"
+    end
+
+    test "named" do
+      assert MDEx.to_html("Café & “quotes” ✓", features: [entity_encoding: :named]) ==
+               "<p>Caf&eacute; &amp; &ldquo;quotes&rdquo; &#10003;</p>\n"
+    end
+  end
+
+  describe "code_block_attributes" do
+    test "customizes pre and code attributes" do
+      opts = [
+        features: [
+          syntax_highlight_theme: "onedark",
+          code_block_attributes: [translate: nil, tabindex: 0, pre: %{"data-copy" => "true"}, code: %{"data-lang" => "txt"}]
+        ]
+      ]
+
+      assert MDEx.to_html("```\ntext\n```", opts) ==
+               ~s(<pre class="autumn highlight" style="background-color: #282C34; color: #ABB2BF;" data-copy="true"><code class="language-plain-text" tabindex="0" data-lang="txt">text\n</code></pre>\n)
+    end
+
+    test "escapes the language of the info string" do
+      markdown = ~s(```a"onmouseover="alert(1)\ntext\n```)
+
+      for opts <- [[], [features: [code_block_attributes: []]]] do
+        html = MDEx.to_html(markdown, opts)
+        assert html =~ ~s(<code class="language-a&quot;onmouseover=&quot;alert(1)")
+        refute html =~ ~s( onmouseover=")
+      end
+    end
+  end
+
+  describe "register_grammar" do
+    test "returns an error when the library can't be loaded" do
+      assert {:error, "failed to load /nonexistent/grammar.so" <> _} =
+               MDEx.register_grammar("missing", "/nonexistent/grammar.so", highlights_query: "")
+    end
+  end
+
+  describe "highlight_code_svg" do
+    test "colors tokens with the theme" do
+      svg = MDEx.highlight_code_svg("def a do\n  :ok\nend\n", "elixir", "onedark", font_size: 10, padding: 0)
+
+      assert svg =~ ~s(<svg xmlns="http://www.w3.org/2000/svg" width="48" height="45")
+      assert svg =~ ~r(<tspan x="0" y="10"><tspan fill="#\w+">def</tspan>)
+      assert svg =~ ~r(<tspan x="0" y="40"><tspan fill="#\w+">end</tspan></tspan></text></svg>$)
+    end
+  end
+
+  describe "input_limits" do
+    test "rejects deeply nested input" do
+      markdown = String.duplicate(">", 200) <> " quote"
+
+      assert MDEx.to_html(markdown, features: [input_limits: []]) == {:error, :pathological_input}
+      assert MDEx.to_html("> > quote", features: [input_limits: [max_nesting_depth: 1]]) == {:error, :pathological_input}
+    end
+
+    test "rejects too many link definitions" do
+      markdown = "[a]: /a\n[b]: /b\n\n[a] [b]"
+
+      assert MDEx.to_html(markdown, features: [input_limits: [max_link_definitions: 1]]) == {:error, :pathological_input}
+      assert MDEx.to_html(markdown, features: [input_limits: [max_link_definitions: 2]]) == "<p><a href=\"/a\">a</a> <a href=\"/b\">b</a></p>\n"
+    end
+  end
+
+  describe "heading_ids" do
+    @markdown "# Install\n## Usage\n# Upgrade\n## Usage\n"
+
+    test "numbers collisions" do
+      assert MDEx.to_html(@markdown, features: [heading_ids: [prefix: "doc-"]]) == """
+             <h1><a href="#doc-install" aria-hidden="true" class="anchor" id="doc-install"></a>Install</h1>
+             <h2><a href="#doc-usage" aria-hidden="true" class="anchor" id="doc-usage"></a>Usage</h2>
+             <h1><a href="#doc-upgrade" aria-hidden="true" class="anchor" id="doc-upgrade"></a>Upgrade</h1>
+             <h2><a href="#doc-usage-1" aria-hidden="true" class="anchor" id="doc-usage-1"></a>Usage</h2>
+             """
+    end
+
+    test "hashes collisions" do
+      assert MDEx.to_html(@markdown, features: [heading_ids: [collisions: :hash]]) =~
+               ~s(<h2><a href="#usage-917990" aria-hidden="true" class="anchor" id="usage-917990"></a>Usage</h2>)
+    end
+
+    test "fails on collisions" do
+      assert MDEx.to_html(@markdown, features: [heading_ids: [collisions: :error]]) ==
+               {:error, {:duplicate_heading_id, "usage"}}
+    end
+
+    test "generates the same ids as header_ids" do
+      markdown = "# Cafe\u0301\n# नमस्ते दुनिया\n# snake_case x²\n"
+
+      assert MDEx.to_html(markdown, features: [heading_ids: []]) == MDEx.to_html(markdown, extension: [header_ids: ""])
+      assert MDEx.to_html(markdown, features: [heading_ids: []]) =~ ~s(id="cafe\u0301")
+    end
+
+    test "keeps the header_ids prefix" do
+      assert MDEx.to_html("# MDEx", extension: [header_ids: "user-content-"], features: [heading_ids: [suffix: "-1"]]) ==
+               ~s(<h1><a href="#user-content-mdex-1" aria-hidden="true" class="anchor" id="user-content-mdex-1"></a>MDEx</h1>\n)
+    end
+  end
+
+  describe "comments preset" do
+    test "renders inline content only" do
+      markdown = """
+      # Title
+
+      > **quoted** ![cat](cat.png) :rocket:
+
+      - https://elixir-lang.org
+
+      ```
+      <script>alert(1)</script>
+      ```
+      """
+
+      assert MDEx.to_html(markdown, preset: :comments) == """
+             <p>Title</p>
+             <p><strong>quoted</strong> cat 🚀</p>
+             <p><a href="https://elixir-lang.org" rel="nofollow noopener noreferrer">https://elixir-lang.org</a></p>
+             <p><code>&lt;script&gt;alert(1)&lt;/script&gt;</code></p>
+             """
+    end
+
+    test "limits the input size" do
+      assert MDEx.to_html(String.duplicate("a", 10_001), preset: :comments) == {:error, :input_too_large}
+      assert MDEx.to_html("abc", preset: :comments, features: [max_input_bytes: 2]) == {:error, :input_too_large}
+    end
+  end
+
+  describe "feed preset" do
+    test "renders feed safe html" do
+      markdown = """
+      # Title
+
+      See [docs](/docs) and ![logo](img/logo.png).
+
+      - [ ] task
+
+      <script>alert(1)</script><iframe src="https://example.com"></iframe><form><input name="q"></form>
+      """
+
+      html = MDEx.to_html(markdown, preset: :feed, features: [base_url: "https://example.com/blog/"])
+
+      assert html =~ "<h1>Title</h1>"
+      assert html =~ ~s(<a href="https://example.com/docs" rel="noopener noreferrer">docs</a>)
+      assert html =~ ~s(<img src="https://example.com/blog/img/logo.png" alt="logo">)
+      assert html =~ "<li>[ ] task</li>"
+      refute html =~ ~r/<script|<iframe|<form|<input/
+    end
+
+    test "removes relative urls without base_url" do
+      assert MDEx.to_html("[docs](/docs)", preset: :feed) == ~s(<p><a rel="noopener noreferrer">docs</a></p>\n)
+    end
+
+    test "keeps inline highlight styles" do
+      assert MDEx.to_html("```elixir\n:ok\n```", preset: :feed) =~ ~s(<span style="color: #98C379;">:ok</span>)
+    end
+
+    test "returns an error for invalid base_url" do
+      assert {:error, "invalid base_url: " <> _} = MDEx.to_html("text", preset: :feed, features: [base_url: "not a url"])
+    end
+  end
+
+  describe "hardened preset" do
+    test "escapes raw html and sanitizes the output" do
+      html = MDEx.to_html("<script>alert(1)</script>\n\n[x](javascript:alert(1))", preset: :hardened)
+
+      assert html =~ "&lt;script&gt;alert(1)&lt;/script&gt;"
+      assert html =~ ~s(rel="noopener noreferrer")
+      refute html =~ "javascript"
+    end
+
+    test "enables the limits" do
+      assert MDEx.to_html(String.duplicate("a", 100_001), preset: :hardened) == {:error, :input_too_large}
+      assert MDEx.to_html(String.duplicate(">", 40) <> " deep", preset: :hardened) == {:error, :pathological_input}
+      assert MDEx.to_html("text", preset: :hardened, features: [timeout: 0]) == {:error, :timeout}
+    end
+
+    test "keeps the limits that were set" do
+      assert MDEx.to_html(String.duplicate(">", 40) <> " deep", preset: :hardened, features: [input_limits: []]) =~ "<blockquote>"
+    end
+  end
+
+  describe "timeout" do
+    test "returns an error when rendering takes too long" do
+      assert MDEx.to_html("# Title", features: [timeout: 0]) == {:error, :timeout}
+      assert MDEx.to_html("# Title", features: [timeout: 1_000]) == "<h1>Title</h1>\n"
+    end
+  end
+
+  describe "relative_urls" do
+    @rules [{"/assets/", "https://cdn.example.com/"}, {~r/^(.+)\.md$/, "$1.html"}]
+
+    test "rewrites relative urls with the first rule that matches" do
+      markdown = "[a](/assets/a.png) [b](guide.md) [c](other) [d](https://example.com/assets/x.md)"
+
+      assert MDEx.to_html(markdown, features: [sanitize: true, relative_urls: @rules]) ==
+               ~s(<p><a href="https://cdn.example.com/a.png" rel="noopener noreferrer">a</a> ) <>
+                 ~s(<a href="guide.html" rel="noopener noreferrer">b</a> ) <>
+                 ~s(<a href="other" rel="noopener noreferrer">c</a> ) <>
+                 ~s(<a href="https://example.com/assets/x.md" rel="noopener noreferrer">d</a></p>\n)
+    end
+
+    test "falls back to base_url and the preset policy" do
+      assert MDEx.to_html("[a](/assets/a.png) [c](other)",
+               features: [sanitize: true, base_url: "https://example.com/docs/", relative_urls: @rules]
+             ) ==
+               ~s(<p><a href="https://cdn.example.com/a.png" rel="noopener noreferrer">a</a> ) <>
+                 ~s(<a href="https://example.com/docs/other" rel="noopener noreferrer">c</a></p>\n)
+
+      assert MDEx.to_html("[c](other)", preset: :feed, features: [relative_urls: @rules]) ==
+               ~s(<p><a rel="noopener noreferrer">c</a></p>\n)
+    end
+
+    test "returns an error for invalid rules" do
+      assert MDEx.to_html("text", features: [sanitize: true, relative_urls: [{"", "x"}]]) ==
+               {:error, "invalid relative_urls pattern: empty string"}
+    end
+  end
+
+  describe "attribute_rules" do
+    test "keeps, drops and rewrites the attributes kept by the sanitizer" do
+      rules = [{"img", "src", {:rewrite, "https://cdn.example.com/images/{value}"}}, {"a", "title", :keep}, {"*", "title", :drop}]
+
+      assert MDEx.to_html(~s(![cat](cat.png "Cat") [docs](/docs "Docs")), features: [sanitize: true, attribute_rules: rules]) ==
+               ~s(<p><img src="https://cdn.example.com/images/cat.png" alt="cat"> ) <>
+                 ~s(<a href="/docs" title="Docs" rel="noopener noreferrer">docs</a></p>\n)
+    end
+
+    test "doesn't keep attributes removed by the sanitizer" do
+      features = [sanitize: true, attribute_rules: [{"p", "onclick", :keep}]]
+      assert MDEx.to_html(~s(<p onclick="x()">hi</p>), render: [unsafe_: true], features: features) == "<p>hi</p>\n"
+    end
+
+    test "removes rewritten urls that move to another host" do
+      features = [sanitize: true, attribute_rules: [{"img", "src", {:rewrite, "https://cdn.example.com{value}"}}]]
+      assert MDEx.to_html("![x](@evil.com/x.png)", features: features) == ~s(<p><img alt="x"></p>\n)
+    end
+
+    test "doesn't rewrite absolute urls" do
+      features = [sanitize: true, attribute_rules: [{"img", "src", {:rewrite, "https://cdn.example.com{value}"}}]]
+
+      assert MDEx.to_html("![x](https://example.org/x.png)", features: features) ==
+               ~s(<p><img src="https://example.org/x.png" alt="x"></p>\n)
+    end
+
+    test "checks the scheme of rewritten urls" do
+      features = [sanitize: true, attribute_rules: [{"a", "href", {:rewrite, "javascript:{value}"}}]]
+      assert MDEx.to_html("[x](alert)", features: features) == ~s(<p><a rel="noopener noreferrer">x</a></p>\n)
+    end
+  end
+
+  describe "number_headings" do
+    @markdown "# Manual\n## Install\n### Linux\n## Usage\n# Appendix\n## Notes\n"
+
+    test "numbers headings in range" do
+      assert MDEx.to_html(@markdown, features: [number_headings: [levels: 2..3, format: "1.1) "]]) == """
+             <h1>Manual</h1>
+             <h2>1) Install</h2>
+             <h3>1.1) Linux</h3>
+             <h2>2) Usage</h2>
+             <h1>Appendix</h1>
+             <h2>1) Notes</h2>
+             """
+    end
+
+    test "optionally numbers anchors" do
+      assert MDEx.to_html("## Install", extension: [header_ids: ""], features: [number_headings: []]) ==
+               ~s(<h2><a href="#install" aria-hidden="true" class="anchor" id="install"></a>1 Install</h2>\n)
+
+      assert MDEx.to_html("## Install", extension: [header_ids: ""], features: [number_headings: [anchors: true]]) ==
+               ~s(<h2><a href="#1-install" aria-hidden="true" class="anchor" id="1-install"></a>1 Install</h2>\n)
+    end
+  end
+
+  describe "images_missing_alt" do
+    test "finds images in nested blocks" do
+      markdown = """
+      > ![ ](a.png "A")
+
+      - ![](b.png) ![ok](c.png)
+      """
+
+      assert MDEx.images_missing_alt(markdown) == [
+               %{url: "a.png", title: "A", sourcepos: {{1, 3}, {1, 17}}},
+               %{url: "b.png", title: "", sourcepos: {{3, 3}, {3, 25}}}
+             ]
+    end
+  end
+
+  describe "seo_metadata" do
+    test "extracts metadata with relative urls resolved against base_url" do
+      markdown = """
+      ![Cover](cover.png "Cover")
+
+      ## Intro
+
+      # The *Title*
+
+      A description with `code`
+      over two lines and ![inline](inline.png).
+
+      - [About](../about) [Home](/#top) [Home](/) [Mail](mailto:a@example.com)
+      """
+
+      assert MDEx.seo_metadata(markdown, features: [base_url: "https://example.com/blog/post"]) == %{
+               title: "The Title",
+               description: "A description with code over two lines and .",
+               images: [
+                 %{url: "https://example.com/blog/cover.png", alt: "Cover", title: "Cover"},
+                 %{url: "https://example.com/blog/inline.png", alt: "inline", title: ""}
+               ],
+               links: ["https://example.com/about", "https://example.com/"]
+             }
+    end
+
+    test "truncates the description by unit" do
+      markdown = "👩‍👩‍👧 family 👍🏽 ok"
+
+      assert MDEx.seo_metadata(markdown, description_length: 12).description == "👩‍👩‍👧 family 👍🏽…"
+      assert MDEx.seo_metadata(markdown, description_length: 14, unit: :char).description == "👩‍👩‍👧 family…"
+      assert MDEx.seo_metadata(markdown, description_length: 30, unit: :byte).description == "👩‍👩‍👧 family…"
+      assert MDEx.seo_metadata(markdown, description_length: 37, unit: :byte).description == markdown
+    end
+
+    test "returns nil without title or description" do
+      assert MDEx.seo_metadata("## Only\n\n> quoted") == %{title: nil, description: nil, images: [], links: []}
+    end
+  end
+
+  describe "document_dir" do
+    test "resolves relative urls" do
+      markdown = "[Intro](intro.md#setup) ![Logo](../images/logo.png) [Home](/) <https://elixir-lang.org>"
+
+      assert MDEx.to_html(markdown, features: [document_dir: "/docs/guides"]) ==
+               ~s(<p><a href="/docs/guides/intro.md#setup">Intro</a> <img src="/docs/images/logo.png" alt="Logo" /> <a href="/">Home</a> <a href="https://elixir-lang.org">https://elixir-lang.org</a></p>\n)
+    end
+  end
+
+  describe "audit" do
+    test "removes and reports violations" do
+      markdown = """
+      [click](javascript:alert(1))
+
+      <img src="cat.png" onerror="alert(1)">
+
+      <script>alert(1)</script>
+      """
+
+      assert {:ok, html, %{violations: violations}} =
+               MDEx.to_html_with_metadata(markdown, render: [unsafe_: true], features: [audit: true])
+
+      assert html == ~s(<p><a>click</a></p>\n<img src="cat.png">\n\n)
+
+      assert violations == [
+               %{kind: :javascript_url, snippet: ~s{href="javascript:alert(1)"}},
+               %{kind: :event_handler, snippet: ~s{onerror="alert(1)"}},
+               %{kind: :script, snippet: "<script>alert(1)</script>"}
+             ]
+    end
+  end
+
+  describe "render_many_to_html" do
+    test "wraps documents and keeps heading ids unique" do
+      documents = [{"intro", "# Setup"}, {"setup", "# Setup\n\n# Setup"}]
+
+      assert MDEx.render_many_to_html(documents, extension: [header_ids: ""]) == """
+             <div class="mdex-document" id="intro">
+             <h1><a href="#setup-1" aria-hidden="true" class="anchor" id="setup-1"></a>Setup</h1>
+             </div>
+             <div class="mdex-document" id="setup">
+             <h1><a href="#setup-2" aria-hidden="true" class="anchor" id="setup-2"></a>Setup</h1>
+             <h1><a href="#setup-3" aria-hidden="true" class="anchor" id="setup-3"></a>Setup</h1>
+             </div>
+             """
+    end
+
+    test "returns the first error" do
+      assert MDEx.render_many_to_html([{"a", "ok"}, {"b", "too large"}], features: [max_input_bytes: 5]) ==
+               {:error, :input_too_large}
+    end
+  end
+
+  describe "wrapper" do
+    test "wraps the sanitized fragment" do
+      html =
+        MDEx.to_html("hello<script>alert(1)</script>",
+          render: [unsafe_: true],
+          features: [
+            sanitize: true,
+            before_html: "<header>Notes</header>\n",
+            after_html: "<footer>End</footer>\n",
+            wrapper: {:article, class: "prose", "data-title": ~s("quoted")}
+          ]
+        )
+
+      assert html == """
+             <article class="prose" data-title="&quot;quoted&quot;">
+             <header>Notes</header>
+             <p>hello</p>
+             <footer>End</footer>
+             </article>
+             """
+    end
+
+    test "rejects invalid tags" do
+      assert MDEx.to_html("hello", features: [wrapper: {"div onclick=alert(1)", []}]) ==
+               {:error, "invalid wrapper tag: div onclick=alert(1)"}
+    end
+  end
+
+  describe "anchor_report" do
+    test "reports anchors and collisions" do
+      documents = [{"a.md", "# Intro\n# Intro"}, {"b.md", "# Intro"}, {"c.md", "# Other"}]
+
+      assert {:ok, %{anchors: anchors, collisions: collisions}} =
+               MDEx.anchor_report(documents, extension: [header_ids: "doc-"])
+
+      assert anchors == [
+               %{path: "a.md", id: "doc-intro", url: "a.md#doc-intro", level: 1, text: "Intro"},
+               %{path: "a.md", id: "doc-intro-1", url: "a.md#doc-intro-1", level: 1, text: "Intro"},
+               %{path: "b.md", id: "doc-intro", url: "b.md#doc-intro", level: 1, text: "Intro"},
+               %{path: "c.md", id: "doc-other", url: "c.md#doc-other", level: 1, text: "Other"}
+             ]
+
+      assert collisions == [%{id: "doc-intro", paths: ["a.md", "b.md"]}]
+    end
+
+    test "ids match the rendered ids" do
+      markdown = "# Intro\n## K8s (tm)\n## Intro"
+      opts = [extension: [header_ids: ""], features: [id_prefix: "p-", replacements: [{"K8s", "Kubernetes"}]]]
+
+      rendered_ids =
+        ~r/ id="([^"]+)"/
+        |> Regex.scan(MDEx.to_html(markdown, opts), capture: :all_but_first)
+        |> List.flatten()
+
+      assert {:ok, %{anchors: anchors}} = MDEx.anchor_report([{"a.md", markdown}], opts)
+      assert Enum.map(anchors, & &1.id) == rendered_ids
+      assert rendered_ids == ["p-intro", "p-kubernetes-tm", "p-intro-1"]
+    end
+  end
+
+  describe "data_images" do
+    @png "data:image/png;base64,iVBORw0KGgo="
+
+    test "keeps allowed images when sanitizing" do
+      assert MDEx.to_html("![logo](#{@png})", features: [sanitize: true]) =~ ~s(<img alt="logo">)
+      assert MDEx.to_html("![logo](#{@png})", features: [sanitize: true, data_images: []]) =~ ~s(src="#{@png}")
+    end
+
+    test "clears images over the limit" do
+      assert MDEx.to_html("![logo](#{@png})", features: [data_images: [max_bytes: 4]]) ==
+               ~s(<p><img src="" alt="logo" /></p>\n)
+    end
+
+    test "clears images of other types" do
+      assert MDEx.to_html("![logo](#{@png})", features: [data_images: [mime_types: ["image/webp"]]]) ==
+               ~s(<p><img src="" alt="logo" /></p>\n)
+    end
+  end
+
+  describe "cache" do
+    test "skips rendering cached documents" do
+      cache = MDEx.new_cache()
+      metrics = MDEx.new_metrics()
+      opts = [features: [cache: cache, metrics: metrics]]
+
+      assert MDEx.to_html("# MDEx", opts) == "<h1>MDEx</h1>\n"
+      assert MDEx.to_html("# MDEx", opts) == "<h1>MDEx</h1>\n"
+      assert %{documents_rendered: 1} = MDEx.get_metrics(metrics)
+
+      assert MDEx.to_html("# MDEx", Keyword.put(opts, :render, hardbreaks: true)) == "<h1>MDEx</h1>\n"
+      assert %{documents_rendered: 2} = MDEx.get_metrics(metrics)
+    end
+
+    test "evicts the least recently used document" do
+      cache = MDEx.new_cache(max_entries: 1)
+      metrics = MDEx.new_metrics()
+
+      for markdown <- ["a", "b", "a"] do
+        MDEx.to_html(markdown, features: [cache: cache, metrics: metrics])
+      end
+
+      assert %{documents_rendered: 3} = MDEx.get_metrics(metrics)
+    end
+
+    test "expires documents" do
+      cache = MDEx.new_cache(ttl: 10)
+      metrics = MDEx.new_metrics()
+
+      MDEx.to_html("a", features: [cache: cache, metrics: metrics])
+      Process.sleep(20)
+      MDEx.to_html("a", features: [cache: cache, metrics: metrics])
+
+      assert %{documents_rendered: 2} = MDEx.get_metrics(metrics)
+    end
+  end
+
+  describe "unknown themes" do
+    test "returns similar themes" do
+      assert MDEx.to_html("```elixir\n:ok\n```", features: [syntax_highlight_theme: "onedrak"]) ==
+               {:error, {:unknown_theme, "onedrak", ["onedark"]}}
+    end
+
+    test "fails svg highlighting" do
+      assert MDEx.highlight_code_svg(":ok", "elixir", "onedrak") == {:error, "unknown theme: onedrak"}
+    end
+  end
+
+  describe "highlight_tokens" do
+    test "returns ranges and scopes" do
+      source = "# TODO: fix\n\"ok\""
+      tokens = MDEx.highlight_tokens(source, "elixir")
+
+      assert Enum.any?(tokens, &match?({{0, _}, "comment" <> _}, &1))
+      assert Enum.any?(tokens, &match?({{12, _}, "string" <> _}, &1))
+    end
+  end
+
+  describe "heading_path_at" do
+    @markdown """
+    # Guide
+
+    ## Install
+
+    ### Linux
+
+    Run it
+
+    ## Usage
+    """
+
+    test "returns the enclosing headings of a position" do
+      assert [%{text: "Guide"}, %{text: "Install"}, %{text: "Linux", level: 3, sourcepos: {{5, 1}, _}}] =
+               MDEx.heading_path_at(@markdown, {7, 1})
+
+      assert MDEx.heading_path_at("Intro\n\n# Guide", {1, 1}) == []
+    end
+
+    test "returns the headings up to an anchor" do
+      assert [%{id: "doc-guide"}, %{id: "doc-usage"}] =
+               MDEx.heading_path_at(@markdown, "doc-usage", extension: [header_ids: "doc-"])
+
+      assert MDEx.heading_path_at(@markdown, "missing") == []
+    end
+  end
+
+  describe "responsive_images" do
+    test "adds srcset and sizes" do
+      assert MDEx.to_html(~s{![A "cat"](/cat.png "Cat")},
+               features: [responsive_images: [widths: [480, 960], url: "/img{url}?w={width}", sizes: "100vw"]]
+             ) ==
+               ~s(<p><img src="/cat.png" srcset="/img/cat.png?w=480 480w, /img/cat.png?w=960 960w" sizes="100vw" alt="A &quot;cat&quot;" title="Cat" /></p>\n)
+    end
+
+    test "keeps removing unsafe urls" do
+      assert MDEx.to_html("![x](javascript:alert(1)) [y](javascript:alert(1))", features: [responsive_images: []]) ==
+               ~s(<p><img src="" alt="x" /> <a href="">y</a></p>\n)
+    end
+  end
+
+  describe "video_embeds" do
+    @embed ~s(<iframe src="https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ" title="YouTube video" loading="lazy" allowfullscreen></iframe>)
+
+    test "embeds bare urls" do
+      assert MDEx.to_html("https://www.youtube.com/watch?v=dQw4w9WgXcQ", features: [video_embeds: []]) ==
+               ~s(<div class="video-embed">#{@embed}</div>\n)
+
+      assert MDEx.to_html("https://youtu.be/dQw4w9WgXcQ", extension: [autolink: true], features: [video_embeds: [wrapper_class: nil]]) ==
+               @embed <> "\n"
+    end
+
+    test "embeds images with a video scheme" do
+      assert MDEx.to_html("![Talk](vimeo:12345)", features: [video_embeds: [wrapper_class: nil]]) ==
+               ~s(<iframe src="https://player.vimeo.com/video/12345?dnt=1" title="Talk" loading="lazy" allowfullscreen></iframe>\n)
+    end
+
+    test "only embeds allowed providers" do
+      assert MDEx.to_html("https://vimeo.com/12345", features: [video_embeds: [providers: [:youtube]]]) ==
+               "<p>https://vimeo.com/12345</p>\n"
+    end
+
+    test "keeps embeds when sanitizing" do
+      html = MDEx.to_html("https://youtu.be/dQw4w9WgXcQ", features: [sanitize: true, video_embeds: []])
+
+      assert html =~ ~s(<div class="video-embed"><iframe src="https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ")
+      refute MDEx.to_html(~s(<iframe src="https://evil.example"></iframe>), render: [unsafe_: true], features: [sanitize: true, video_embeds: []]) =~ "evil"
+    end
+  end
+
+  describe "heading id sources" do
+    test "uses explicit ids" do
+      assert MDEx.to_html("# Instalación {#install_guide}\n# Uso", features: [heading_ids: [source: :explicit]]) == """
+             <h1><a href="#install_guide" aria-hidden="true" class="anchor" id="install_guide"></a>Instalación</h1>
+             <h1><a href="#uso" aria-hidden="true" class="anchor" id="uso"></a>Uso</h1>
+             """
+    end
+
+    test "uses ids from the front matter" do
+      markdown = """
+      ---
+      heading_ids:
+        "Instalación": install
+      ---
+      # Instalación
+      # Uso
+      """
+
+      assert MDEx.to_html(markdown,
+               extension: [front_matter_delimiter: "---"],
+               features: [heading_ids: [source: :front_matter_map]]
+             ) == """
+             <h1><a href="#install" aria-hidden="true" class="anchor" id="install"></a>Instalación</h1>
+             <h1><a href="#uso" aria-hidden="true" class="anchor" id="uso"></a>Uso</h1>
+             """
+    end
+  end
+
+  describe "sanitize_preview" do
+    test "lists removed elements, attributes and comments" do
+      html = """
+      <!-- note -->
+      <div>
+        <marquee>news</marquee>
+        <img src="cat.png" onerror="alert(1)">
+      </div>
+      """
+
+      assert %{html: sanitized, removed: removed} = MDEx.sanitize_preview(html)
+      refute sanitized =~ "marquee"
+
+      assert removed == [
+               %{kind: :comment, name: "", snippet: "<!-- note -->", line: 1, column: 1},
+               %{kind: :element, name: "marquee", snippet: "<marquee>", line: 3, column: 3},
+               %{kind: :attribute, name: "onerror", snippet: ~s{onerror="alert(1)"}, line: 4, column: 3}
+             ]
+    end
+
+    test "uses the preset policy" do
+      assert %{removed: [%{kind: :element, name: "h1"}]} = MDEx.sanitize_preview("<h1>Hi</h1>", preset: :comments)
+    end
+
+    test "lists urls removed by the policy" do
+      html = ~s{<a href="javascript:alert(1)" onclick="x()">a</a><img src="data:image/png;base64,AAAA">}
+
+      assert %{removed: removed} = MDEx.sanitize_preview(html)
+
+      assert removed == [
+               %{kind: :url, name: "href", snippet: ~s{href="javascript:alert(1)"}, line: 1, column: 1},
+               %{kind: :attribute, name: "onclick", snippet: ~s{onclick="x()"}, line: 1, column: 1},
+               %{kind: :url, name: "src", snippet: ~s{src="data:image/png;base64,AAAA"}, line: 1, column: 50}
+             ]
+    end
+  end
+
+  describe "typography" do
+    test "normalizes whitespace in text" do
+      assert MDEx.to_html("Hello  ,\u00A0world ;\nkeep `a  ,`", features: [typography: []]) ==
+               "<p>Hello, world;\nkeep <code>a  ,</code></p>\n"
+    end
+
+    test "keeps numbers and disabled rules" do
+      assert MDEx.to_html("costs .5  ,", features: [typography: [collapse_spaces: false]]) ==
+               "<p>costs .5,</p>\n"
+
+      assert MDEx.to_html("Hi  !", features: [typography: [space_before_punctuation: false]]) ==
+               "<p>Hi !</p>\n"
+    end
+  end
+
+  describe "warnings" do
+    test "reports unknown languages, dropped urls and undefined footnotes" do
+      markdown = """
+      ```nope
+      code
+      ```
+
+      [link](javascript:alert(1)) and [^missing]
+      """
+
+      assert {:ok, _html, %{warnings: warnings}} = MDEx.to_html_with_metadata(markdown, extension: [footnotes: true])
+
+      assert [
+               %{kind: :unknown_language, sourcepos: {{1, 1}, {3, 3}}},
+               %{kind: :dropped_url, message: "url javascript:alert(1) removed by the scheme policy", sourcepos: {{5, 1}, _}},
+               %{kind: :undefined_footnote, message: "footnote [^missing] has no definition"}
+             ] = warnings
+    end
+
+    test "ignores urls allowed by the options" do
+      assert {:ok, _html, %{warnings: []}} = MDEx.to_html_with_metadata("[link](javascript:alert(1))", render: [unsafe_: true])
+    end
+  end
+
+  describe "toc" do
+    test "replaces the marker with links to the headings" do
+      assert MDEx.to_html("<!-- toc -->\n\n# Intro\n## Install\n# Usage", features: [toc: []]) == """
+             <nav class="toc">
+             <ul>
+             <li><a href="#intro">Intro</a>
+             <ul>
+             <li><a href="#install">Install</a></li>
+             </ul>
+             </li>
+             <li><a href="#usage">Usage</a></li>
+             </ul>
+             </nav>
+             <h1><a href="#intro" aria-hidden="true" class="anchor" id="intro"></a>Intro</h1>
+             <h2><a href="#install" aria-hidden="true" class="anchor" id="install"></a>Install</h2>
+             <h1><a href="#usage" aria-hidden="true" class="anchor" id="usage"></a>Usage</h1>
+             """
+    end
+
+    test "lists the levels in range" do
+      html = MDEx.to_html("# Title\n\n[[toc]]\n\n## One\n### Deep", features: [toc: [levels: 2..2], heading_ids: [prefix: "doc-"]])
+
+      assert html =~ ~s(<nav class="toc">\n<ul>\n<li><a href="#doc-one">One</a></li>\n</ul>\n</nav>\n)
+      refute html =~ "[[toc]]"
+    end
+  end
+
+  describe "link_titles" do
+    test "sets the destination as title" do
+      assert MDEx.to_html("[docs][ref] <https://example.com>\n\n[ref]: https://example.com/docs", features: [link_titles: true]) ==
+               ~s(<p><a href="https://example.com/docs" title="https://example.com/docs">docs</a> <a href="https://example.com">https://example.com</a></p>\n)
+    end
+
+    test "sets the footnote text as title" do
+      html = MDEx.to_html("Hi[^note]\n\n[^note]: A \"quoted\" note.", extension: [footnotes: true], features: [link_titles: true])
+
+      assert html =~ ~s(<a href="#fn1" id="fnref1" title="A &quot;quoted&quot; note.">1</a>)
+    end
+  end
+
+  describe "max_spans_per_block" do
+    test "renders blocks over the limit as plain text" do
+      markdown = "```elixir\n%{a: 1} <> \"b\"\n```"
+
+      assert {:ok, html, %{warnings: [%{kind: :too_many_spans, sourcepos: {{1, 1}, {3, 3}}}]}} =
+               MDEx.to_html_with_metadata(markdown, features: [max_spans_per_block: 1])
+
+      assert html =~ "%{a: 1} &lt;&gt; &quot;b&quot;\n</code>"
+      refute html =~ "<span"
+
+      assert {:ok, highlighted, %{warnings: []}} = MDEx.to_html_with_metadata(markdown, features: [max_spans_per_block: 1_000])
+      assert highlighted =~ "<span"
+    end
+  end
+
+  describe "headings_as" do
+    test "renders headings as divs" do
+      assert MDEx.to_html("# Title\n## Sub", features: [headings_as: :div]) == """
+             <div role="heading" aria-level="1">Title</div>
+             <div role="heading" aria-level="2">Sub</div>
+             """
+    end
+
+    test "keeps anchors" do
+      assert MDEx.to_html("# Title", extension: [header_ids: ""], features: [headings_as: :div]) ==
+               ~s(<div role="heading" aria-level="1"><a href="#title" aria-hidden="true" class="anchor" id="title"></a>Title</div>\n)
+    end
+
+    test "keeps the attributes when sanitizing" do
+      assert MDEx.to_html(~s(# Title\n<div role="button" aria-level="9">x</div>),
+               render: [unsafe_: true],
+               features: [headings_as: :div, sanitize: true]
+             ) == ~s(<div role="heading" aria-level="1">Title</div>\n<div>x</div>\n)
+    end
+  end
+
+  describe "short_links" do
+    test "shortens github urls" do
+      markdown = """
+      https://github.com/leandrocp/mdex/issues/12 https://github.com/leandrocp/mdex/pull/7/
+      https://github.com/leandrocp/mdex/commit/0265635a1b2c3d4e https://github.com/leandrocp/mdex/issues/12#issuecomment-1
+      """
+
+      assert MDEx.to_html(markdown, extension: [autolink: true], features: [short_links: []]) == """
+             <p><a href="https://github.com/leandrocp/mdex/issues/12">leandrocp/mdex#12</a> <a href="https://github.com/leandrocp/mdex/pull/7/">leandrocp/mdex#7</a>
+             <a href="https://github.com/leandrocp/mdex/commit/0265635a1b2c3d4e">0265635</a> <a href="https://github.com/leandrocp/mdex/issues/12#issuecomment-1">https://github.com/leandrocp/mdex/issues/12#issuecomment-1</a></p>
+             """
+    end
+
+    test "keeps links with text and disabled rules" do
+      assert MDEx.to_html("[issue](https://github.com/a/b/issues/1) <https://github.com/a/b/pull/2>", features: [short_links: [rules: [:github_issues]]]) ==
+               ~s(<p><a href="https://github.com/a/b/issues/1">issue</a> <a href="https://github.com/a/b/pull/2">https://github.com/a/b/pull/2</a></p>\n)
+    end
+  end
+
+  describe "detect_language" do
+    test "returns the language of the text" do
+      markdown = """
+      # Introducción
+
+      Este documento explica cómo instalar la biblioteca y cómo usarla en una aplicación de ejemplo.
+
+      ```elixir
+      def hello, do: :world
+      ```
+      """
+
+      assert {:ok, _html, %{language: %{lang: "es", name: "Spanish", reliable: true}}} =
+               MDEx.to_html_with_metadata(markdown, features: [detect_language: true])
+
+      assert {:ok, _html, %{language: nil}} = MDEx.to_html_with_metadata(markdown)
+    end
+  end
+
+  describe "lang" do
+    @english "This document explains how to install the library and how to use it in an example application."
+    @spanish "Este documento explica cómo instalar la biblioteca y cómo usarla en una aplicación de ejemplo."
+
+    test "adds the language tag" do
+      assert MDEx.to_html("Hi", features: [lang: [tag: "en"]]) == ~s(<div lang="en">\n<p>Hi</p>\n</div>\n)
+
+      assert MDEx.to_html("Hi", features: [lang: [tag: "en"], wrapper: {"article", class: "prose"}]) ==
+               ~s(<article class="prose" lang="en">\n<p>Hi</p>\n</article>\n)
+    end
+
+    test "detects the language" do
+      assert MDEx.to_html(@spanish, features: [lang: []]) == ~s(<div lang="es">\n<p>#{@spanish}</p>\n</div>\n)
+      assert MDEx.to_html("ok", features: [lang: []]) == "<p>ok</p>\n"
+    end
+
+    test "marks paragraphs in other languages" do
+      assert MDEx.to_html("#{@english}\n\n#{@spanish}", features: [lang: [per_block: true]]) ==
+               ~s(<div lang="en">\n<p>#{@english}</p>\n<p lang="es">#{@spanish}</p>\n</div>\n)
+    end
+  end
+
+  describe "heading anchor exclusion" do
+    test "skips marked and excluded headings" do
+      markdown = "# Contents {-}\n\n[[toc]]\n\n# Intro\n# Index {.no-anchor}\n# Changelog"
+
+      assert MDEx.to_html(markdown, features: [toc: [], heading_ids: [exclude: ["Changelog"]]]) == """
+             <h1>Contents</h1>
+             <nav class="toc">
+             <ul>
+             <li><a href="#intro">Intro</a></li>
+             </ul>
+             </nav>
+             <h1><a href="#intro" aria-hidden="true" class="anchor" id="intro"></a>Intro</h1>
+             <h1>Index</h1>
+             <h1>Changelog</h1>
+             """
+    end
+  end
+
+  describe "id_prefix" do
+    test "prefixes heading and footnote ids" do
+      html = MDEx.to_html("# Title\n\nHi[^1]\n\n[^1]: Note", extension: [header_ids: "", footnotes: true], features: [id_prefix: "post-"])
+
+      assert html =~ ~s(<a href="#post-title" aria-hidden="true" class="anchor" id="post-title"></a>Title)
+      assert html =~ ~s(<a href="#post-fn1" id="post-fnref1">1</a>)
+      assert html =~ ~s(<li id="post-fn1">)
+      assert html =~ ~s(<a href="#post-fnref1" class="footnote-backref">)
+    end
+
+    test "keeps the heading_ids prefix after the namespace" do
+      assert MDEx.to_html("# Title", features: [id_prefix: "post-", heading_ids: [prefix: "h-"]]) ==
+               ~s(<h1><a href="#post-h-title" aria-hidden="true" class="anchor" id="post-h-title"></a>Title</h1>\n)
+    end
+  end
+
+  describe "id_prefix with sanitize" do
+    test "keeps the heading anchors" do
+      html = MDEx.to_html("# Intro\n\n<a id=\"raw\">x</a>", extension: [header_ids: ""], render: [unsafe_: true], features: [sanitize: true, id_prefix: "post-"])
+
+      assert html =~ ~s(href="#post-intro")
+      assert html =~ ~s(id="post-intro")
+      assert html =~ ~s(id="post-raw")
+    end
+
+    test "keeps the toc links and anchors together" do
+      html = MDEx.to_html("<!-- toc -->\n\n# Intro", features: [sanitize: true, toc: [], id_prefix: "p-"])
+
+      assert html =~ ~s(href="#p-intro")
+      assert html =~ ~s(id="p-intro")
+    end
+
+    test "removes heading ids without a prefix" do
+      html = MDEx.to_html("# Intro", extension: [header_ids: ""], features: [sanitize: true])
+
+      assert html =~ ~s(href="#intro")
+      refute html =~ "id="
+    end
+
+    test "uses id_prefix as the single prefix" do
+      html = MDEx.to_html("<!-- toc -->\n\n# Intro", extension: [header_ids: "p-"], features: [sanitize: true, toc: [], id_prefix: "p-"])
+
+      assert html =~ ~s(href="#p-intro")
+      assert html =~ ~s(id="p-intro")
+      refute html =~ "p-p-intro"
+    end
+
+    test "returns an error for conflicting prefixes" do
+      assert MDEx.to_html("# Intro", extension: [header_ids: "h-"], features: [id_prefix: "p-"]) ==
+               {:error, ~s(id_prefix "p-" conflicts with the header_ids prefix "h-", set only one of them)}
+    end
+  end
+
+  describe "heading_template" do
+    test "renders headings from the template" do
+      template = ~s(<h{level} id="{id}" class="heading"><a href="#{id}">{content}</a></h{level}>)
+
+      assert MDEx.to_html("## Hello *world*", features: [heading_template: template]) ==
+               ~s(<h2 id="hello-world" class="heading"><a href="#hello-world">Hello <em>world</em></a></h2>\n)
+    end
+
+    test "returns an error without a single content placeholder" do
+      assert {:error, "invalid heading_template: " <> _} = MDEx.to_html("# Hi", features: [heading_template: "<h{level}>"])
+    end
+  end
+
+  describe "replacements" do
+    test "replaces strings and regexes in text" do
+      markdown = "Acme(tm) runs on k8s and K8s, `k8s` [k8s](https://k8s.io)"
+      replacements = [{"(tm)", "™"}, {~r/(?i)\bk8s\b/, "Kubernetes"}]
+
+      assert MDEx.to_html(markdown, features: [replacements: replacements]) ==
+               ~s(<p>Acme™ runs on Kubernetes and Kubernetes, <code>k8s</code> <a href="https://k8s.io">k8s</a></p>\n)
+    end
+
+    test "expands groups" do
+      assert MDEx.to_html("v1.2", features: [replacements: [{~r/v(\d+)\.(\d+)/, "version $1.$2"}]]) == "<p>version 1.2</p>\n"
+    end
+
+    test "returns an error for invalid regexes" do
+      assert {:error, "invalid replacement pattern: " <> _} = MDEx.to_html("a", features: [replacements: [{~r/(?<=a)b/, ""}]])
+    end
+  end
+
+  describe "abbreviations" do
+    test "wraps abbreviations" do
+      markdown = """
+      The HTML spec, not XHTML, by the W3C.
+
+      `HTML`
+
+      *[HTML]: Hyper "Text" Markup Language
+      *[W3C]: World Wide Web Consortium
+      """
+
+      assert MDEx.to_html(markdown, features: [abbreviations: true]) == """
+             <p>The <abbr title="Hyper &quot;Text&quot; Markup Language">HTML</abbr> spec, not XHTML, by the <abbr title="World Wide Web Consortium">W3C</abbr>.</p>
+             <p><code>HTML</code></p>
+             """
+    end
+
+    test "keeps definitions in code blocks" do
+      assert MDEx.to_html("```\n*[HTML]: x\n```", features: [abbreviations: true, syntax_highlight_theme: nil]) ==
+               "<pre><code>*[HTML]: x\n</code></pre>\n"
+    end
+  end
+
+  describe "omit" do
+    test "removes node types with their content" do
+      markdown = """
+      # Title
+
+      Text ![alt](https://example.com/a.png) and `code`.
+
+      ![only](https://example.com/b.png)
+
+      <div>html</div>
+      """
+
+      assert MDEx.to_html(markdown, features: [omit: [:image, :html_block, :heading]], render: [unsafe_: true]) == """
+             <p>Text  and <code>code</code>.</p>
+             """
+    end
+
+    test "removes list items left empty" do
+      assert MDEx.to_html("- [link](https://example.com)\n- item\n", features: [omit: [:link]]) == """
+             <ul>
+             <li>item</li>
+             </ul>
+             """
+    end
+  end
+
+  describe "heading_lint" do
+    test "reports duplicate and long headings" do
+      markdown = """
+      ## Install
+
+      # Install
+
+      ## Install
+
+      ## A very long heading
+      """
+
+      assert {:ok, _html, %{warnings: warnings}} =
+               MDEx.to_html_with_metadata(markdown, features: [heading_lint: [max_length: 10]])
+
+      assert warnings == [
+               %{
+                 kind: :duplicate_heading,
+                 message: "heading Install duplicates the level 2 heading on line 1",
+                 sourcepos: {{5, 1}, {5, 10}}
+               },
+               %{
+                 kind: :long_heading,
+                 message: "heading is 19 characters long, over the limit of 10",
+                 sourcepos: {{7, 1}, {7, 22}}
+               }
+             ]
+    end
+
+    test "counts the heading length in the unit" do
+      for {unit, message} <- [
+            grapheme: nil,
+            char: "heading is 10 code points long, over the limit of 6",
+            byte: "heading is 24 bytes long, over the limit of 6"
+          ] do
+        {:ok, _html, %{warnings: warnings}} =
+          MDEx.to_html_with_metadata("# 👩‍👩‍👧 café", features: [heading_lint: [max_length: 6, unit: unit]])
+
+        assert Enum.map(warnings, & &1.message) == List.wrap(message)
+      end
+    end
+
+    test "reports nothing by default" do
+      assert {:ok, _html, %{warnings: []}} = MDEx.to_html_with_metadata("# A\n\n# A")
+    end
+  end
+
+  describe "internal_links" do
+    test "rewrites links to the site" do
+      markdown = """
+      [Guide](/guides "Guide") [Other](//example.com) [Ext](https://example.com) `/code` <a href="/raw">raw</a>
+      """
+
+      assert MDEx.to_html(markdown,
+               render: [unsafe_: true],
+               features: [
+                 internal_links: [path_prefix: "/docs/", attributes: ["data-phx-link": "redirect", "data-phx-link-state": "push"]]
+               ]
+             ) == """
+             <p><a href="/docs/guides" title="Guide" data-phx-link="redirect" data-phx-link-state="push">Guide</a> <a href="//example.com">Other</a> <a href="https://example.com">Ext</a> <code>/code</code> <a href="/raw">raw</a></p>
+             """
+    end
+
+    test "only prefixes the path without attributes" do
+      assert MDEx.to_html("[Guide](/guides)", features: [internal_links: [prefixes: ["/guides"], path_prefix: "/docs"]]) ==
+               ~s(<p><a href="/docs/guides">Guide</a></p>\n)
+    end
+  end
+
+  describe "render_options_fingerprint" do
+    test "resolves presets and defaults" do
+      fingerprint = MDEx.render_options_fingerprint([])
+
+      assert fingerprint =~ ~r/\A[0-9a-f]{16}\z/
+      assert MDEx.render_options_fingerprint(features: [syntax_highlight_theme: "onedark", cache: MDEx.new_cache()]) == fingerprint
+      refute MDEx.render_options_fingerprint(preset: :comments) == fingerprint
+
+      assert MDEx.render_options_fingerprint(preset: :comments, render: [unsafe_: true]) ==
+               MDEx.render_options_fingerprint(preset: :comments)
+    end
+
+    test "ignores the order of code block attributes" do
+      opts = [features: [code_block_attributes: [pre: Map.new(1..20, &{"data-#{&1}", "#{&1}"})]]]
+
+      # decoded into a new hash map with a random iteration order every call
+      assert MDEx.render_options_fingerprint(opts) == MDEx.render_options_fingerprint(opts)
+    end
+
+    test "changes with the MDEx version" do
+      options = %MDEx.Types.Options{}
+
+      refute MDEx.Native.render_options_fingerprint(options, "0.1.13") ==
+               MDEx.Native.render_options_fingerprint(options, "0.1.14")
+
+      assert MDEx.render_options_fingerprint([]) ==
+               MDEx.Native.render_options_fingerprint(options, Mix.Project.config()[:version])
+    end
+
+    test "returns errors for invalid options" do
+      assert {:error, {:unknown_theme, "nope", _}} = MDEx.render_options_fingerprint(features: [syntax_highlight_theme: "nope"])
+    end
+  end
+
+  describe "code_blocks_to_html" do
+    test "renders code blocks like to_html" do
+      markdown = """
+      Intro
+
+      ```elixir
+      {:mdex, "~> 0.1"}
+      ```
+
+      > ```
+      > nested
+      > ```
+      """
+
+      opts = [features: [code_block_attributes: [tabindex: 0]]]
+      html = MDEx.to_html(markdown, opts)
+
+      assert [{0, elixir}, {1, nested}] = MDEx.code_blocks_to_html(markdown, opts)
+      assert html =~ elixir
+      assert html =~ nested
+    end
+
+    test "follows the preset" do
+      assert MDEx.code_blocks_to_html("```\ncode\n```", preset: :comments) == []
+    end
+  end
+
+  describe "to_json_ast" do
+    test "serializes nodes with their fields" do
+      markdown = """
+      1. [x] [Link](https://example.com "Title")
+
+      | a |
+      |:-:|
+      """
+
+      json = MDEx.to_json_ast(markdown, extension: [tasklist: true, table: true])
+
+      assert json =~ ~s("type":"list","list_type":"ordered","start":1,"delimiter":"period","bullet_char":null,"tight":true)
+      assert json =~ ~s("type":"task_item","checked":true)
+      assert json =~ ~s("type":"link","url":"https://example.com","title":"Title")
+      assert json =~ ~s("type":"table","alignments":["center"])
+    end
+
+    test "checks the input limits" do
+      assert MDEx.to_json_ast("abc", features: [max_input_bytes: 2]) == {:error, :input_too_large}
+    end
+  end
+
+  describe "diff_words" do
+    test "marks the changed words of paired lines" do
+      markdown = """
+      ```diff
+      -let x = foo(1);
+      +let x = bar(1, 2);
+      ```
+      """
+
+      html = MDEx.to_html(markdown, features: [diff_words: true])
+
+      assert html =~ ~s(<span class="diff-change">foo</span>)
+      assert html =~ ~s(<span class="diff-change">bar</span>)
+      assert html =~ ~s(<span class="diff-change">, 2</span>)
+      refute MDEx.to_html(markdown) =~ "diff-change"
+    end
+
+    test "skips runs with different number of lines" do
+      markdown = """
+      ```diff
+      -one
+      -two
+      +three
+      ```
+      """
+
+      refute MDEx.to_html(markdown, features: [diff_words: true]) =~ "diff-change"
+    end
+  end
+
+  describe "parse_front_matter" do
+    test "decodes toml and json" do
+      assert {:ok, %{"title" => "MDEx", "draft" => false}, "<p>text</p>\n"} =
+               MDEx.parse_front_matter("+++\ntitle = \"MDEx\"\ndraft = false\n+++\ntext")
+
+      assert {:ok, %{"weight" => 1.5, "author" => nil}, "<p>text</p>\n"} =
+               MDEx.parse_front_matter(";;;\n{\"weight\": 1.5, \"author\": null}\n;;;\ntext")
+    end
+
+    test "decodes empty front matter to an empty map" do
+      assert {:ok, %{}, "<p>text</p>\n"} = MDEx.parse_front_matter("---\n---\ntext")
+    end
+
+    test "pins heading ids from the front matter" do
+      markdown = "---\nheading_ids:\n  Intro: start\n---\n# Intro"
+
+      assert {:ok, %{"heading_ids" => %{"Intro" => "start"}}, html} =
+               MDEx.parse_front_matter(markdown, features: [heading_ids: [source: :front_matter_map]])
+
+      assert html =~ ~s(id="start")
+    end
+
+    test "returns an error for invalid front matter" do
+      assert {:error, "invalid front matter: " <> _} = MDEx.parse_front_matter("---\n[a, b]\n---\ntext")
+      assert {:error, "invalid front matter: " <> _} = MDEx.parse_front_matter(";;;\n{\n;;;\ntext")
+    end
+  end
+
+  describe "collect_ids" do
+    test "returns the ids of the output" do
+      markdown = """
+      # Intro
+
+      Text[^1] <span id="raw">x</span> <!-- <a id="comment"> -->
+
+      # Intro
+
+      [^1]: Note
+      """
+
+      opts = [extension: [header_ids: "", footnotes: true], render: [unsafe_: true], features: [collect_ids: true, id_prefix: "p-"]]
+
+      assert {:ok, _html, %{ids: ["p-intro", "p-fnref1", "raw", "p-intro-1", "p-fn1"]}} = MDEx.to_html_with_metadata(markdown, opts)
+    end
+
+    test "is nil unless enabled" do
+      assert {:ok, _html, %{ids: nil}} = MDEx.to_html_with_metadata("# Intro", extension: [header_ids: ""])
+    end
+  end
+
+  describe "html_to_markdown" do
+    test "converts lists, code blocks and tables" do
+      html = """
+      <ul>
+        <li>one</li>
+        <li>two <code>mix test</code>
+          <ol start="3"><li>nested</li></ol>
+        </li>
+      </ul>
+      <pre><code class="language-elixir">IO.puts(1 &lt; 2)
+      </code></pre>
+      <table>
+        <thead><tr><th>Name</th><th style="text-align: right">Stars</th></tr></thead>
+        <tbody><tr><td>mdex</td><td>100</td></tr></tbody>
+      </table>
+      """
+
+      markdown = MDEx.html_to_markdown(html)
+
+      assert markdown =~ "- one\n- two `mix test`\n  3. nested\n"
+      assert markdown =~ ~r/``` ?elixir\nIO.puts\(1 < 2\)\n```\n/
+      assert markdown =~ "| Name | Stars |\n"
+      assert markdown =~ "| mdex | 100 |\n"
+    end
+
+    test "collapses whitespace outside of emphasis" do
+      assert MDEx.html_to_markdown("<p>Hello   <b>bold </b>world,<i> it</i>alic</p>") == "Hello **bold** world, *it*alic\n"
+    end
+
+    test "removes scripts, styles and comments" do
+      assert MDEx.html_to_markdown("<style>p {}</style><p>text<!-- note --><script>alert('<p>')</script></p>") == "text\n"
+    end
+
+    test "escapes markdown in the text" do
+      assert MDEx.html_to_markdown("<p>*not emphasis*</p>") == "\\*not emphasis\\*\n"
+    end
+
+    test "replaces elements nested too deep by their content" do
+      html = String.duplicate("<div>", 100_000) <> "deep" <> String.duplicate("</div>", 100_000)
+      assert MDEx.html_to_markdown(html) == "deep\n"
+
+      html = "<blockquote><blockquote><blockquote>a</blockquote></blockquote></blockquote><p>b</p>"
+      assert MDEx.html_to_markdown(html, features: [input_limits: [max_nesting_depth: 2]]) == "> > a\n\nb\n"
+    end
+
+    test "applies the size limits" do
+      assert MDEx.html_to_markdown("<p>text</p>", features: [max_input_bytes: 5]) == {:error, :input_too_large}
+      assert MDEx.html_to_markdown("<p>text</p>", features: [max_output_bytes: 2]) == {:error, :output_too_large}
+      assert MDEx.html_to_markdown(String.duplicate("<p>a</p>", 200_000), preset: :hardened) == {:error, :input_too_large}
+    end
+  end
+
+  describe "markdown_to_html_batch" do
+    test "returns the results in the order of the documents" do
+      documents = for n <- 1..50, do: String.duplicate("# Title #{n}\n\ntext\n", n)
+
+      assert MDEx.markdown_to_html_batch(documents, max_concurrency: 4) == Enum.map(documents, &MDEx.to_html/1)
+    end
+
+    test "returns errors for each document" do
+      assert MDEx.markdown_to_html_batch(["# ok", String.duplicate("a", 100)], features: [max_input_bytes: 10]) == [
+               "<h1>ok</h1>\n",
+               {:error, :input_too_large}
+             ]
+    end
+
+    test "renders sequentially with max_concurrency 1" do
+      assert MDEx.markdown_to_html_batch(["*a*", "**b**"], max_concurrency: 1) == ["<p><em>a</em></p>\n", "<p><strong>b</strong></p>\n"]
+    end
+  end
+
+  describe "verify_roundtrip" do
+    test "ignores syntax and escaping differences" do
+      markdown = """
+      Title
+      -----
+
+          indented code
+
+      + a *b* \\[c] `d`
+      + e &amp; f
+      """
+
+      assert {:ok, %{equivalent: true, path: nil}} = MDEx.verify_roundtrip(markdown)
+    end
+
+    test "ignores reflowed paragraphs" do
+      assert {:ok, %{equivalent: true, markdown: markdown}} =
+               MDEx.verify_roundtrip("one two three four five six", render: [width: 10])
+
+      assert length(String.split(markdown, "\n", trim: true)) > 1
+    end
+
+    test "reports the path of the first node that differs" do
+      assert {:ok, %{equivalent: false, path: [0, 1], expected: "list", actual: "html_block"}} =
+               MDEx.verify_roundtrip("> - a\n>\n> + b")
+    end
+
+    test "returns errors" do
+      assert MDEx.verify_roundtrip("abc", features: [max_input_bytes: 2]) == {:error, :input_too_large}
+    end
+  end
+
+  describe "to_ansi" do
+    test "styles inlines" do
+      assert MDEx.to_ansi("**bold** `code` [link](https://hexdocs.pm) <https://elixir-lang.org>") ==
+               "\e[1mbold\e[0m \e[36mcode\e[0m \e[4m\e[34mlink\e[0m\e[2m (https://hexdocs.pm)\e[0m \e[4m\e[34mhttps://elixir-lang.org\e[0m\n"
+    end
+
+    test "separates blocks and aligns tables" do
+      markdown = """
+      1. one
+      2. two
+
+      | name | stars |
+      |------|------:|
+      | mdex | 9 |
+      """
+
+      assert MDEx.to_ansi(markdown, extension: [table: true]) ==
+               "1. one\n2. two\n\nname \e[2m│\e[0m stars\n\e[2m─────┼──────\e[0m\nmdex \e[2m│\e[0m     9\n"
+    end
+
+    test "highlights code blocks with the theme" do
+      markdown = "```elixir\ndefmodule MDEx do\nend\n```"
+
+      assert MDEx.to_ansi(markdown) =~ "\e[38;2;"
+      assert MDEx.to_ansi(markdown, features: [syntax_highlight_theme: nil]) == "  defmodule MDEx do\n  end\n"
+    end
+
+    test "returns errors" do
+      assert {:error, {:unknown_theme, "nope", _}} = MDEx.to_ansi("# MDEx", features: [syntax_highlight_theme: "nope"])
+    end
+  end
+
+  describe "broken_link_resolver" do
+    test "resolves references from a url template" do
+      assert MDEx.to_html("See [Some  Page] and [the guide][Getting Started]", features: [broken_link_resolver: "/wiki/{ref}"]) ==
+               "<p>See <a href=\"/wiki/some%20page\">Some  Page</a> and <a href=\"/wiki/getting%20started\">the guide</a></p>\n"
+    end
+
+    test "resolves references from a map of labels" do
+      opts = [features: [broken_link_resolver: %{"Install" => "/docs/install", usage: "/docs/usage"}]]
+
+      assert MDEx.to_html("[install] [Usage][] [missing]", opts) ==
+               "<p><a href=\"/docs/install\">install</a> <a href=\"/docs/usage\">Usage</a> [missing]</p>\n"
+    end
+
+    test "keeps defined references" do
+      assert MDEx.to_html("[page]\n\n[page]: /defined", features: [broken_link_resolver: "/wiki/{ref}"]) ==
+               "<p><a href=\"/defined\">page</a></p>\n"
+    end
   end
 end